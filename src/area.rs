@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{Error, Result};
+
+/// inclusive box of block positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BoundingBox {
+    pub min: [i32; 3],
+    pub max: [i32; 3],
+}
+
+impl BoundingBox {
+    /// box spanning both corners, in any order
+    pub fn new(a: [i32; 3], b: [i32; 3]) -> Self {
+        Self {
+            min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+        }
+    }
+
+    pub fn size(&self) -> [i32; 3] {
+        [
+            self.max[0] - self.min[0] + 1,
+            self.max[1] - self.min[1] + 1,
+            self.max[2] - self.min[2] + 1,
+        ]
+    }
+
+    pub fn contains(&self, pos: [i32; 3]) -> bool {
+        (0..3).all(|i| self.min[i] <= pos[i] && pos[i] <= self.max[i])
+    }
+
+    pub fn intersection(&self, other: &BoundingBox) -> Option<BoundingBox> {
+        let min = [0, 1, 2].map(|i| self.min[i].max(other.min[i]));
+        let max = [0, 1, 2].map(|i| self.max[i].min(other.max[i]));
+        (0..3)
+            .all(|i| min[i] <= max[i])
+            .then_some(BoundingBox { min, max })
+    }
+
+    pub fn contains_box(&self, other: &BoundingBox) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// chunks the box touches
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> {
+        let (min, max) = (self.min, self.max);
+        (min[2] >> 4..=max[2] >> 4)
+            .flat_map(move |z| (min[0] >> 4..=max[0] >> 4).map(move |x| (x, z)))
+    }
+
+    /// the 16x16 column of chunk x z between `min_y` and `max_y`
+    pub fn chunk_column(x: i32, z: i32, min_y: i32, max_y: i32) -> Self {
+        Self {
+            min: [x * 16, min_y, z * 16],
+            max: [x * 16 + 15, max_y, z * 16 + 15],
+        }
+    }
+
+    /// the 16x16x16 section at chunk x z, section y
+    pub fn section(x: i32, y: i32, z: i32) -> Self {
+        Self {
+            min: [x * 16, y * 16, z * 16],
+            max: [x * 16 + 15, y * 16 + 15, z * 16 + 15],
+        }
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = Error;
+
+    /// parses `x1,y1,z1:x2,y2,z2`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Custom(format!("invalid bounding box {s}, expected x,y,z:x,y,z"));
+        let (a, b) = s.split_once(':').ok_or_else(invalid)?;
+        let corner = |c: &str| -> Result<[i32; 3]> {
+            let v = c
+                .split(',')
+                .map(|e| e.trim().parse::<i32>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>>>()?;
+            v.try_into().map_err(|_| invalid())
+        };
+        Ok(Self::new(corner(a)?, corner(b)?))
+    }
+}
+
+/// the part of a dimension an operation is limited to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Area {
+    Dimension,
+    Chunks(Vec<(i32, i32)>),
+    Box(BoundingBox),
+}
+
+impl Area {
+    pub fn contains_chunk(&self, x: i32, z: i32) -> bool {
+        match self {
+            Area::Dimension => true,
+            Area::Chunks(chunks) => chunks.contains(&(x, z)),
+            Area::Box(b) => b
+                .intersection(&BoundingBox::chunk_column(x, z, b.min[1], b.max[1]))
+                .is_some(),
+        }
+    }
+
+    pub fn contains_region(&self, x: i32, z: i32) -> bool {
+        match self {
+            Area::Dimension => true,
+            Area::Chunks(chunks) => chunks.iter().any(|e| e.0 >> 5 == x && e.1 >> 5 == z),
+            Area::Box(b) => {
+                b.min[0] >> 9 <= x && x <= b.max[0] >> 9 && b.min[2] >> 9 <= z && z <= b.max[2] >> 9
+            }
+        }
+    }
+
+    /// the blocks of `section` inside the area, `None` when it lies outside
+    pub fn clip(&self, section: &BoundingBox) -> Option<BoundingBox> {
+        match self {
+            Area::Box(b) => b.intersection(section),
+            _ => self
+                .contains_chunk(section.min[0] >> 4, section.min[2] >> 4)
+                .then_some(*section),
+        }
+    }
+}
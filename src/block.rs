@@ -0,0 +1,332 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::Serialize;
+
+use crate::{Error, Map, NbtList, NbtValue, Result};
+
+pub const SECTION_BLOCKS: usize = 4096;
+pub const SECTION_BIOMES: usize = 64;
+
+/// a block with its properties, sorted by key so equal states compare equal
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct BlockState {
+    pub name: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl BlockState {
+    /// `minecraft:` is assumed when `name` has no namespace
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: namespaced(name),
+            properties: vec![],
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: &str) -> Self {
+        self.set_property(key, value);
+        self
+    }
+
+    pub fn set_property(&mut self, key: &str, value: &str) {
+        match self.properties.binary_search_by(|e| e.0.as_str().cmp(key)) {
+            Ok(i) => self.properties[i].1 = value.to_string(),
+            Err(i) => self
+                .properties
+                .insert(i, (key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|e| e.0 == key)
+            .map(|e| e.1.as_str())
+    }
+
+    pub fn is_air(&self) -> bool {
+        matches!(
+            self.name.as_str(),
+            "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+        )
+    }
+
+    /// reads a palette entry, `{Name, Properties}`
+    pub fn from_nbt(nbt: &NbtValue) -> Option<Self> {
+        let mut state = Self {
+            name: nbt.get("Name")?.as_str()?.to_string(),
+            properties: vec![],
+        };
+        if let Some(properties) = nbt.get("Properties").and_then(NbtValue::as_compound) {
+            for (k, v) in properties.iter() {
+                state.set_property(k, v.as_str()?);
+            }
+        }
+        Some(state)
+    }
+
+    pub fn to_nbt(&self) -> NbtValue {
+        let mut nbt = Map::new();
+        nbt.insert("Name".to_string(), NbtValue::String(self.name.clone()));
+        if !self.properties.is_empty() {
+            nbt.insert(
+                "Properties".to_string(),
+                NbtValue::Compound(Map::from(
+                    self.properties
+                        .iter()
+                        .map(|(k, v)| (k.clone(), NbtValue::String(v.clone()))),
+                )),
+            );
+        }
+        NbtValue::Compound(nbt)
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = Error;
+
+    /// parses `name[key=value,...]`
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, properties) = match s.split_once('[') {
+            Some((name, rest)) => (
+                name,
+                rest.strip_suffix(']')
+                    .ok_or_else(|| Error::Custom(format!("invalid block state {s}")))?,
+            ),
+            None => (s, ""),
+        };
+        if name.is_empty() {
+            return Err(Error::Custom(format!("invalid block state {s}")));
+        }
+        let mut state = BlockState::new(name.trim());
+        for property in properties.split(',').filter(|e| !e.trim().is_empty()) {
+            let (k, v) = property
+                .split_once('=')
+                .ok_or_else(|| Error::Custom(format!("invalid block state {s}")))?;
+            state.set_property(k.trim(), v.trim());
+        }
+        Ok(state)
+    }
+}
+
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if !self.properties.is_empty() {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// matches block states by name and the listed properties, other properties are ignored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockPattern(pub BlockState);
+
+impl BlockPattern {
+    pub fn matches(&self, state: &BlockState) -> bool {
+        self.0.name == state.name
+            && self
+                .0
+                .properties
+                .iter()
+                .all(|(k, v)| state.property(k) == Some(v))
+    }
+}
+
+impl FromStr for BlockPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self(s.parse()?))
+    }
+}
+
+pub(crate) fn namespaced(id: &str) -> String {
+    if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    }
+}
+
+/// palette plus one index per entry, the way sections store blocks and biomes
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettedContainer<T> {
+    pub palette: Vec<T>,
+    pub indices: Vec<u16>,
+}
+
+impl<T: PartialEq + Clone> PalettedContainer<T> {
+    pub fn filled(value: T, size: usize) -> Self {
+        Self {
+            palette: vec![value],
+            indices: vec![0; size],
+        }
+    }
+
+    /// unpacks `data`, entries never span two longs
+    pub fn unpack(
+        palette: Vec<T>,
+        data: Option<&[i64]>,
+        size: usize,
+        min_bits: u32,
+    ) -> Result<Self> {
+        let bits = Self::bits(palette.len(), min_bits);
+        let mut indices = vec![0; size];
+        if bits > 0 {
+            let data = data.ok_or(Error::Malformed(line!()))?;
+            let per_long = 64 / bits as usize;
+            if data.len() < size.div_ceil(per_long) {
+                return Err(Error::Malformed(line!()));
+            }
+            let mask = (1u64 << bits) - 1;
+            for (i, index) in indices.iter_mut().enumerate() {
+                let long = data[i / per_long] as u64;
+                *index = ((long >> ((i % per_long) as u32 * bits)) & mask) as u16;
+                if *index as usize >= palette.len() {
+                    return Err(Error::Malformed(line!()));
+                }
+            }
+        }
+        Ok(Self { palette, indices })
+    }
+
+    /// packs the indices, `None` when the palette has a single entry
+    pub fn pack(&self, min_bits: u32) -> Option<Vec<i64>> {
+        let bits = Self::bits(self.palette.len(), min_bits);
+        if bits == 0 {
+            return None;
+        }
+        let per_long = 64 / bits as usize;
+        let mut data = vec![0i64; self.indices.len().div_ceil(per_long)];
+        for (i, index) in self.indices.iter().enumerate() {
+            data[i / per_long] |= ((*index as u64) << ((i % per_long) as u32 * bits)) as i64;
+        }
+        Some(data)
+    }
+
+    fn bits(len: usize, min_bits: u32) -> u32 {
+        if len <= 1 {
+            0
+        } else {
+            (usize::BITS - (len - 1).leading_zeros()).max(min_bits)
+        }
+    }
+
+    pub fn get(&self, i: usize) -> &T {
+        &self.palette[self.indices[i] as usize]
+    }
+
+    pub fn set(&mut self, i: usize, value: T) {
+        let index = match self.palette.iter().position(|e| *e == value) {
+            Some(index) => index,
+            None => {
+                self.palette.push(value);
+                self.palette.len() - 1
+            }
+        };
+        self.indices[i] = index as u16;
+    }
+
+    /// drops unused and duplicate palette entries
+    pub fn compact(&mut self) {
+        let mut palette: Vec<T> = vec![];
+        let mut remap = vec![None; self.palette.len()];
+        for index in self.indices.iter_mut() {
+            let new = *remap[*index as usize].get_or_insert_with(|| {
+                let value = &self.palette[*index as usize];
+                match palette.iter().position(|e| e == value) {
+                    Some(i) => i,
+                    None => {
+                        palette.push(value.clone());
+                        palette.len() - 1
+                    }
+                }
+            });
+            *index = new as u16;
+        }
+        self.palette = palette;
+    }
+}
+
+/// index of a block inside a section
+pub fn section_index(x: i32, y: i32, z: i32) -> usize {
+    ((y & 15) * 256 + (z & 15) * 16 + (x & 15)) as usize
+}
+
+/// reads the `block_states` of a 1.18+ section
+pub fn section_blocks(
+    section: &Map<String, NbtValue>,
+) -> Result<Option<PalettedContainer<BlockState>>> {
+    let Some(states) = section.get("block_states") else {
+        return Ok(None);
+    };
+    let palette = states
+        .get("palette")
+        .and_then(NbtValue::as_list)
+        .ok_or(Error::Malformed(line!()))?
+        .compounds()
+        .iter()
+        .map(|e| BlockState::from_nbt(&NbtValue::Compound(e.clone())))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::Malformed(line!()))?;
+    let data = match states.get("data") {
+        Some(NbtValue::LongArray(v)) => Some(v.as_slice()),
+        _ => None,
+    };
+    PalettedContainer::unpack(palette, data, SECTION_BLOCKS, 4).map(Some)
+}
+
+pub fn palette_to_nbt(palette: &[BlockState]) -> NbtValue {
+    NbtValue::List(NbtList::CompoundList(
+        palette
+            .iter()
+            .filter_map(|e| e.to_nbt().as_compound().cloned())
+            .collect(),
+    ))
+}
+
+pub fn blocks_to_nbt(blocks: &PalettedContainer<BlockState>) -> NbtValue {
+    let mut nbt = Map::new();
+    nbt.insert("palette".to_string(), palette_to_nbt(&blocks.palette));
+    if let Some(data) = blocks.pack(4) {
+        nbt.insert("data".to_string(), NbtValue::LongArray(data));
+    }
+    NbtValue::Compound(nbt)
+}
+
+/// reads the `biomes` of a 1.18+ section
+pub fn section_biomes(
+    section: &Map<String, NbtValue>,
+) -> Result<Option<PalettedContainer<String>>> {
+    let Some(biomes) = section.get("biomes") else {
+        return Ok(None);
+    };
+    let palette = match biomes.get("palette") {
+        Some(NbtValue::List(NbtList::StringList(v))) => v.clone(),
+        _ => return Err(Error::Malformed(line!())),
+    };
+    let data = match biomes.get("data") {
+        Some(NbtValue::LongArray(v)) => Some(v.as_slice()),
+        _ => None,
+    };
+    PalettedContainer::unpack(palette, data, SECTION_BIOMES, 1).map(Some)
+}
+
+pub fn biomes_to_nbt(biomes: &PalettedContainer<String>) -> NbtValue {
+    let mut nbt = Map::new();
+    nbt.insert(
+        "palette".to_string(),
+        NbtValue::List(NbtList::StringList(biomes.palette.clone())),
+    );
+    if let Some(data) = biomes.pack(1) {
+        nbt.insert("data".to_string(), NbtValue::LongArray(data));
+    }
+    NbtValue::Compound(nbt)
+}
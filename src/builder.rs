@@ -0,0 +1,419 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    hash::{BuildHasher, RandomState},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    block::BlockState,
+    region::RegionFile,
+    world::{unix_time, Dimension, RegionKind, World},
+    Error, Map, NbtFile, NbtList, NbtValue, Result,
+};
+
+/// data version of the chunks and level.dat written here, 1.21.4
+pub const DATA_VERSION: i32 = 4189;
+
+/// the release with data version `data_version`, snapshots and unknown versions have none
+pub fn version_name(data_version: i32) -> Option<&'static str> {
+    Some(match data_version {
+        2586 => "1.16.5",
+        2724 => "1.17",
+        2730 => "1.17.1",
+        2860 => "1.18",
+        2865 => "1.18.1",
+        2975 => "1.18.2",
+        3105 => "1.19",
+        3117 => "1.19.1",
+        3120 => "1.19.2",
+        3218 => "1.19.3",
+        3337 => "1.19.4",
+        3463 => "1.20",
+        3465 => "1.20.1",
+        3578 => "1.20.2",
+        3698 => "1.20.3",
+        3700 => "1.20.4",
+        3837 => "1.20.5",
+        3839 => "1.20.6",
+        3953 => "1.21",
+        3955 => "1.21.1",
+        4080 => "1.21.2",
+        4082 => "1.21.3",
+        4189 => "1.21.4",
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    /// the `GameType` stored in level.dat
+    pub fn id(&self) -> i32 {
+        match self {
+            GameMode::Survival => 0,
+            GameMode::Creative => 1,
+            GameMode::Adventure => 2,
+            GameMode::Spectator => 3,
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "survival" => Ok(GameMode::Survival),
+            "creative" => Ok(GameMode::Creative),
+            "adventure" => Ok(GameMode::Adventure),
+            "spectator" => Ok(GameMode::Spectator),
+            _ => Err(Error::Custom(format!("unknown game mode {s}"))),
+        }
+    }
+}
+
+/// how the game generates chunks that weren't written beforehand
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    /// the normal terrain of each dimension
+    Default,
+    /// layers from the bottom of the world up, with how many blocks each is thick
+    Flat {
+        layers: Vec<(BlockState, u32)>,
+        biome: String,
+    },
+    /// nothing but air
+    Void,
+    /// generator settings as they go into `WorldGenSettings.dimensions.<dimension>.generator`
+    Custom(NbtValue),
+}
+
+impl Generator {
+    /// the `generator` compound for `dim`, flat and void worlds only change the overworld
+    pub fn to_nbt(&self, dim: Dimension) -> NbtValue {
+        let string = |s: &str| NbtValue::String(s.to_string());
+        let compound = |entries: Vec<(&str, NbtValue)>| {
+            NbtValue::Compound(Map::from(
+                entries.into_iter().map(|(k, v)| (k.to_string(), v)),
+            ))
+        };
+        let flat = |layers: Vec<Map<String, NbtValue>>, biome: &str| {
+            compound(vec![
+                ("type", string("minecraft:flat")),
+                (
+                    "settings",
+                    compound(vec![
+                        ("layers", NbtValue::List(NbtList::CompoundList(layers))),
+                        ("biome", string(biome)),
+                        ("features", NbtValue::Byte(0)),
+                        ("lakes", NbtValue::Byte(0)),
+                        ("structure_overrides", NbtValue::List(NbtList::EmptyList())),
+                    ]),
+                ),
+            ])
+        };
+        match (self, dim) {
+            (Generator::Flat { layers, biome }, Dimension::Overworld) => flat(
+                layers
+                    .iter()
+                    .map(|(block, height)| {
+                        Map::from([
+                            ("block".to_string(), string(&block.name)),
+                            ("height".to_string(), NbtValue::Int(*height as i32)),
+                        ])
+                    })
+                    .collect(),
+                biome,
+            ),
+            (Generator::Void, Dimension::Overworld) => flat(
+                vec![Map::from([
+                    ("block".to_string(), string("minecraft:air")),
+                    ("height".to_string(), NbtValue::Int(1)),
+                ])],
+                "minecraft:the_void",
+            ),
+            (Generator::Custom(nbt), Dimension::Overworld) => nbt.clone(),
+            (_, Dimension::End) => compound(vec![
+                ("type", string("minecraft:noise")),
+                ("settings", string("minecraft:end")),
+                (
+                    "biome_source",
+                    compound(vec![("type", string("minecraft:the_end"))]),
+                ),
+            ]),
+            (_, dim) => {
+                let preset = match dim {
+                    Dimension::Nether => "nether",
+                    _ => "overworld",
+                };
+                compound(vec![
+                    ("type", string("minecraft:noise")),
+                    ("settings", string(&format!("minecraft:{preset}"))),
+                    (
+                        "biome_source",
+                        compound(vec![
+                            ("type", string("minecraft:multi_noise")),
+                            ("preset", string(&format!("minecraft:{preset}"))),
+                        ]),
+                    ),
+                ])
+            }
+        }
+    }
+}
+
+/// writes a new world directory, anything not set is what the game picks for a new world
+#[derive(Debug, Clone)]
+pub struct WorldBuilder {
+    name: String,
+    seed: i64,
+    game_mode: GameMode,
+    generator: Generator,
+    data_version: i32,
+    version_name: Option<String>,
+    spawn: [i32; 3],
+    hardcore: bool,
+    allow_commands: bool,
+    game_rules: Vec<(String, String)>,
+    chunks: Vec<(Dimension, RegionKind, i32, i32, NbtValue)>,
+    pregenerate: Option<((i32, i32), (i32, i32))>,
+}
+
+impl WorldBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            // like the game, a random seed unless one is given
+            seed: RandomState::new().hash_one(unix_time()) as i64,
+            game_mode: GameMode::Survival,
+            generator: Generator::Default,
+            data_version: DATA_VERSION,
+            version_name: None,
+            spawn: [0, 64, 0],
+            hardcore: false,
+            allow_commands: false,
+            game_rules: vec![],
+            chunks: vec![],
+            pregenerate: None,
+        }
+    }
+
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = game_mode;
+        self
+    }
+
+    pub fn generator(mut self, generator: Generator) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    /// written to level.dat, the game upgrades worlds with an older one when they're opened
+    pub fn data_version(mut self, data_version: i32) -> Self {
+        self.data_version = data_version;
+        self
+    }
+
+    /// the version name shown in the world list, by default the release of the data version
+    /// or none at all when it isn't known
+    pub fn version_name(mut self, name: impl Into<String>) -> Self {
+        self.version_name = Some(name.into());
+        self
+    }
+
+    pub fn spawn(mut self, spawn: [i32; 3]) -> Self {
+        self.spawn = spawn;
+        self
+    }
+
+    pub fn hardcore(mut self, hardcore: bool) -> Self {
+        self.hardcore = hardcore;
+        self
+    }
+
+    pub fn allow_commands(mut self, allow_commands: bool) -> Self {
+        self.allow_commands = allow_commands;
+        self
+    }
+
+    /// game rules are stored as strings, `true`, `false` or a number
+    pub fn game_rule(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.game_rules.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// a chunk written along with the world, at absolute chunk coordinates
+    pub fn chunk(
+        mut self,
+        dim: Dimension,
+        kind: RegionKind,
+        x: i32,
+        z: i32,
+        nbt: NbtValue,
+    ) -> Self {
+        self.chunks.push((dim, kind, x, z, nbt));
+        self
+    }
+
+    /// writes the overworld chunks between corners `min` and `max` with a flat or void
+    /// generator, chunks given with [`WorldBuilder::chunk`] are kept
+    pub fn pregenerate(mut self, min: (i32, i32), max: (i32, i32)) -> Self {
+        self.pregenerate = Some((min, max));
+        self
+    }
+
+    /// the `Data` compound of level.dat
+    pub fn level_data(&self) -> NbtValue {
+        let string = |s: &str| NbtValue::String(s.to_string());
+        let byte = |b: bool| NbtValue::Byte(b as i8);
+        let dimensions = Dimension::ALL
+            .into_iter()
+            .map(|dim| {
+                let kind = dim.id().to_string();
+                (
+                    kind.clone(),
+                    NbtValue::Compound(Map::from([
+                        ("type".to_string(), NbtValue::String(kind)),
+                        ("generator".to_string(), self.generator.to_nbt(dim)),
+                    ])),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut version = Map::from([
+            ("Id".to_string(), NbtValue::Int(self.data_version)),
+            ("Series".to_string(), string("main")),
+            ("Snapshot".to_string(), byte(false)),
+        ]);
+        let name = self.version_name.as_deref();
+        if let Some(name) = name.or(version_name(self.data_version)) {
+            version.insert("Name".to_string(), string(name));
+        }
+        let data = [
+            ("DataVersion", NbtValue::Int(self.data_version)),
+            ("Version", NbtValue::Compound(version)),
+            // the anvil format
+            ("version", NbtValue::Int(19133)),
+            ("LevelName", string(&self.name)),
+            ("GameType", NbtValue::Int(self.game_mode.id())),
+            ("hardcore", byte(self.hardcore)),
+            ("allowCommands", byte(self.allow_commands)),
+            (
+                "Difficulty",
+                NbtValue::Byte(if self.hardcore { 3 } else { 2 }),
+            ),
+            ("initialized", byte(true)),
+            ("LastPlayed", NbtValue::Long(unix_time() as i64 * 1000)),
+            ("SpawnX", NbtValue::Int(self.spawn[0])),
+            ("SpawnY", NbtValue::Int(self.spawn[1])),
+            ("SpawnZ", NbtValue::Int(self.spawn[2])),
+            ("SpawnAngle", NbtValue::Float(0.0)),
+            ("Time", NbtValue::Long(0)),
+            ("DayTime", NbtValue::Long(0)),
+            ("raining", byte(false)),
+            ("thundering", byte(false)),
+            ("BorderCenterX", NbtValue::Double(0.0)),
+            ("BorderCenterZ", NbtValue::Double(0.0)),
+            ("BorderSize", NbtValue::Double(59999968.0)),
+            (
+                "GameRules",
+                NbtValue::Compound(Map::from(
+                    self.game_rules.iter().map(|(k, v)| (k.clone(), string(v))),
+                )),
+            ),
+            (
+                "DataPacks",
+                NbtValue::Compound(Map::from([
+                    (
+                        "Enabled".to_string(),
+                        NbtValue::List(NbtList::StringList(vec!["vanilla".to_string()])),
+                    ),
+                    ("Disabled".to_string(), NbtValue::List(NbtList::EmptyList())),
+                ])),
+            ),
+            (
+                "WorldGenSettings",
+                NbtValue::Compound(Map::from([
+                    ("seed".to_string(), NbtValue::Long(self.seed)),
+                    (
+                        "generate_features".to_string(),
+                        byte(self.generator == Generator::Default),
+                    ),
+                    ("bonus_chest".to_string(), byte(false)),
+                    (
+                        "dimensions".to_string(),
+                        NbtValue::Compound(Map::from(dimensions)),
+                    ),
+                ])),
+            ),
+        ];
+        NbtValue::Compound(Map::from(data.map(|(k, v)| (k.to_string(), v))))
+    }
+
+    /// writes the world to `path`, which must not hold a world already
+    pub fn create(mut self, path: impl AsRef<Path>) -> Result<World> {
+        let path = path.as_ref();
+        if path.join("level.dat").exists() {
+            return Err(Error::Custom(format!(
+                "{} already contains a world",
+                path.display()
+            )));
+        }
+        if let Some((min, max)) = self.pregenerate {
+            let dim = Dimension::Overworld;
+            for z in min.1.min(max.1)..=min.1.max(max.1) {
+                for x in min.0.min(max.0)..=min.0.max(max.0) {
+                    if self
+                        .chunks
+                        .iter()
+                        .any(|e| (e.0, e.1, e.2, e.3) == (dim, RegionKind::Chunks, x, z))
+                    {
+                        continue;
+                    }
+                    let nbt = self.generator.chunk(x, z, dim)?.ok_or_else(|| {
+                        Error::Custom("only flat and void worlds can be pregenerated".to_string())
+                    })?;
+                    self.chunks.push((dim, RegionKind::Chunks, x, z, nbt));
+                }
+            }
+        }
+        std::fs::create_dir_all(path).map_err(Error::FileError)?;
+        let level_dat = NbtFile {
+            name: String::new(),
+            nbt: NbtValue::Compound(Map::from([("Data".to_string(), self.level_data())])),
+        };
+        let file = File::create(path.join("level.dat")).map_err(Error::FileError)?;
+        level_dat
+            .to_compressed_writer(file)
+            .map_err(Error::FileError)?;
+
+        let mut regions = BTreeMap::<PathBuf, RegionFile>::new();
+        for (dim, kind, x, z, nbt) in self.chunks.iter() {
+            let region_path = dim
+                .dir(path)
+                .join(kind.dir_name())
+                .join(crate::region::region_file_name(x >> 5, z >> 5));
+            regions
+                .entry(region_path)
+                .or_default()
+                .write_chunk(*x, *z, nbt, unix_time())?;
+        }
+        for (region_path, region) in regions {
+            std::fs::create_dir_all(region_path.parent().unwrap()).map_err(Error::FileError)?;
+            region.save(&region_path)?;
+        }
+        World::open(path)
+    }
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashSet, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    region::{self, RegionFile, COMPRESSION_CUSTOM, COMPRESSION_ZLIB, DEFAULT_LEVEL},
+    transaction,
+    world::{Dimension, RegionKind, World},
+    Error, Result,
+};
+
+/// how chunks are recompressed while compacting, `None` keeps what is there; chunks in lz4,
+/// which can't be written, get zlib like the game uses by default
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CompactOptions {
+    pub compression: Option<u8>,
+    pub level: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct CompactReport {
+    pub regions: usize,
+    pub chunks_recompressed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+}
+
+/// rewrites a region file with its chunks packed in slot order, the new file replaces the old
+/// one atomically and `.mcc` files of chunks that fit in the region again are deleted; empty
+/// regions are left as they are, chunks in a custom compression aren't recompressed
+pub fn compact_region(path: &Path, options: CompactOptions) -> Result<CompactReport> {
+    if let Some(compression) = options.compression.filter(|e| !region::can_compress(*e)) {
+        return Err(Error::Custom(format!(
+            "chunks can't be compressed with {compression}"
+        )));
+    }
+    let bytes_before = std::fs::metadata(path).map_err(Error::FileError)?.len();
+    let mut region = RegionFile::open(path)?;
+    let mut report = CompactReport {
+        regions: 1,
+        bytes_before,
+        bytes_after: bytes_before,
+        ..Default::default()
+    };
+    if region.is_empty() {
+        return Ok(report);
+    }
+    if options.compression.is_some() || options.level.is_some() {
+        for (x, z) in region.positions().collect::<Vec<_>>() {
+            let chunk = region.chunk(x, z).unwrap();
+            if chunk.compression == COMPRESSION_CUSTOM {
+                continue;
+            }
+            let compression = match options.compression.unwrap_or(chunk.compression) {
+                compression if region::can_compress(compression) => compression,
+                _ => COMPRESSION_ZLIB,
+            };
+            let recompressed =
+                chunk.recompress(compression, options.level.unwrap_or(DEFAULT_LEVEL))?;
+            region.set_chunk(x, z, recompressed);
+            report.chunks_recompressed += 1;
+        }
+    }
+    let (rx, rz) = region::region_coords(path).ok_or(Error::Malformed(line!()))?;
+    let (bytes, external) = region.to_bytes();
+    let mut kept = HashSet::new();
+    for (i, chunk) in external {
+        let (x, z) = (rx * 32 + (i % 32) as i32, rz * 32 + (i / 32) as i32);
+        transaction::replace_file(&region::external_chunk_path(path, x, z), &chunk.data)?;
+        kept.insert((x, z));
+    }
+    transaction::replace_file(path, &bytes)?;
+    report.bytes_after = bytes.len() as u64;
+    report.bytes_reclaimed = bytes_before.saturating_sub(report.bytes_after);
+
+    // the region no longer points at these, they were inlined or the chunk is gone
+    let dir = path.parent().ok_or(Error::Malformed(line!()))?;
+    for entry in std::fs::read_dir(dir).map_err(Error::FileError)? {
+        let entry = entry.map_err(Error::FileError)?.path();
+        let Some((x, z)) = external_coords(&entry) else {
+            continue;
+        };
+        if (x >> 5, z >> 5) == (rx, rz) && !kept.contains(&(x, z)) {
+            std::fs::remove_file(&entry).map_err(Error::FileError)?;
+        }
+    }
+    Ok(report)
+}
+
+/// chunk coordinates of a `c.x.z.mcc` file
+fn external_coords(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let (x, z) = name
+        .strip_prefix("c.")?
+        .strip_suffix(".mcc")?
+        .split_once('.')?;
+    Some((x.parse().ok()?, z.parse().ok()?))
+}
+
+impl World {
+    /// compacts every region file of `dim`, open regions are saved and closed first
+    pub fn compact(&self, dim: Dimension, options: CompactOptions) -> Result<CompactReport> {
+        self.check_writable()?;
+        self.save()?;
+        self.clear_cache();
+        let mut report = CompactReport::default();
+        for kind in RegionKind::ALL {
+            for (x, z) in self.region_positions(dim, kind)? {
+                let region = compact_region(&self.region_path(x, z, dim, kind), options)?;
+                report.regions += region.regions;
+                report.chunks_recompressed += region.chunks_recompressed;
+                report.bytes_before += region.bytes_before;
+                report.bytes_after += region.bytes_after;
+                report.bytes_reclaimed += region.bytes_reclaimed;
+            }
+        }
+        Ok(report)
+    }
+}
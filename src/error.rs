@@ -5,6 +5,10 @@ pub enum Error {
     Custom(String),
     FileError(std::io::Error),
     CompressionError,
+    /// another process holds the `session.lock` of the world at this path
+    WorldInUse(std::path::PathBuf),
+    /// the world was opened read only
+    ReadOnly,
 }
 
 impl PartialEq for Error {
@@ -0,0 +1,485 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{
+    block::{self, BlockState, PalettedContainer, SECTION_BIOMES, SECTION_BLOCKS},
+    builder::{Generator, DATA_VERSION},
+    transaction::by_region,
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, Map, NbtList, NbtValue, Result,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct GenerateReport {
+    pub chunks: usize,
+    /// entity and poi chunks removed from where the new chunks went
+    pub cleared_chunks: usize,
+    /// edit session to pass to [`World::undo`]
+    pub session: Option<String>,
+}
+
+/// blocks you can walk through, everything else that isn't air or a fluid blocks motion
+const PASSABLE: &[&str] = &[
+    "short_grass",
+    "grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "red_tulip",
+    "orange_tulip",
+    "white_tulip",
+    "pink_tulip",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "torchflower",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "brown_mushroom",
+    "red_mushroom",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "sugar_cane",
+    "vine",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+    "nether_sprouts",
+    "crimson_roots",
+    "warped_roots",
+    "redstone_wire",
+    "lever",
+    "tripwire",
+    "fire",
+    "soul_fire",
+    "structure_void",
+    "light",
+];
+
+pub(crate) fn has_fluid(state: &BlockState) -> bool {
+    matches!(
+        state.name.as_str(),
+        "minecraft:water"
+            | "minecraft:lava"
+            | "minecraft:bubble_column"
+            | "minecraft:seagrass"
+            | "minecraft:tall_seagrass"
+            | "minecraft:kelp"
+            | "minecraft:kelp_plant"
+    ) || state.property("waterlogged") == Some("true")
+}
+
+fn blocks_motion(state: &BlockState) -> bool {
+    let name = state.name.strip_prefix("minecraft:").unwrap_or(&state.name);
+    !state.is_air()
+        && !matches!(name, "water" | "lava" | "bubble_column")
+        && !PASSABLE.contains(&name)
+        && ![
+            "sapling",
+            "torch",
+            "sign",
+            "button",
+            "pressure_plate",
+            "rail",
+            "banner",
+        ]
+        .iter()
+        .any(|e| name.ends_with(e))
+}
+
+/// whether a block counts for a heightmap
+type Counts = fn(&BlockState) -> bool;
+
+/// heightmaps the game keeps for full chunks, and which blocks count for each
+const HEIGHTMAPS: [(&str, Counts); 4] = [
+    ("WORLD_SURFACE", |e| !e.is_air()),
+    ("OCEAN_FLOOR", blocks_motion),
+    ("MOTION_BLOCKING", |e| blocks_motion(e) || has_fluid(e)),
+    ("MOTION_BLOCKING_NO_LEAVES", |e| {
+        (blocks_motion(e) || has_fluid(e)) && !e.name.ends_with("_leaves")
+    }),
+];
+
+/// index of the biome cell holding a block inside a section
+fn biome_index(x: i32, y: i32, z: i32) -> usize {
+    (((y & 15) >> 2) * 16 + ((z & 15) >> 2) * 4 + ((x & 15) >> 2)) as usize
+}
+
+/// blocks and biomes of a chunk, written out as a finished chunk the game loads as is
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkBuilder {
+    x: i32,
+    z: i32,
+    min_y: i32,
+    sections: Vec<PalettedContainer<BlockState>>,
+    biomes: Vec<PalettedContainer<String>>,
+    /// everything else of the chunk this was read from
+    rest: Map<String, NbtValue>,
+}
+
+impl ChunkBuilder {
+    /// an empty chunk at chunk coordinates x, z with the height of `dim`
+    pub fn new(x: i32, z: i32, dim: Dimension) -> Self {
+        let sections = (dim.height() / 16) as usize;
+        let biome = match dim {
+            Dimension::Overworld => "minecraft:plains",
+            Dimension::Nether => "minecraft:nether_wastes",
+            Dimension::End => "minecraft:the_end",
+        };
+        Self {
+            x,
+            z,
+            min_y: dim.min_y(),
+            sections: vec![
+                PalettedContainer::filled(BlockState::new("air"), SECTION_BLOCKS);
+                sections
+            ],
+            biomes: vec![PalettedContainer::filled(biome.to_string(), SECTION_BIOMES); sections],
+            rest: Map::new(),
+        }
+    }
+
+    /// reads the blocks and biomes of a 1.18+ chunk, its entities, block entities and other
+    /// data are written back as they are
+    pub fn from_nbt(nbt: &NbtValue, dim: Dimension) -> Result<Self> {
+        let mut rest = nbt.as_compound().ok_or(Error::Malformed(line!()))?.clone();
+        let int = |key| {
+            rest.get(key)
+                .and_then(NbtValue::as_i64)
+                .map(|e| e as i32)
+                .ok_or(Error::Custom(format!(
+                    "no {key}, only 1.18+ chunks can be edited"
+                )))
+        };
+        let mut chunk = Self::new(int("xPos")?, int("zPos")?, dim);
+        let min_section = chunk.min_y >> 4;
+        if let Some(NbtValue::List(sections)) = rest.get("sections") {
+            for section in sections.compounds() {
+                let Some(y) = section.get("Y").and_then(NbtValue::as_i64) else {
+                    continue;
+                };
+                let Some(i) = usize::try_from(y as i32 - min_section)
+                    .ok()
+                    .filter(|i| *i < chunk.sections.len())
+                else {
+                    continue;
+                };
+                if let Some(blocks) = block::section_blocks(section)? {
+                    chunk.sections[i] = blocks;
+                }
+                if let Some(biomes) = block::section_biomes(section)? {
+                    chunk.biomes[i] = biomes;
+                }
+            }
+        }
+        for key in ["xPos", "yPos", "zPos", "sections", "Heightmaps"] {
+            rest.remove(key);
+        }
+        chunk.rest = rest;
+        Ok(chunk)
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        (self.x, self.z)
+    }
+
+    /// lowest and highest block y, inclusive
+    pub fn y_range(&self) -> (i32, i32) {
+        (self.min_y, self.min_y + self.sections.len() as i32 * 16 - 1)
+    }
+
+    fn section(&self, y: i32) -> Option<usize> {
+        let (min, max) = self.y_range();
+        (min..=max).contains(&y).then(|| ((y - min) >> 4) as usize)
+    }
+
+    /// the block at x, z inside the chunk (taken modulo 16) and absolute y, air outside the
+    /// chunk's height
+    pub fn block(&self, x: i32, y: i32, z: i32) -> BlockState {
+        match self.section(y) {
+            Some(s) => self.sections[s].get(block::section_index(x, y, z)).clone(),
+            None => BlockState::new("air"),
+        }
+    }
+
+    /// sets a block, positions above or below the chunk are ignored
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: &BlockState) {
+        if let Some(s) = self.section(y) {
+            self.sections[s].set(block::section_index(x, y, z), state.clone());
+        }
+    }
+
+    /// sets every block of the layer at y
+    pub fn fill_layer(&mut self, y: i32, state: &BlockState) {
+        for z in 0..16 {
+            for x in 0..16 {
+                self.set_block(x, y, z, state);
+            }
+        }
+    }
+
+    /// the biome of the 4 by 4 by 4 cell holding the block at x, z inside the chunk and
+    /// absolute y, the nearest section's for y outside the chunk's height
+    pub fn biome(&self, x: i32, y: i32, z: i32) -> &str {
+        let (min, max) = self.y_range();
+        let y = y.clamp(min, max);
+        let s = ((y - min) >> 4) as usize;
+        self.biomes[s].get(biome_index(x, y, z))
+    }
+
+    /// sets the biome of the 4 by 4 by 4 cell holding the block, positions above or below the
+    /// chunk are ignored
+    pub fn set_biome_at(&mut self, x: i32, y: i32, z: i32, biome: &str) {
+        if let Some(s) = self.section(y) {
+            self.biomes[s].set(biome_index(x, y, z), block::namespaced(biome));
+        }
+    }
+
+    /// sets the biome of the whole chunk
+    pub fn set_biome(&mut self, biome: &str) {
+        for biomes in self.biomes.iter_mut() {
+            *biomes = PalettedContainer::filled(block::namespaced(biome), SECTION_BIOMES);
+        }
+    }
+
+    /// height above `min_y` of the block on top of each column that counts for `f`, x first
+    fn heightmap(&self, f: Counts) -> Vec<i64> {
+        let mut tops = [0usize; 256];
+        for (s, blocks) in self.sections.iter().enumerate().rev() {
+            let counts = blocks.palette.iter().map(f).collect::<Vec<_>>();
+            if !counts.contains(&true) {
+                continue;
+            }
+            for (i, top) in tops.iter_mut().enumerate().filter(|e| *e.1 == 0) {
+                if let Some(y) = (0..16)
+                    .rev()
+                    .find(|y| counts[blocks.indices[y * 256 + i] as usize])
+                {
+                    *top = s * 16 + y + 1;
+                }
+            }
+        }
+        let bits = usize::BITS - (self.sections.len() * 16).leading_zeros();
+        let per_long = (64 / bits) as usize;
+        let mut data = vec![0i64; 256usize.div_ceil(per_long)];
+        for (i, top) in tops.into_iter().enumerate() {
+            data[i / per_long] |= (top as i64) << ((i % per_long) as u32 * bits);
+        }
+        data
+    }
+
+    /// nbt of a chunk with status `minecraft:full`, lighting is left for the game to compute
+    ///
+    /// a chunk read with [`ChunkBuilder::from_nbt`] keeps its other data, block entities
+    /// included even where their block was replaced
+    pub fn to_nbt(&self) -> NbtValue {
+        let min_section = self.min_y >> 4;
+        let sections = self
+            .sections
+            .iter()
+            .zip(self.biomes.iter())
+            .enumerate()
+            .map(|(i, (blocks, biomes))| {
+                let mut blocks = blocks.clone();
+                blocks.compact();
+                Map::from([
+                    (
+                        "Y".to_string(),
+                        NbtValue::Byte((min_section + i as i32) as i8),
+                    ),
+                    ("block_states".to_string(), block::blocks_to_nbt(&blocks)),
+                    ("biomes".to_string(), block::biomes_to_nbt(biomes)),
+                ])
+            })
+            .collect();
+        let heightmaps = HEIGHTMAPS
+            .iter()
+            .map(|(name, f)| (name.to_string(), NbtValue::LongArray(self.heightmap(*f))))
+            .collect::<Vec<_>>();
+        let empty = || NbtValue::List(NbtList::EmptyList());
+        let structures = NbtValue::Compound(Map::from([
+            ("References".to_string(), NbtValue::Compound(Map::new())),
+            ("starts".to_string(), NbtValue::Compound(Map::new())),
+        ]));
+        let defaults = [
+            ("DataVersion", NbtValue::Int(DATA_VERSION)),
+            ("LastUpdate", NbtValue::Long(0)),
+            ("InhabitedTime", NbtValue::Long(0)),
+            ("block_entities", empty()),
+            ("block_ticks", empty()),
+            ("fluid_ticks", empty()),
+            ("PostProcessing", empty()),
+            ("structures", structures),
+        ];
+        let mut nbt = Map::from([
+            ("xPos".to_string(), NbtValue::Int(self.x)),
+            ("yPos".to_string(), NbtValue::Int(min_section)),
+            ("zPos".to_string(), NbtValue::Int(self.z)),
+        ]);
+        for (key, value) in self.rest.iter() {
+            nbt.insert(key.clone(), value.clone());
+        }
+        for (key, value) in defaults {
+            if !nbt.contains_key(key) {
+                nbt.insert(key.to_string(), value);
+            }
+        }
+        let status = NbtValue::String("minecraft:full".to_string());
+        nbt.insert("Status".to_string(), status);
+        nbt.insert("isLightOn".to_string(), NbtValue::Byte(0));
+        nbt.insert(
+            "sections".to_string(),
+            NbtValue::List(NbtList::CompoundList(sections)),
+        );
+        nbt.insert(
+            "Heightmaps".to_string(),
+            NbtValue::Compound(Map::from(heightmaps)),
+        );
+        NbtValue::Compound(nbt)
+    }
+}
+
+/// parses a superflat layer spec from the bottom up, `minecraft:bedrock,2*minecraft:dirt,...`
+pub fn parse_layers(spec: &str) -> Result<Vec<(BlockState, u32)>> {
+    let mut layers = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    // commas inside block properties don't separate layers
+    for (i, c) in spec.char_indices().chain([(spec.len(), ',')]) {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                let layer = spec[start..i].trim();
+                start = i + 1;
+                if layer.is_empty() {
+                    continue;
+                }
+                let (count, state) = match layer.split_once('*') {
+                    Some((count, state)) if !count.contains('[') => (
+                        count.trim().parse::<u32>().map_err(|_| {
+                            Error::Custom(format!("invalid layer count in {layer}"))
+                        })?,
+                        state,
+                    ),
+                    _ => (1, layer),
+                };
+                layers.push((BlockState::from_str(state.trim())?, count));
+            }
+            _ => {}
+        }
+    }
+    Ok(layers)
+}
+
+/// a chunk of a flat world, layers start at the bottom of the dimension
+pub fn flat_chunk(
+    x: i32,
+    z: i32,
+    dim: Dimension,
+    layers: &[(BlockState, u32)],
+    biome: &str,
+) -> Result<NbtValue> {
+    let mut chunk = ChunkBuilder::new(x, z, dim);
+    let (min, max) = chunk.y_range();
+    let total = layers.iter().map(|e| e.1 as i64).sum::<i64>();
+    if total > (max - min + 1) as i64 {
+        return Err(Error::Custom(format!(
+            "{total} layers don't fit in {} blocks",
+            max - min + 1
+        )));
+    }
+    let mut y = min;
+    for (state, count) in layers {
+        for _ in 0..*count {
+            chunk.fill_layer(y, state);
+            y += 1;
+        }
+    }
+    chunk.set_biome(biome);
+    Ok(chunk.to_nbt())
+}
+
+impl Generator {
+    /// the chunk this generator makes at x, z, `None` for generators only the game can run
+    pub fn chunk(&self, x: i32, z: i32, dim: Dimension) -> Result<Option<NbtValue>> {
+        match self {
+            Generator::Flat { layers, biome } => flat_chunk(x, z, dim, layers, biome).map(Some),
+            Generator::Void => flat_chunk(x, z, dim, &[], "minecraft:the_void").map(Some),
+            Generator::Default | Generator::Custom(_) => Ok(None),
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = Error;
+
+    /// `default`, `void` or a superflat layer spec on plains
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "default" => Ok(Generator::Default),
+            "void" => Ok(Generator::Void),
+            _ => Ok(Generator::Flat {
+                layers: parse_layers(s)?,
+                biome: "minecraft:plains".to_string(),
+            }),
+        }
+    }
+}
+
+impl World {
+    /// writes the chunks of a flat or void generator between corners `min` and `max`,
+    /// replacing whatever is there, in one transaction; the entities and poi of the old
+    /// chunks are removed along with them
+    pub fn generate(
+        &self,
+        dim: Dimension,
+        generator: &Generator,
+        min: (i32, i32),
+        max: (i32, i32),
+    ) -> Result<GenerateReport> {
+        let transaction = self.transaction()?;
+        let mut report = GenerateReport::default();
+        let chunks = (min.1.min(max.1)..=min.1.max(max.1))
+            .flat_map(|z| (min.0.min(max.0)..=min.0.max(max.0)).map(move |x| (x, z)));
+        for ((rx, rz), chunks) in by_region(chunks) {
+            let region = transaction.get_region(rx, rz, dim, RegionKind::Chunks)?;
+            for &(x, z) in chunks.iter() {
+                let nbt = generator.chunk(x, z, dim)?.ok_or_else(|| {
+                    Error::Custom("only flat and void chunks can be generated".to_string())
+                })?;
+                region.set_chunk(x & 31, z & 31, &Chunk::new(nbt))?;
+                report.chunks += 1;
+            }
+            transaction.finish_region(rx, rz, dim, RegionKind::Chunks)?;
+            for kind in [RegionKind::Entities, RegionKind::Poi] {
+                if !self.region_path(rx, rz, dim, kind).is_file() {
+                    continue;
+                }
+                let region = transaction.get_region(rx, rz, dim, kind)?;
+                for (x, z) in chunks.iter() {
+                    if region.remove_chunk(x & 31, z & 31) {
+                        report.cleared_chunks += 1;
+                    }
+                }
+                transaction.finish_region(rx, rz, dim, kind)?;
+            }
+        }
+        let session = transaction.session().to_string();
+        report.session = (transaction.commit()? > 0).then_some(session);
+        Ok(report)
+    }
+}
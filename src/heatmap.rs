@@ -0,0 +1,241 @@
+use std::{io::Write, str::FromStr};
+
+use serde::Serialize;
+
+use crate::{
+    image::Image,
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, NbtValue, Result,
+};
+
+/// what a chunk heatmap shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChunkMetric {
+    /// ticks players spent near the chunk
+    InhabitedTime,
+    /// game tick the chunk was last saved at
+    LastUpdate,
+    /// when the region file says the chunk was last written, in seconds since the epoch
+    Timestamp,
+    /// bytes of the uncompressed nbt
+    Size,
+}
+
+impl ChunkMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChunkMetric::InhabitedTime => "inhabited-time",
+            ChunkMetric::LastUpdate => "last-update",
+            ChunkMetric::Timestamp => "timestamp",
+            ChunkMetric::Size => "size",
+        }
+    }
+
+    /// how a value is written in the legend
+    fn label(&self, value: i64) -> String {
+        match self {
+            ChunkMetric::Timestamp => date(value),
+            _ => compact_number(value),
+        }
+    }
+}
+
+impl FromStr for ChunkMetric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            ChunkMetric::InhabitedTime,
+            ChunkMetric::LastUpdate,
+            ChunkMetric::Timestamp,
+            ChunkMetric::Size,
+        ]
+        .into_iter()
+        .find(|e| e.name() == s)
+        .ok_or_else(|| Error::Custom(format!("unknown chunk metric {s}")))
+    }
+}
+
+/// `12.3k`, `4.5M`
+fn compact_number(value: i64) -> String {
+    let v = value.unsigned_abs() as f64;
+    let sign = if value < 0 { "-" } else { "" };
+    match v {
+        v if v < 1e3 => value.to_string(),
+        v if v < 1e6 => format!("{sign}{:.1}k", v / 1e3),
+        v if v < 1e9 => format!("{sign}{:.1}M", v / 1e6),
+        v => format!("{sign}{:.1}G", v / 1e9),
+    }
+}
+
+/// `yyyy-mm-dd` of seconds since the epoch
+fn date(seconds: i64) -> String {
+    // days to civil date, from Howard Hinnant's algorithms
+    let days = seconds.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// low to high
+const RAMP: [[u8; 3]; 5] = [
+    [48, 18, 59],
+    [70, 134, 251],
+    [26, 228, 182],
+    [250, 186, 57],
+    [122, 4, 3],
+];
+
+/// color of `t` between 0 and 1
+fn ramp(t: f64) -> [u8; 4] {
+    let t = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+    let i = (t.floor() as usize).min(RAMP.len() - 2);
+    let f = t - i as f64;
+    let [r, g, b] = [0, 1, 2]
+        .map(|c| (RAMP[i][c] as f64 * (1.0 - f) + RAMP[i + 1][c] as f64 * f).round() as u8);
+    [r, g, b, 255]
+}
+
+/// one value per chunk of a dimension
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChunkHeatmap {
+    pub metric: ChunkMetric,
+    /// sorted by chunk position
+    pub chunks: Vec<((i32, i32), i64)>,
+}
+
+impl ChunkHeatmap {
+    /// the smallest and largest value, `None` without chunks
+    pub fn range(&self) -> Option<(i64, i64)> {
+        let min = self.chunks.iter().map(|e| e.1).min()?;
+        let max = self.chunks.iter().map(|e| e.1).max()?;
+        Some((min, max))
+    }
+
+    /// chunk coordinates of the top left corner of [`ChunkHeatmap::render`]
+    pub fn origin(&self) -> Option<(i32, i32)> {
+        let x = self.chunks.iter().map(|e| e.0 .0).min()?;
+        let z = self.chunks.iter().map(|e| e.0 .1).min()?;
+        Some((x, z))
+    }
+
+    /// `chunk_x,chunk_z,<metric>` with a header line
+    pub fn to_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "chunk_x,chunk_z,{}", self.metric.name()).map_err(Error::FileError)?;
+        for ((x, z), value) in self.chunks.iter() {
+            writeln!(writer, "{x},{z},{value}").map_err(Error::FileError)?;
+        }
+        Ok(())
+    }
+
+    /// a map with `pixels_per_chunk` pixels to a chunk, north up, and a legend below with the
+    /// color ramp between the smallest and the largest value; `log` spreads the colors on a
+    /// logarithmic scale, which suits `InhabitedTime` where a few chunks dwarf the rest
+    pub fn render(&self, pixels_per_chunk: u32, log: bool) -> Image {
+        let scale = pixels_per_chunk.max(1);
+        let (Some((min, max)), Some((x0, z0))) = (self.range(), self.origin()) else {
+            return Image::new(1, 1);
+        };
+        let x1 = self.chunks.iter().map(|e| e.0 .0).max().unwrap();
+        let z1 = self.chunks.iter().map(|e| e.0 .1).max().unwrap();
+        let (map_width, map_height) = ((x1 - x0 + 1) as u32 * scale, (z1 - z0 + 1) as u32 * scale);
+        let position = |value: i64| {
+            let (v, low, high) = match log {
+                true => (
+                    ((value - min) as f64).ln_1p(),
+                    0.0,
+                    ((max - min) as f64).ln_1p(),
+                ),
+                false => (value as f64, min as f64, max as f64),
+            };
+            if high > low {
+                (v - low) / (high - low)
+            } else {
+                0.0
+            }
+        };
+
+        // legend: the ramp and the labels at both ends, 2 pixels to a font pixel
+        let (min_label, max_label) = (self.metric.label(min), self.metric.label(max));
+        let labels = Image::text_width(&min_label, 2) + Image::text_width(&max_label, 2) + 24;
+        let width = map_width.max(labels).max(64);
+        let mut image = Image::new(width, map_height + 30);
+        for ((x, z), value) in self.chunks.iter() {
+            let color = ramp(position(*value));
+            let (px, pz) = ((x - x0) as u32 * scale, (z - z0) as u32 * scale);
+            for dz in 0..scale {
+                for dx in 0..scale {
+                    image.set(px + dx, pz + dz, color);
+                }
+            }
+        }
+        for y in map_height..image.height() {
+            for x in 0..width {
+                image.set(x, y, [0, 0, 0, 255]);
+            }
+        }
+        for x in 4..width - 4 {
+            let color = ramp((x - 4) as f64 / (width - 9) as f64);
+            for y in map_height + 4..map_height + 12 {
+                image.set(x, y, color);
+            }
+        }
+        let white = [255; 4];
+        image.draw_text(4, map_height + 16, &min_label, 2, white);
+        let right = width - 4 - Image::text_width(&max_label, 2);
+        image.draw_text(right, map_height + 16, &max_label, 2, white);
+        image
+    }
+}
+
+impl World {
+    /// `metric` of every chunk in `dim`, chunks that can't be read are left out
+    pub fn chunk_heatmap(&self, dim: Dimension, metric: ChunkMetric) -> Result<ChunkHeatmap> {
+        let chunks = match metric {
+            ChunkMetric::InhabitedTime | ChunkMetric::LastUpdate => {
+                let key = match metric {
+                    ChunkMetric::InhabitedTime => "InhabitedTime",
+                    _ => "LastUpdate",
+                };
+                self.par_chunks(dim)
+                    .run(|_, nbt| Chunk::root(nbt).get(key).and_then(NbtValue::as_i64))?
+                    .results
+                    .into_iter()
+                    .filter_map(|(pos, value)| Some((pos, value?)))
+                    .collect()
+            }
+            // from the region files, without decoding the chunks
+            ChunkMetric::Timestamp | ChunkMetric::Size => {
+                let mut chunks = vec![];
+                for (rx, rz) in self.region_positions(dim, RegionKind::Chunks)? {
+                    let Some(region) = self.get_existing_region(rx, rz, dim, RegionKind::Chunks)?
+                    else {
+                        continue;
+                    };
+                    region.with_file(|file| {
+                        for (x, z) in file.positions() {
+                            let chunk = file.chunk(x, z).unwrap();
+                            let value = match metric {
+                                ChunkMetric::Timestamp => chunk.timestamp as i64,
+                                _ => match chunk.decompress() {
+                                    Ok(raw) => raw.len() as i64,
+                                    Err(_) => continue,
+                                },
+                            };
+                            chunks.push(((rx * 32 + x, rz * 32 + z), value));
+                        }
+                    });
+                }
+                chunks.sort_by_key(|e| e.0);
+                chunks
+            }
+        };
+        Ok(ChunkHeatmap { metric, chunks })
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, Write},
+    path::Path,
+};
+
+use crate::{Error, Result};
+
+/// 3 by 5 glyphs, one row of 3 bits per line from the top
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('k', [0b100, 0b101, 0b110, 0b101, 0b101]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('G', [0b111, 0b100, 0b101, 0b101, 0b111]),
+];
+
+/// rgba pixels, row by row, starts out transparent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 4]; width as usize * height as usize],
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_png(BufReader::new(File::open(path).map_err(Error::FileError)?))
+    }
+
+    /// reads a png of any color type, 16 bit channels keep their high byte
+    pub fn from_png<R: BufRead + Seek>(reader: R) -> Result<Self> {
+        let invalid = |e: png::DecodingError| Error::Custom(format!("invalid png: {e}"));
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buf = vec![
+            0;
+            reader
+                .output_buffer_size()
+                .ok_or(Error::Malformed(line!()))?
+        ];
+        let info = reader.next_frame(&mut buf).map_err(invalid)?;
+        let channels = info.color_type.samples();
+        let mut image = Image::new(info.width, info.height);
+        for (y, row) in buf[..info.buffer_size()].chunks(info.line_size).enumerate() {
+            for (x, pixel) in row.chunks(channels).take(info.width as usize).enumerate() {
+                let color = match *pixel {
+                    [v] => [v, v, v, 255],
+                    [v, a] => [v, v, v, a],
+                    [r, g, b] => [r, g, b, 255],
+                    [r, g, b, a] => [r, g, b, a],
+                    _ => return Err(Error::Malformed(line!())),
+                };
+                image.set(x as u32, y as u32, color);
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// pixels outside the image are ignored
+    pub fn set(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x < self.width && y < self.height {
+            self.pixels[y as usize * self.width as usize + x as usize] = color;
+        }
+    }
+
+    /// copies `other` with its top left corner at x, y, clipped to this image
+    pub fn paste(&mut self, other: &Image, x: u32, y: u32) {
+        for oy in 0..other.height {
+            for ox in 0..other.width {
+                self.set(x + ox, y + oy, other.get(ox, oy));
+            }
+        }
+    }
+
+    /// averages every `factor` by `factor` block of pixels into one, transparent pixels don't
+    /// count
+    pub fn downscale(&self, factor: u32) -> Image {
+        let factor = factor.max(1);
+        let mut image = Image::new(self.width.div_ceil(factor), self.height.div_ceil(factor));
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let mut sum = [0u32; 4];
+                let mut count = 0;
+                for sy in y * factor..((y + 1) * factor).min(self.height) {
+                    for sx in x * factor..((x + 1) * factor).min(self.width) {
+                        let pixel = self.get(sx, sy);
+                        if pixel[3] > 0 {
+                            sum.iter_mut().zip(pixel).for_each(|(s, p)| *s += p as u32);
+                            count += 1;
+                        }
+                    }
+                }
+                if count > 0 {
+                    image.set(x, y, sum.map(|e| (e / count) as u8));
+                }
+            }
+        }
+        image
+    }
+
+    /// scales the image to `width` by `height`, every pixel is the average of the pixels it
+    /// covers or the nearest one when enlarging; transparency counts like in
+    /// [`Image::downscale`]
+    pub fn resize(&self, width: u32, height: u32) -> Image {
+        let mut image = Image::new(width, height);
+        let (fx, fy) = (
+            self.width as f64 / width as f64,
+            self.height as f64 / height as f64,
+        );
+        for y in 0..height {
+            for x in 0..width {
+                let (x0, y0) = ((x as f64 * fx) as u32, (y as f64 * fy) as u32);
+                let x1 = (((x + 1) as f64 * fx).ceil() as u32).clamp(x0 + 1, self.width);
+                let y1 = (((y + 1) as f64 * fy).ceil() as u32).clamp(y0 + 1, self.height);
+                let mut sum = [0u32; 4];
+                let mut count = 0;
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let pixel = self.get(sx, sy);
+                        if pixel[3] > 0 {
+                            sum.iter_mut().zip(pixel).for_each(|(s, p)| *s += p as u32);
+                            count += 1;
+                        }
+                    }
+                }
+                if count > 0 {
+                    image.set(x, y, sum.map(|e| (e / count) as u8));
+                }
+            }
+        }
+        image
+    }
+
+    /// width in pixels of `text` drawn at `scale`
+    pub fn text_width(text: &str, scale: u32) -> u32 {
+        (text.chars().count() as u32 * 4).saturating_sub(1) * scale
+    }
+
+    /// draws digits, `-`, `.`, `k`, `M` and `G` in a 3 by 5 font with its top left corner at
+    /// x, y, other characters are left blank
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, scale: u32, color: [u8; 4]) {
+        for (i, c) in text.chars().enumerate() {
+            let Some((_, rows)) = GLYPHS.iter().find(|e| e.0 == c) else {
+                continue;
+            };
+            let left = x + i as u32 * 4 * scale;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits >> (2 - column) & 1 == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            self.set(
+                                left + column * scale + dx,
+                                y + row as u32 * scale + dy,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn to_png<W: Write>(&self, writer: W) -> Result<()> {
+        let invalid = |e: png::EncodingError| Error::Custom(format!("can't write png: {e}"));
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .map_err(invalid)?
+            .write_image_data(self.pixels.as_flattened())
+            .map_err(invalid)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.to_png(std::io::BufWriter::new(
+            File::create(path).map_err(Error::FileError)?,
+        ))
+    }
+}
@@ -0,0 +1,481 @@
+use crate::block::{self, BlockState};
+
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+/// blocks with a single state by id, colored and wooden families are handled in
+/// [`legacy_block`]
+const NAMES: &[(u16, &str)] = &[
+    (0, "air"),
+    (2, "grass_block"),
+    (4, "cobblestone"),
+    (7, "bedrock"),
+    (13, "gravel"),
+    (14, "gold_ore"),
+    (15, "iron_ore"),
+    (16, "coal_ore"),
+    (20, "glass"),
+    (21, "lapis_ore"),
+    (22, "lapis_block"),
+    (25, "note_block"),
+    (26, "red_bed"),
+    (27, "powered_rail"),
+    (28, "detector_rail"),
+    (29, "sticky_piston"),
+    (30, "cobweb"),
+    (32, "dead_bush"),
+    (33, "piston"),
+    (34, "piston_head"),
+    (36, "moving_piston"),
+    (37, "dandelion"),
+    (39, "brown_mushroom"),
+    (40, "red_mushroom"),
+    (41, "gold_block"),
+    (42, "iron_block"),
+    (45, "bricks"),
+    (46, "tnt"),
+    (47, "bookshelf"),
+    (48, "mossy_cobblestone"),
+    (49, "obsidian"),
+    (51, "fire"),
+    (52, "spawner"),
+    (55, "redstone_wire"),
+    (56, "diamond_ore"),
+    (57, "diamond_block"),
+    (58, "crafting_table"),
+    (59, "wheat"),
+    (60, "farmland"),
+    (66, "rail"),
+    (69, "lever"),
+    (70, "stone_pressure_plate"),
+    (72, "oak_pressure_plate"),
+    (73, "redstone_ore"),
+    (77, "stone_button"),
+    (79, "ice"),
+    (80, "snow_block"),
+    (81, "cactus"),
+    (82, "clay"),
+    (83, "sugar_cane"),
+    (84, "jukebox"),
+    (85, "oak_fence"),
+    (87, "netherrack"),
+    (88, "soul_sand"),
+    (89, "glowstone"),
+    (90, "nether_portal"),
+    (92, "cake"),
+    (93, "repeater"),
+    (96, "oak_trapdoor"),
+    (99, "brown_mushroom_block"),
+    (100, "red_mushroom_block"),
+    (101, "iron_bars"),
+    (102, "glass_pane"),
+    (103, "melon"),
+    (104, "pumpkin_stem"),
+    (105, "melon_stem"),
+    (106, "vine"),
+    (107, "oak_fence_gate"),
+    (110, "mycelium"),
+    (111, "lily_pad"),
+    (112, "nether_bricks"),
+    (113, "nether_brick_fence"),
+    (115, "nether_wart"),
+    (116, "enchanting_table"),
+    (117, "brewing_stand"),
+    (118, "cauldron"),
+    (119, "end_portal"),
+    (120, "end_portal_frame"),
+    (121, "end_stone"),
+    (122, "dragon_egg"),
+    (123, "redstone_lamp"),
+    (127, "cocoa"),
+    (129, "emerald_ore"),
+    (131, "tripwire_hook"),
+    (132, "tripwire"),
+    (133, "emerald_block"),
+    (137, "command_block"),
+    (138, "beacon"),
+    (140, "flower_pot"),
+    (141, "carrots"),
+    (142, "potatoes"),
+    (143, "oak_button"),
+    (144, "skeleton_skull"),
+    (147, "light_weighted_pressure_plate"),
+    (148, "heavy_weighted_pressure_plate"),
+    (149, "comparator"),
+    (151, "daylight_detector"),
+    (152, "redstone_block"),
+    (153, "nether_quartz_ore"),
+    (154, "hopper"),
+    (157, "activator_rail"),
+    (165, "slime_block"),
+    (166, "barrier"),
+    (167, "iron_trapdoor"),
+    (169, "sea_lantern"),
+    (172, "terracotta"),
+    (173, "coal_block"),
+    (174, "packed_ice"),
+    (176, "white_banner"),
+    (177, "white_wall_banner"),
+    (183, "spruce_fence_gate"),
+    (184, "birch_fence_gate"),
+    (185, "jungle_fence_gate"),
+    (186, "dark_oak_fence_gate"),
+    (187, "acacia_fence_gate"),
+    (188, "spruce_fence"),
+    (189, "birch_fence"),
+    (190, "jungle_fence"),
+    (191, "dark_oak_fence"),
+    (192, "acacia_fence"),
+    (198, "end_rod"),
+    (199, "chorus_plant"),
+    (200, "chorus_flower"),
+    (201, "purpur_block"),
+    (206, "end_stone_bricks"),
+    (207, "beetroots"),
+    (208, "dirt_path"),
+    (209, "end_gateway"),
+    (210, "repeating_command_block"),
+    (211, "chain_command_block"),
+    (212, "frosted_ice"),
+    (213, "magma_block"),
+    (214, "nether_wart_block"),
+    (215, "red_nether_bricks"),
+    (217, "structure_void"),
+    (218, "observer"),
+    (255, "structure_block"),
+];
+
+/// stairs by id, they share their data values
+const STAIRS: &[(u16, &str)] = &[
+    (53, "oak_stairs"),
+    (67, "cobblestone_stairs"),
+    (108, "brick_stairs"),
+    (109, "stone_brick_stairs"),
+    (114, "nether_brick_stairs"),
+    (128, "sandstone_stairs"),
+    (134, "spruce_stairs"),
+    (135, "birch_stairs"),
+    (136, "jungle_stairs"),
+    (156, "quartz_stairs"),
+    (163, "acacia_stairs"),
+    (164, "dark_oak_stairs"),
+    (180, "red_sandstone_stairs"),
+    (203, "purpur_stairs"),
+];
+
+/// doors by id, they share their data values
+const DOORS: &[(u16, &str)] = &[
+    (64, "oak_door"),
+    (71, "iron_door"),
+    (193, "spruce_door"),
+    (194, "birch_door"),
+    (195, "jungle_door"),
+    (196, "acacia_door"),
+    (197, "dark_oak_door"),
+];
+
+/// block entity ids from before 1.11 that aren't the modern id in lower case
+const BLOCK_ENTITIES: &[(&str, &str)] = &[
+    ("Airportal", "end_portal"),
+    ("Cauldron", "brewing_stand"),
+    ("Control", "command_block"),
+    ("DLDetector", "daylight_detector"),
+    ("EnchantTable", "enchanting_table"),
+    ("EndGateway", "end_gateway"),
+    ("EnderChest", "ender_chest"),
+    ("FlowerPot", "flower_pot"),
+    ("MobSpawner", "mob_spawner"),
+    ("Music", "note_block"),
+    ("RecordPlayer", "jukebox"),
+    ("Structure", "structure_block"),
+    ("Trap", "dispenser"),
+];
+
+/// the namespaced modern id of a block entity id from an old schematic or world
+pub fn legacy_block_entity(id: &str) -> String {
+    match BLOCK_ENTITIES.iter().find(|e| e.0 == id) {
+        Some((_, id)) => block::namespaced(id),
+        None => block::namespaced(&id.to_lowercase()),
+    }
+}
+
+/// horizontal facing of chests, furnaces, ladders and wall signs
+fn facing(data: u8) -> &'static str {
+    match data & 7 {
+        3 => "south",
+        4 => "west",
+        5 => "east",
+        _ => "north",
+    }
+}
+
+/// the modern block state of a legacy id and data value; the variant, color, axis, facing and
+/// half are kept for the common families, other blocks get their default state and ids
+/// without a block become air
+///
+/// each half of a door only stores part of its state, the lower one its facing and whether
+/// it is open, the upper one its hinge and whether it is powered; legacy schematics join the
+/// two halves when both are there and otherwise keep the defaults for the rest
+pub fn legacy_block(id: u16, data: u8) -> BlockState {
+    let color = COLORS[(data & 15) as usize];
+    let wood = |i: u8| WOODS[(i as usize).min(5)];
+    let pick = |names: &[&'static str]| names.get(data as usize).copied().unwrap_or(names[0]);
+    let axis = |bits: u8| match bits & 12 {
+        4 => "x",
+        8 => "z",
+        _ => "y",
+    };
+    let slab = |name: String, top: bool| {
+        BlockState::new(&name).with("type", if top { "top" } else { "bottom" })
+    };
+    let stone_slab = |i: u8| {
+        [
+            "smooth_stone_slab",
+            "sandstone_slab",
+            "oak_slab",
+            "cobblestone_slab",
+            "brick_slab",
+            "stone_brick_slab",
+            "nether_brick_slab",
+            "quartz_slab",
+        ][(i & 7) as usize]
+            .to_string()
+    };
+    if let Some((_, name)) = STAIRS.iter().find(|e| e.0 == id) {
+        let facing = ["east", "west", "south", "north"][(data & 3) as usize];
+        let half = if data & 4 != 0 { "top" } else { "bottom" };
+        return BlockState::new(name)
+            .with("facing", facing)
+            .with("half", half);
+    }
+    if let Some((_, name)) = DOORS.iter().find(|e| e.0 == id) {
+        let state = BlockState::new(name);
+        return match data & 8 {
+            0 => state
+                .with(
+                    "facing",
+                    ["east", "south", "west", "north"][(data & 3) as usize],
+                )
+                .with("open", &(data & 4 != 0).to_string())
+                .with("half", "lower")
+                .with("hinge", "left")
+                .with("powered", "false"),
+            _ => state
+                .with("facing", "east")
+                .with("open", "false")
+                .with("half", "upper")
+                .with("hinge", if data & 1 != 0 { "right" } else { "left" })
+                .with("powered", &(data & 2 != 0).to_string()),
+        };
+    }
+    if let Some((_, name)) = NAMES.iter().find(|e| e.0 == id) {
+        return BlockState::new(name);
+    }
+    let name = match id {
+        1 => pick(&[
+            "stone",
+            "granite",
+            "polished_granite",
+            "diorite",
+            "polished_diorite",
+            "andesite",
+            "polished_andesite",
+        ])
+        .to_string(),
+        3 => pick(&["dirt", "coarse_dirt", "podzol"]).to_string(),
+        5 => format!("{}_planks", wood(data)),
+        6 => format!("{}_sapling", wood(data & 7)),
+        8 | 9 => return BlockState::new("water").with("level", &(data & 15).to_string()),
+        10 | 11 => return BlockState::new("lava").with("level", &(data & 15).to_string()),
+        12 => pick(&["sand", "red_sand"]).to_string(),
+        17 | 162 => {
+            let wood = wood((data & 3) + if id == 162 { 4 } else { 0 });
+            return match data & 12 {
+                12 => BlockState::new(&format!("{wood}_wood")).with("axis", "y"),
+                _ => BlockState::new(&format!("{wood}_log")).with("axis", axis(data)),
+            };
+        }
+        18 | 161 => {
+            let wood = wood((data & 3) + if id == 161 { 4 } else { 0 });
+            return BlockState::new(&format!("{wood}_leaves")).with("persistent", "true");
+        }
+        19 => pick(&["sponge", "wet_sponge"]).to_string(),
+        23 | 158 => {
+            let name = if id == 23 { "dispenser" } else { "dropper" };
+            let facing =
+                ["down", "up", "north", "south", "west", "east"][(data & 7).min(5) as usize];
+            return BlockState::new(name).with("facing", facing);
+        }
+        24 => pick(&["sandstone", "chiseled_sandstone", "cut_sandstone"]).to_string(),
+        31 => pick(&["dead_bush", "short_grass", "fern"]).to_string(),
+        35 => format!("{color}_wool"),
+        38 => pick(&[
+            "poppy",
+            "blue_orchid",
+            "allium",
+            "azure_bluet",
+            "red_tulip",
+            "orange_tulip",
+            "white_tulip",
+            "pink_tulip",
+            "oxeye_daisy",
+        ])
+        .to_string(),
+        43 => return BlockState::new(&stone_slab(data)).with("type", "double"),
+        44 => return slab(stone_slab(data), data & 8 != 0),
+        50 | 75 | 76 => {
+            let lit = if id == 75 { "false" } else { "true" };
+            let (torch, wall) = match id {
+                50 => ("torch", "wall_torch"),
+                _ => ("redstone_torch", "redstone_wall_torch"),
+            };
+            let state = match data & 7 {
+                1 => BlockState::new(wall).with("facing", "east"),
+                2 => BlockState::new(wall).with("facing", "west"),
+                3 => BlockState::new(wall).with("facing", "south"),
+                4 => BlockState::new(wall).with("facing", "north"),
+                _ => BlockState::new(torch),
+            };
+            return match id {
+                50 => state,
+                _ => state.with("lit", lit),
+            };
+        }
+        54 | 130 | 146 | 61 | 62 | 65 | 68 => {
+            let name = match id {
+                54 => "chest",
+                130 => "ender_chest",
+                146 => "trapped_chest",
+                61 | 62 => "furnace",
+                65 => "ladder",
+                _ => "oak_wall_sign",
+            };
+            let state = BlockState::new(name).with("facing", facing(data));
+            return match id {
+                61 | 62 => state.with("lit", if id == 62 { "true" } else { "false" }),
+                _ => state,
+            };
+        }
+        63 => return BlockState::new("oak_sign").with("rotation", &(data & 15).to_string()),
+        74 => return BlockState::new("redstone_ore").with("lit", "true"),
+        78 => return BlockState::new("snow").with("layers", &((data & 7) + 1).to_string()),
+        86 | 91 => {
+            let name = if id == 86 {
+                "carved_pumpkin"
+            } else {
+                "jack_o_lantern"
+            };
+            let facing = ["south", "west", "north", "east"][(data & 3) as usize];
+            return BlockState::new(name).with("facing", facing);
+        }
+        94 => return BlockState::new("repeater").with("powered", "true"),
+        95 => format!("{color}_stained_glass"),
+        97 => pick(&[
+            "infested_stone",
+            "infested_cobblestone",
+            "infested_stone_bricks",
+            "infested_mossy_stone_bricks",
+            "infested_cracked_stone_bricks",
+            "infested_chiseled_stone_bricks",
+        ])
+        .to_string(),
+        98 => pick(&[
+            "stone_bricks",
+            "mossy_stone_bricks",
+            "cracked_stone_bricks",
+            "chiseled_stone_bricks",
+        ])
+        .to_string(),
+        124 => return BlockState::new("redstone_lamp").with("lit", "true"),
+        125 => return BlockState::new(&format!("{}_slab", wood(data & 7))).with("type", "double"),
+        126 => return slab(format!("{}_slab", wood(data & 7)), data & 8 != 0),
+        139 => pick(&["cobblestone_wall", "mossy_cobblestone_wall"]).to_string(),
+        145 => ["anvil", "chipped_anvil", "damaged_anvil"][((data >> 2) & 3).min(2) as usize]
+            .to_string(),
+        150 => return BlockState::new("comparator").with("powered", "true"),
+        155 => {
+            return match data {
+                1 => BlockState::new("chiseled_quartz_block"),
+                2 => BlockState::new("quartz_pillar").with("axis", "y"),
+                3 => BlockState::new("quartz_pillar").with("axis", "x"),
+                4 => BlockState::new("quartz_pillar").with("axis", "z"),
+                _ => BlockState::new("quartz_block"),
+            }
+        }
+        159 => format!("{color}_terracotta"),
+        160 => format!("{color}_stained_glass_pane"),
+        168 => pick(&["prismarine", "prismarine_bricks", "dark_prismarine"]).to_string(),
+        170 => return BlockState::new("hay_block").with("axis", axis(data)),
+        171 => format!("{color}_carpet"),
+        175 => {
+            // the upper half only stores that it is one
+            let name = [
+                "sunflower",
+                "lilac",
+                "tall_grass",
+                "large_fern",
+                "rose_bush",
+                "peony",
+            ][(data & 7).min(5) as usize];
+            let half = if data & 8 != 0 { "upper" } else { "lower" };
+            return BlockState::new(name).with("half", half);
+        }
+        178 => return BlockState::new("daylight_detector").with("inverted", "true"),
+        179 => pick(&[
+            "red_sandstone",
+            "chiseled_red_sandstone",
+            "cut_red_sandstone",
+        ])
+        .to_string(),
+        181 => return BlockState::new("red_sandstone_slab").with("type", "double"),
+        182 => return slab("red_sandstone_slab".to_string(), data & 8 != 0),
+        202 => return BlockState::new("purpur_pillar").with("axis", axis(data)),
+        204 => return BlockState::new("purpur_slab").with("type", "double"),
+        205 => return slab("purpur_slab".to_string(), data & 8 != 0),
+        216 => return BlockState::new("bone_block").with("axis", axis(data)),
+        219..=234 => format!("{}_shulker_box", COLORS[(id - 219) as usize]),
+        235..=250 => format!("{}_glazed_terracotta", COLORS[(id - 235) as usize]),
+        251 => format!("{color}_concrete"),
+        252 => format!("{color}_concrete_powder"),
+        _ => "air".to_string(),
+    };
+    BlockState::new(&name)
+}
+
+/// both halves of a door from [`legacy_block`] with the state the other half stores, `None`
+/// when `lower` and `upper` aren't the two halves of one door
+pub(crate) fn join_door(
+    lower: &BlockState,
+    upper: &BlockState,
+) -> Option<(BlockState, BlockState)> {
+    let is_door = |e: &BlockState, half| {
+        DOORS.iter().any(|d| block::namespaced(d.1) == e.name) && e.property("half") == Some(half)
+    };
+    if !is_door(lower, "lower") || !is_door(upper, "upper") || lower.name != upper.name {
+        return None;
+    }
+    let (facing, open) = (lower.property("facing")?, lower.property("open")?);
+    let (hinge, powered) = (upper.property("hinge")?, upper.property("powered")?);
+    Some((
+        lower.clone().with("hinge", hinge).with("powered", powered),
+        upper.clone().with("facing", facing).with("open", open),
+    ))
+}
@@ -1,6 +1,29 @@
+mod area;
+pub mod block;
+mod builder;
+mod compact;
 mod error;
+mod generate;
+mod heatmap;
+mod image;
+mod legacy;
+mod map_item;
 mod nbt_value;
+mod prune;
 mod pylib;
+pub mod region;
+mod relocate;
+mod render;
+mod replace;
+mod scan;
+mod schematic;
+mod search;
+mod session;
+mod structure;
+mod terrain;
+mod transaction;
+mod undo;
+mod validate;
 mod world;
 
 use std::{
@@ -8,10 +31,37 @@ use std::{
     io::{Read, Write},
 };
 
+pub use area::{Area, BoundingBox};
+pub use block::{BlockPattern, BlockState};
+pub use builder::{version_name, GameMode, Generator, WorldBuilder, DATA_VERSION};
+pub use compact::{compact_region, CompactOptions, CompactReport};
 pub use error::{Error, Result};
+pub use generate::{flat_chunk, parse_layers, ChunkBuilder, GenerateReport};
+pub use heatmap::{ChunkHeatmap, ChunkMetric};
+pub use image::Image;
+pub use legacy::{legacy_block, legacy_block_entity};
+pub use map_item::{
+    map_color, nearest_map_color, quantize, MapArtOptions, MapArtReport, MapExportReport, MapItem,
+    MAP_SIZE,
+};
 pub use nbt_value::{Map, NbtList, NbtValue};
-pub use pylib::*;
+pub use prune::{PruneReport, WorldBorder};
+pub use relocate::{relocate_chunk, CopyReport};
+pub use render::{biome_color, block_color, chunk_surface, Column, RenderOptions, Slice};
+pub use replace::ReplaceReport;
+pub use scan::{Scan, ScanError, ScanProgress, ScanResult};
+pub use schematic::{Mirror, PasteOptions, PasteReport, Rotation, Schematic, SchematicSummary};
+pub use search::{ItemHolder, ItemMatch, ItemPredicate};
 use serde::{Deserialize, Serialize};
+pub use structure::{Structure, StructureBlock, StructureEntity};
+pub use terrain::{Heightmap, TerrainOptions, TerrainReport};
+pub use transaction::{Transaction, JOURNAL};
+pub use undo::{UndoReport, UNDO_DIR};
+pub use validate::{validate_region, ChunkIssue, Issue, RegionReport, Repair};
+pub use world::{
+    Chunk, Dimension, LevelDat, Region, RegionKind, World, DEFAULT_CACHED_CHUNKS,
+    DEFAULT_CACHED_REGIONS,
+};
 
 #[cfg(test)]
 mod test;
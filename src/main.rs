@@ -1,5 +1,12 @@
-use clap::Parser;
-use minecraft_world::NbtFile;
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser};
+use minecraft_world::{
+    parse_layers, Area, BlockPattern, BlockState, BoundingBox, ChunkMetric, CompactOptions,
+    Dimension, GameMode, Generator, Heightmap, Image, ItemPredicate, MapArtOptions, Mirror,
+    NbtFile, PasteOptions, RenderOptions, Rotation, Schematic, Slice, Structure, TerrainOptions,
+    World, WorldBuilder,
+};
 #[derive(Parser)]
 enum Options {
     FromJson,
@@ -10,6 +17,348 @@ enum Options {
     ToMsgPack,
     CompressedFromMsgPack,
     CompressedToMsgPack,
+    /// search every container, entity and player in a world, prints matches as json
+    FindItems {
+        world: PathBuf,
+        #[arg(long)]
+        id: Option<String>,
+        /// substring of the custom name
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        enchantment: Option<String>,
+        /// path into the custom data, `a.b.c`
+        #[arg(long)]
+        custom_data: Option<String>,
+    },
+    /// replace every block matching `from`, like `mod:ore[lit=true]`, with `to`
+    ReplaceBlocks {
+        world: PathBuf,
+        from: BlockPattern,
+        to: BlockState,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        /// limit to a box, `x,y,z:x,y,z`
+        #[arg(long)]
+        bounds: Option<BoundingBox>,
+        /// limit to chunks, `x,z`, can be repeated
+        #[arg(long, value_parser = parse_pair)]
+        chunk: Vec<(i32, i32)>,
+    },
+    /// delete chunks players spent less than `min_inhabited` ticks in, with their entities and poi
+    Prune {
+        world: PathBuf,
+        #[arg(long)]
+        min_inhabited: i64,
+        /// also keep chunks this many chunks away from a kept chunk
+        #[arg(long, default_value_t = 0)]
+        radius: i32,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        /// only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// check region files for corrupt chunks, prints the problems as json
+    Validate {
+        world: PathBuf,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        /// drop or relocate bad chunks and rebuild the header, originals are kept as .mca.bak
+        #[arg(long)]
+        repair: bool,
+    },
+    /// rewrite region files with their chunks packed together
+    Compact {
+        world: PathBuf,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        /// recompress every chunk with `gzip`, `zlib` or `none`
+        #[arg(long, value_parser = minecraft_world::region::compression_from_name)]
+        compression: Option<u8>,
+        /// recompress every chunk at this level, 0 to 9
+        #[arg(long)]
+        level: Option<u32>,
+    },
+    /// delete every chunk outside the world border from level.dat
+    TrimBorder {
+        world: PathBuf,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        /// only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// copy the chunks between two corners into another world or dimension
+    CopyChunks {
+        source: PathBuf,
+        destination: PathBuf,
+        /// first corner, in chunks
+        #[arg(long, value_parser = parse_pair)]
+        min: (i32, i32),
+        #[arg(long, value_parser = parse_pair)]
+        max: (i32, i32),
+        /// chunks to move the copy by
+        #[arg(long, value_parser = parse_pair, default_value = "0,0")]
+        offset: (i32, i32),
+        #[arg(long, default_value = "overworld")]
+        from_dimension: Dimension,
+        #[arg(long, default_value = "overworld")]
+        to_dimension: Dimension,
+    },
+    /// write a new world with a level.dat and nothing generated yet
+    CreateWorld {
+        path: PathBuf,
+        #[arg(long, default_value = "New World")]
+        name: String,
+        /// random when left out
+        #[arg(long)]
+        seed: Option<i64>,
+        #[arg(long, default_value = "survival")]
+        game_mode: GameMode,
+        /// `default`, `void` or superflat layers, `minecraft:bedrock,2*minecraft:dirt,...`
+        #[arg(long, default_value = "default")]
+        generator: Generator,
+        /// write the overworld chunks up to this distance from 0,0 right away, flat and void only
+        #[arg(long)]
+        pregenerate: Option<i32>,
+    },
+    /// write flat or void chunks between two corners, replacing what is there
+    Generate {
+        world: PathBuf,
+        /// `void` or superflat layers, `minecraft:bedrock,2*minecraft:dirt,...`
+        generator: Generator,
+        #[arg(long, value_parser = parse_pair)]
+        min: (i32, i32),
+        #[arg(long, value_parser = parse_pair)]
+        max: (i32, i32),
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// build terrain from a grayscale png, black is the lowest height and white the highest
+    ImportHeightmap {
+        world: PathBuf,
+        image: PathBuf,
+        /// block x, z of the image's top left corner
+        #[arg(long, value_parser = parse_pair, default_value = "0,0")]
+        origin: (i32, i32),
+        /// blocks per pixel
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+        #[arg(long, default_value_t = 40)]
+        min_height: i32,
+        #[arg(long, default_value_t = 160)]
+        max_height: i32,
+        #[arg(long, default_value = "stone")]
+        fill: BlockState,
+        /// layers from the top down, `grass_block,3*dirt`
+        #[arg(long, default_value = "grass_block[snowy=false],3*dirt")]
+        surface: String,
+        /// surface layers below the sea level
+        #[arg(long, default_value = "3*sand")]
+        underwater: String,
+        #[arg(long, default_value_t = 62)]
+        sea_level: i32,
+        #[arg(long)]
+        no_water: bool,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// draw a top-down map, one `r.x.z.png` tile per region or with `--single` one image
+    Render {
+        world: PathBuf,
+        /// directory for the tiles, or the png with `--single`
+        output: PathBuf,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        #[arg(long)]
+        no_shading: bool,
+        /// the whole dimension in one image
+        #[arg(long)]
+        single: bool,
+        /// blocks per pixel with `--single`
+        #[arg(long, default_value_t = 1)]
+        blocks_per_pixel: u32,
+        /// draw the biomes at this y instead of the blocks
+        #[arg(long)]
+        biomes: Option<i32>,
+        /// color of a biome, `namespace:biome=rrggbb`, can be repeated
+        #[arg(long, value_parser = parse_biome_color)]
+        biome_color: Vec<(String, [u8; 3])>,
+    },
+    /// draw one plane through the world, a pixel per block, give exactly one of --x, --y, --z
+    #[command(group(ArgGroup::new("plane").required(true).args(["x", "y", "z"])))]
+    Slice {
+        world: PathBuf,
+        output: PathBuf,
+        /// the z, y plane at this x
+        #[arg(long)]
+        x: Option<i32>,
+        /// the x, z plane at this y
+        #[arg(long)]
+        y: Option<i32>,
+        /// the x, y plane at this z
+        #[arg(long)]
+        z: Option<i32>,
+        /// first corner in the plane, horizontal axis then y (z for --y)
+        #[arg(long, value_parser = parse_pair)]
+        min: (i32, i32),
+        #[arg(long, value_parser = parse_pair)]
+        max: (i32, i32),
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// color every chunk by a metric, writes a png with a legend or a csv by the extension
+    Heatmap {
+        world: PathBuf,
+        output: PathBuf,
+        /// inhabited-time, last-update, timestamp or size
+        #[arg(long, default_value = "inhabited-time")]
+        metric: ChunkMetric,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+        #[arg(long, default_value_t = 4)]
+        pixels_per_chunk: u32,
+        /// spread the colors on a logarithmic scale
+        #[arg(long)]
+        log: bool,
+    },
+    /// write map items as `map_<id>.png` to a directory, or with `--id` one map to a png
+    ExportMaps {
+        world: PathBuf,
+        output: PathBuf,
+        #[arg(long)]
+        id: Option<u32>,
+    },
+    /// turn a png into new map items and print their ids
+    MapArt {
+        world: PathBuf,
+        image: PathBuf,
+        /// maps across and down to stretch the image over, `columns,rows`
+        #[arg(long, value_parser = parse_pair)]
+        size: Option<(i32, i32)>,
+        #[arg(long)]
+        no_dither: bool,
+    },
+    /// copy a box into a Sponge schematic (`.schem`)
+    ExportSchematic {
+        world: PathBuf,
+        output: PathBuf,
+        /// the box to copy, `x,y,z:x,y,z`
+        bounds: BoundingBox,
+        /// sponge format version, 2 or 3
+        #[arg(long, default_value_t = 3)]
+        version: i32,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// paste a Sponge or MCEdit schematic with its lowest corner at `x,y,z`
+    PasteSchematic {
+        world: PathBuf,
+        schematic: PathBuf,
+        #[arg(value_parser = parse_position, allow_hyphen_values = true)]
+        origin: [i32; 3],
+        /// degrees clockwise, 0, 90, 180 or 270
+        #[arg(long, default_value = "0")]
+        rotate: Rotation,
+        /// none, x or z, applied before turning
+        #[arg(long, default_value = "none")]
+        mirror: Mirror,
+        /// keep the world's blocks where the schematic has air
+        #[arg(long)]
+        skip_air: bool,
+        #[arg(long)]
+        no_entities: bool,
+        #[arg(long)]
+        no_biomes: bool,
+        /// write chunks that don't exist yet as all air instead of skipping them
+        #[arg(long)]
+        create_chunks: bool,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// copy a box into a vanilla structure file (`.nbt`)
+    ExportStructure {
+        world: PathBuf,
+        output: PathBuf,
+        /// the box to copy, `x,y,z:x,y,z`
+        bounds: BoundingBox,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// place a vanilla structure file with its lowest corner at `x,y,z`
+    PlaceStructure {
+        world: PathBuf,
+        structure: PathBuf,
+        #[arg(value_parser = parse_position, allow_hyphen_values = true)]
+        origin: [i32; 3],
+        /// which of several palettes to use
+        #[arg(long, default_value_t = 0)]
+        palette: usize,
+        /// degrees clockwise, 0, 90, 180 or 270
+        #[arg(long, default_value = "0")]
+        rotate: Rotation,
+        /// none, x or z, applied before turning
+        #[arg(long, default_value = "none")]
+        mirror: Mirror,
+        #[arg(long)]
+        no_entities: bool,
+        /// write chunks that don't exist yet as all air instead of skipping them
+        #[arg(long)]
+        create_chunks: bool,
+        #[arg(long, default_value = "overworld")]
+        dimension: Dimension,
+    },
+    /// restore the chunks an edit session changed, lists the sessions without one
+    Undo {
+        world: PathBuf,
+        session: Option<String>,
+    },
+}
+
+fn parse_biome_color(s: &str) -> Result<(String, [u8; 3]), String> {
+    let invalid = || format!("invalid biome color {s}, expected biome=rrggbb");
+    let (biome, hex) = s.split_once('=').ok_or_else(invalid)?;
+    let rgb = u32::from_str_radix(hex.trim_start_matches('#'), 16).map_err(|_| invalid())?;
+    if hex.trim_start_matches('#').len() != 6 {
+        return Err(invalid());
+    }
+    let biome = match biome.contains(':') {
+        true => biome.to_string(),
+        false => format!("minecraft:{biome}"),
+    };
+    Ok((biome, [16, 8, 0].map(|e| (rgb >> e) as u8)))
+}
+
+fn parse_pair(s: &str) -> Result<(i32, i32), String> {
+    s.split_once(',')
+        .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)))
+        .ok_or_else(|| format!("invalid coordinates {s}, expected a,b"))
+}
+
+fn parse_position(s: &str) -> Result<[i32; 3], String> {
+    let invalid = || format!("invalid position {s}, expected x,y,z");
+    let coordinates = s
+        .split(',')
+        .map(|e| e.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<i32>, _>>()?;
+    coordinates.try_into().map_err(|_| invalid())
+}
+
+fn area(bounds: Option<BoundingBox>, chunks: Vec<(i32, i32)>) -> Area {
+    match bounds {
+        Some(bounds) => Area::Box(bounds),
+        None if !chunks.is_empty() => Area::Chunks(chunks),
+        None => Area::Dimension,
+    }
+}
+
+/// commands that only look at the world don't need the session lock
+fn open_world(path: PathBuf, write: bool) -> World {
+    match write {
+        true => World::open(path).unwrap(),
+        false => World::open_read_only(path).unwrap(),
+    }
 }
 
 fn main() {
@@ -53,5 +402,356 @@ fn main() {
             &NbtFile::from_compressed_reader(std::io::stdin()).unwrap(),
         )
         .unwrap(),
+        Options::FindItems {
+            world,
+            id,
+            name,
+            enchantment,
+            custom_data,
+        } => {
+            let predicate = ItemPredicate::All(
+                [
+                    id.map(ItemPredicate::Id),
+                    name.map(ItemPredicate::Name),
+                    enchantment.map(ItemPredicate::Enchantment),
+                    custom_data.map(ItemPredicate::CustomData),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            );
+            let matches = open_world(world, false).find_items(&predicate).unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &matches).unwrap()
+        }
+        Options::ReplaceBlocks {
+            world,
+            from,
+            to,
+            dimension,
+            bounds,
+            chunk,
+        } => {
+            let report = open_world(world, true)
+                .replace_blocks(dimension, &area(bounds, chunk), &from, &to)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::Prune {
+            world,
+            min_inhabited,
+            radius,
+            dimension,
+            dry_run,
+        } => {
+            let report = open_world(world, !dry_run)
+                .prune_inhabited(dimension, min_inhabited, radius, dry_run)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::Validate {
+            world,
+            dimension,
+            repair,
+        } => {
+            let reports = open_world(world, repair)
+                .validate(dimension, repair)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &reports).unwrap()
+        }
+        Options::Compact {
+            world,
+            dimension,
+            compression,
+            level,
+        } => {
+            let report = open_world(world, true)
+                .compact(dimension, CompactOptions { compression, level })
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::TrimBorder {
+            world,
+            dimension,
+            dry_run,
+        } => {
+            let report = open_world(world, !dry_run)
+                .trim_world_border(dimension, dry_run)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::CopyChunks {
+            source,
+            destination,
+            min,
+            max,
+            offset,
+            from_dimension,
+            to_dimension,
+        } => {
+            // the source is only read, so it can be the destination as well
+            let report = open_world(destination, true)
+                .copy_chunks(
+                    &open_world(source, false),
+                    from_dimension,
+                    min,
+                    max,
+                    to_dimension,
+                    offset,
+                )
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::CreateWorld {
+            path,
+            name,
+            seed,
+            game_mode,
+            generator,
+            pregenerate,
+        } => {
+            let mut builder = WorldBuilder::new(name)
+                .game_mode(game_mode)
+                .generator(generator);
+            if let Some(seed) = seed {
+                builder = builder.seed(seed);
+            }
+            if let Some(r) = pregenerate {
+                builder = builder.pregenerate((-r, -r), (r, r));
+            }
+            builder.create(path).unwrap();
+        }
+        Options::Generate {
+            world,
+            generator,
+            min,
+            max,
+            dimension,
+        } => {
+            let report = open_world(world, true)
+                .generate(dimension, &generator, min, max)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::ImportHeightmap {
+            world,
+            image,
+            origin,
+            scale,
+            min_height,
+            max_height,
+            fill,
+            surface,
+            underwater,
+            sea_level,
+            no_water,
+            dimension,
+        } => {
+            let options = TerrainOptions {
+                dimension,
+                origin,
+                scale,
+                heights: (min_height, max_height),
+                fill,
+                surface: parse_layers(&surface).unwrap(),
+                underwater: parse_layers(&underwater).unwrap(),
+                sea_level: (!no_water).then_some(sea_level),
+                ..Default::default()
+            };
+            let heightmap = Heightmap::open(&image).unwrap();
+            let report = open_world(world, true)
+                .import_heightmap(&heightmap, &options)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::Render {
+            world,
+            output,
+            dimension,
+            no_shading,
+            single,
+            blocks_per_pixel,
+            biomes,
+            biome_color,
+        } => {
+            let world = open_world(world, false);
+            let options = RenderOptions {
+                shading: !no_shading,
+                biomes,
+                biome_colors: biome_color.into_iter().collect(),
+                ..Default::default()
+            };
+            if single {
+                let (image, origin) = world
+                    .render_dimension(dimension, &options, blocks_per_pixel)
+                    .unwrap()
+                    .expect("no regions to render");
+                image.save(&output).unwrap();
+                serde_json::ser::to_writer(std::io::stdout(), &origin).unwrap()
+            } else {
+                std::fs::create_dir_all(&output).unwrap();
+                let tiles = world
+                    .render_tiles(dimension, &options, |(x, z), image| {
+                        image.save(&output.join(format!("r.{x}.{z}.png")))
+                    })
+                    .unwrap();
+                serde_json::ser::to_writer(std::io::stdout(), &tiles).unwrap()
+            }
+        }
+        Options::Slice {
+            world,
+            output,
+            x,
+            y,
+            z,
+            min,
+            max,
+            dimension,
+        } => {
+            let slice = match (x, y, z) {
+                (Some(x), None, None) => Slice::X(x),
+                (None, Some(y), None) => Slice::Y(y),
+                (None, None, Some(z)) => Slice::Z(z),
+                _ => unreachable!("clap takes exactly one of --x, --y, --z"),
+            };
+            open_world(world, false)
+                .render_slice(dimension, slice, min, max)
+                .unwrap()
+                .save(&output)
+                .unwrap();
+        }
+        Options::Heatmap {
+            world,
+            output,
+            metric,
+            dimension,
+            pixels_per_chunk,
+            log,
+        } => {
+            let heatmap = open_world(world, false)
+                .chunk_heatmap(dimension, metric)
+                .unwrap();
+            if output.extension().is_some_and(|e| e == "csv") {
+                let file = std::fs::File::create(&output).unwrap();
+                heatmap.to_csv(std::io::BufWriter::new(file)).unwrap();
+            } else {
+                heatmap.render(pixels_per_chunk, log).save(&output).unwrap();
+            }
+            serde_json::ser::to_writer(std::io::stdout(), &heatmap.origin()).unwrap()
+        }
+        Options::ExportMaps { world, output, id } => {
+            let world = open_world(world, false);
+            match id {
+                Some(id) => {
+                    let map = world.get_map(id).unwrap().expect("no map with this id");
+                    map.to_image().save(&output).unwrap();
+                    serde_json::ser::to_writer(std::io::stdout(), &map).unwrap()
+                }
+                None => {
+                    let report = world.export_maps(&output).unwrap();
+                    serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+                }
+            }
+        }
+        Options::MapArt {
+            world,
+            image,
+            size,
+            no_dither,
+        } => {
+            let options = MapArtOptions {
+                size: size.map(|(columns, rows)| (columns.max(0) as u32, rows.max(0) as u32)),
+                dither: !no_dither,
+            };
+            let image = Image::open(&image).unwrap();
+            let report = open_world(world, true)
+                .create_maps(&image, &options)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::ExportSchematic {
+            world,
+            output,
+            bounds,
+            version,
+            dimension,
+        } => {
+            let schematic = open_world(world, false)
+                .extract_schematic(dimension, bounds)
+                .unwrap();
+            schematic.save_sponge(&output, version).unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &schematic.summary()).unwrap()
+        }
+        Options::PasteSchematic {
+            world,
+            schematic,
+            origin,
+            rotate,
+            mirror,
+            skip_air,
+            no_entities,
+            no_biomes,
+            create_chunks,
+            dimension,
+        } => {
+            let schematic = Schematic::open(&schematic).unwrap();
+            let options = PasteOptions {
+                rotation: rotate,
+                mirror,
+                skip_air,
+                entities: !no_entities,
+                biomes: !no_biomes,
+                create_chunks,
+            };
+            let report = open_world(world, true)
+                .paste_schematic(dimension, &schematic, origin, &options)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::ExportStructure {
+            world,
+            output,
+            bounds,
+            dimension,
+        } => {
+            let schematic = open_world(world, false)
+                .extract_schematic(dimension, bounds)
+                .unwrap();
+            Structure::from_schematic(&schematic).save(&output).unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &schematic.summary()).unwrap()
+        }
+        Options::PlaceStructure {
+            world,
+            structure,
+            origin,
+            palette,
+            rotate,
+            mirror,
+            no_entities,
+            create_chunks,
+            dimension,
+        } => {
+            let structure = Structure::open(&structure).unwrap();
+            let options = PasteOptions {
+                rotation: rotate,
+                mirror,
+                entities: !no_entities,
+                create_chunks,
+                ..Default::default()
+            };
+            let report = open_world(world, true)
+                .place_structure(dimension, &structure, palette, origin, &options)
+                .unwrap();
+            serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+        }
+        Options::Undo { world, session } => match session {
+            Some(session) => {
+                let report = open_world(world, true).undo(&session).unwrap();
+                serde_json::ser::to_writer(std::io::stdout(), &report).unwrap()
+            }
+            None => {
+                let sessions = open_world(world, false).undo_sessions().unwrap();
+                serde_json::ser::to_writer(std::io::stdout(), &sessions).unwrap()
+            }
+        },
     }
 }
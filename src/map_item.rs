@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    builder::DATA_VERSION, image::Image, world::World, Error, Map, NbtFile, NbtList, NbtValue,
+    Result,
+};
+
+/// pixels along each side of a map
+pub const MAP_SIZE: u32 = 128;
+
+/// base colors of the vanilla map palette by id, id 0 is transparent
+const MAP_COLORS: [[u8; 3]; 62] = [
+    [0, 0, 0],
+    [127, 178, 56],
+    [247, 233, 163],
+    [199, 199, 199],
+    [255, 0, 0],
+    [160, 160, 255],
+    [167, 167, 167],
+    [0, 124, 0],
+    [255, 255, 255],
+    [164, 168, 184],
+    [151, 109, 77],
+    [112, 112, 112],
+    [64, 64, 255],
+    [143, 119, 72],
+    [255, 252, 245],
+    [216, 127, 51],
+    [178, 76, 216],
+    [102, 153, 216],
+    [229, 229, 51],
+    [127, 204, 25],
+    [242, 127, 165],
+    [76, 76, 76],
+    [153, 153, 153],
+    [76, 127, 153],
+    [127, 63, 178],
+    [51, 76, 178],
+    [102, 76, 51],
+    [102, 127, 51],
+    [153, 51, 51],
+    [25, 25, 25],
+    [250, 238, 77],
+    [92, 219, 213],
+    [74, 128, 255],
+    [0, 217, 58],
+    [129, 86, 49],
+    [112, 2, 0],
+    [209, 177, 161],
+    [159, 82, 36],
+    [149, 87, 108],
+    [112, 108, 138],
+    [186, 133, 36],
+    [103, 117, 53],
+    [160, 77, 78],
+    [57, 41, 35],
+    [135, 107, 98],
+    [87, 92, 92],
+    [122, 73, 88],
+    [76, 62, 92],
+    [76, 50, 35],
+    [76, 82, 42],
+    [142, 60, 46],
+    [37, 22, 16],
+    [189, 48, 49],
+    [148, 63, 97],
+    [92, 25, 29],
+    [22, 126, 134],
+    [58, 142, 140],
+    [86, 44, 62],
+    [20, 180, 133],
+    [100, 100, 100],
+    [216, 175, 147],
+    [127, 167, 150],
+];
+
+/// the low two bits of a map color pick one of these brightnesses, out of 255
+const MAP_SHADES: [u32; 4] = [180, 220, 255, 135];
+
+/// rgba of a byte in a map's `colors`, the base color id times 4 plus the shade; unknown ids
+/// are transparent like id 0
+pub fn map_color(byte: u8) -> [u8; 4] {
+    let (id, shade) = ((byte / 4) as usize, MAP_SHADES[(byte % 4) as usize]);
+    match MAP_COLORS.get(id) {
+        Some(base) if id > 0 => {
+            let [r, g, b] = base.map(|e| (e as u32 * shade / 255) as u8);
+            [r, g, b, 255]
+        }
+        _ => [0; 4],
+    }
+}
+
+/// the palette byte whose color is closest to `rgb`, weighted the way eyes see differences
+pub fn nearest_map_color([r, g, b]: [u8; 3]) -> u8 {
+    let distance = |[r2, g2, b2, _]: [u8; 4]| {
+        let mean = (r as i32 + r2 as i32) / 2;
+        let (dr, dg, db) = (
+            r as i32 - r2 as i32,
+            g as i32 - g2 as i32,
+            b as i32 - b2 as i32,
+        );
+        (((512 + mean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean) * db * db) >> 8)
+    };
+    (4..MAP_COLORS.len() as u8 * 4)
+        .min_by_key(|e| distance(map_color(*e)))
+        .unwrap()
+}
+
+/// palette bytes for every pixel of `image`, row by row; pixels that are mostly transparent
+/// become transparent, `dither` spreads the error of every pixel over its neighbours
+/// (floyd-steinberg) instead of dropping it
+pub fn quantize(image: &Image, dither: bool) -> Vec<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut pixels: Vec<[f64; 3]> = image
+        .pixels()
+        .iter()
+        .map(|e| [e[0], e[1], e[2]].map(|c| c as f64))
+        .collect();
+    let mut colors = vec![0; pixels.len()];
+    // images rarely have many distinct colors, even dithered
+    let mut nearest = HashMap::new();
+    for i in 0..pixels.len() {
+        if image.pixels()[i][3] < 128 {
+            continue;
+        }
+        let rgb = pixels[i].map(|e| e.round().clamp(0.0, 255.0) as u8);
+        colors[i] = *nearest.entry(rgb).or_insert_with(|| nearest_map_color(rgb));
+        if !dither {
+            continue;
+        }
+        let found = map_color(colors[i]);
+        let error = [0, 1, 2].map(|c| pixels[i][c] - found[c] as f64);
+        let (x, y) = (i % width, i / width);
+        for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+            let (nx, ny) = (x as isize + dx, y + dy);
+            if nx < 0 || nx as usize >= width || ny >= height {
+                continue;
+            }
+            let pixel = &mut pixels[ny * width + nx as usize];
+            for c in 0..3 {
+                pixel[c] += error[c] * weight / 16.0;
+            }
+        }
+    }
+    colors
+}
+
+/// a map item as saved in `data/map_<id>.dat`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapItem {
+    /// each pixel covers 2^scale blocks
+    pub scale: u8,
+    pub dimension: String,
+    /// block x, z of the middle of the map
+    pub center: (i32, i32),
+    pub locked: bool,
+    /// 128 by 128 palette bytes, row by row
+    #[serde(skip)]
+    pub colors: Vec<u8>,
+}
+
+impl MapItem {
+    /// reads the root of a map file, which keeps everything in `data`
+    pub fn from_nbt(nbt: &NbtValue) -> Result<Self> {
+        let data = nbt.get("data").ok_or(Error::Malformed(line!()))?;
+        let int = |key| data.get(key).and_then(NbtValue::as_i64).unwrap_or(0);
+        let colors = match data.get("colors") {
+            Some(NbtValue::ByteArray(v)) if v.len() == (MAP_SIZE * MAP_SIZE) as usize => {
+                v.iter().map(|e| *e as u8).collect()
+            }
+            _ => return Err(Error::Malformed(line!())),
+        };
+        // maps from before 1.16 store the dimension as a number
+        let dimension = match data.get("dimension") {
+            Some(NbtValue::String(s)) => s.clone(),
+            _ => match int("dimension") {
+                -1 => "minecraft:the_nether",
+                1 => "minecraft:the_end",
+                _ => "minecraft:overworld",
+            }
+            .to_string(),
+        };
+        Ok(Self {
+            scale: int("scale").clamp(0, 4) as u8,
+            dimension,
+            center: (int("xCenter") as i32, int("zCenter") as i32),
+            locked: int("locked") != 0,
+            colors,
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = NbtFile::from_compressed_reader(File::open(path).map_err(Error::FileError)?)?;
+        Self::from_nbt(&file.nbt)
+    }
+
+    /// a locked overworld map showing `colors`, for map art
+    pub fn new(colors: Vec<u8>) -> Result<Self> {
+        if colors.len() != (MAP_SIZE * MAP_SIZE) as usize {
+            return Err(Error::Custom(format!("{} colors for a map", colors.len())));
+        }
+        Ok(Self {
+            scale: 0,
+            dimension: "minecraft:overworld".to_string(),
+            center: (0, 0),
+            locked: true,
+            colors,
+        })
+    }
+
+    /// the root of a map file
+    pub fn to_nbt(&self) -> NbtValue {
+        let data = [
+            ("scale", NbtValue::Byte(self.scale as i8)),
+            ("dimension", NbtValue::String(self.dimension.clone())),
+            ("xCenter", NbtValue::Int(self.center.0)),
+            ("zCenter", NbtValue::Int(self.center.1)),
+            ("locked", NbtValue::Byte(self.locked as i8)),
+            ("trackingPosition", NbtValue::Byte(0)),
+            ("unlimitedTracking", NbtValue::Byte(0)),
+            ("banners", NbtValue::List(NbtList::EmptyList())),
+            ("frames", NbtValue::List(NbtList::EmptyList())),
+            (
+                "colors",
+                NbtValue::ByteArray(self.colors.iter().map(|e| *e as i8).collect()),
+            ),
+        ];
+        NbtValue::Compound(Map::from([
+            (
+                "data".to_string(),
+                NbtValue::Compound(Map::from(data.map(|(k, v)| (k.to_string(), v)))),
+            ),
+            ("DataVersion".to_string(), NbtValue::Int(DATA_VERSION)),
+        ]))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = NbtFile {
+            name: String::new(),
+            nbt: self.to_nbt(),
+        };
+        file.to_compressed_writer(File::create(path).map_err(Error::FileError)?)
+            .map_err(Error::FileError)
+    }
+
+    /// the map as the game draws it, 128 by 128 pixels
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(MAP_SIZE, MAP_SIZE);
+        for (i, byte) in self.colors.iter().enumerate() {
+            image.set(i as u32 % MAP_SIZE, i as u32 / MAP_SIZE, map_color(*byte));
+        }
+        image
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct MapExportReport {
+    /// ids of the maps written
+    pub maps: Vec<u32>,
+    /// ids of the maps that couldn't be read
+    pub unreadable: Vec<u32>,
+}
+
+/// how an image becomes map items
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapArtOptions {
+    /// maps across and down, the image is stretched to fill them; `None` keeps the image's
+    /// size and pads it with transparent pixels to whole maps
+    pub size: Option<(u32, u32)>,
+    pub dither: bool,
+}
+
+impl Default for MapArtOptions {
+    fn default() -> Self {
+        Self {
+            size: None,
+            dither: true,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct MapArtReport {
+    pub columns: u32,
+    pub rows: u32,
+    /// ids of the new maps, row by row from the top left
+    pub ids: Vec<u32>,
+}
+
+impl World {
+    /// ids of every `data/map_<id>.dat`, sorted
+    pub fn map_ids(&self) -> Result<Vec<u32>> {
+        let dir = self.path().join("data");
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut ids = vec![];
+        for entry in std::fs::read_dir(dir).map_err(Error::FileError)? {
+            let path = entry.map_err(Error::FileError)?.path();
+            let id = path.file_name().and_then(|e| e.to_str()).and_then(|e| {
+                e.strip_prefix("map_")?
+                    .strip_suffix(".dat")?
+                    .parse::<u32>()
+                    .ok()
+            });
+            ids.extend(id);
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    pub fn map_path(&self, id: u32) -> PathBuf {
+        self.path().join("data").join(format!("map_{id}.dat"))
+    }
+
+    /// the map item with this id, `None` when there is no file for it
+    pub fn get_map(&self, id: u32) -> Result<Option<MapItem>> {
+        let path = self.map_path(id);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        MapItem::open(&path).map(Some)
+    }
+
+    /// writes every map item of the world to `dir` as `map_<id>.png`, maps that can't be read
+    /// are reported instead of stopping the export
+    pub fn export_maps(&self, dir: &Path) -> Result<MapExportReport> {
+        std::fs::create_dir_all(dir).map_err(Error::FileError)?;
+        let mut report = MapExportReport::default();
+        for id in self.map_ids()? {
+            match MapItem::open(&self.map_path(id)) {
+                Ok(map) => {
+                    map.to_image().save(&dir.join(format!("map_{id}.png")))?;
+                    report.maps.push(id);
+                }
+                Err(_) => report.unreadable.push(id),
+            }
+        }
+        Ok(report)
+    }
+
+    /// turns `image` into new map items, one per 128 by 128 tile, with ids after the last one
+    /// in `data/idcounts.dat` and any existing map file; `idcounts.dat` is updated so the game
+    /// doesn't hand the ids out again
+    pub fn create_maps(&self, image: &Image, options: &MapArtOptions) -> Result<MapArtReport> {
+        self.check_writable()?;
+        let (columns, rows) = options.size.unwrap_or((
+            image.width().div_ceil(MAP_SIZE),
+            image.height().div_ceil(MAP_SIZE),
+        ));
+        if columns == 0 || rows == 0 {
+            return Err(Error::Custom("no maps to create".to_string()));
+        }
+        let (width, height) = (columns * MAP_SIZE, rows * MAP_SIZE);
+        let image = match options.size {
+            Some(_) => image.resize(width, height),
+            None => {
+                let mut padded = Image::new(width, height);
+                padded.paste(image, 0, 0);
+                padded
+            }
+        };
+        let colors = quantize(&image, options.dither);
+
+        let dir = self.path().join("data");
+        std::fs::create_dir_all(&dir).map_err(Error::FileError)?;
+        let counts_path = dir.join("idcounts.dat");
+        let mut counts = match counts_path.is_file() {
+            true => {
+                let file = File::open(&counts_path).map_err(Error::FileError)?;
+                NbtFile::from_compressed_reader(file)?.nbt
+            }
+            false => NbtValue::Compound(Map::new()),
+        };
+        let counted = counts
+            .get("data")
+            .and_then(|e| e.get("map"))
+            .and_then(NbtValue::as_i64)
+            .map(|e| e + 1);
+        let existing = self.map_ids()?.last().map(|e| *e as i64 + 1);
+        let first = counted.max(existing).unwrap_or(0) as u32;
+
+        let mut report = MapArtReport {
+            columns,
+            rows,
+            ids: vec![],
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                let id = first + report.ids.len() as u32;
+                let tile = (0..MAP_SIZE)
+                    .flat_map(|y| {
+                        let start = ((row * MAP_SIZE + y) * width + column * MAP_SIZE) as usize;
+                        colors[start..start + MAP_SIZE as usize].iter().copied()
+                    })
+                    .collect();
+                MapItem::new(tile)?.save(&self.map_path(id))?;
+                report.ids.push(id);
+            }
+        }
+
+        let last = NbtValue::Int(*report.ids.last().unwrap() as i32);
+        let root = counts.as_compound_mut().ok_or(Error::Malformed(line!()))?;
+        match root.get_mut("data").and_then(NbtValue::as_compound_mut) {
+            Some(data) => {
+                data.insert("map".to_string(), last);
+            }
+            None => {
+                let data = Map::from([("map".to_string(), last)]);
+                root.insert("data".to_string(), NbtValue::Compound(data));
+            }
+        }
+        root.insert("DataVersion".to_string(), NbtValue::Int(DATA_VERSION));
+        let file = NbtFile {
+            name: String::new(),
+            nbt: counts,
+        };
+        file.to_compressed_writer(File::create(&counts_path).map_err(Error::FileError)?)
+            .map_err(Error::FileError)?;
+        Ok(report)
+    }
+}
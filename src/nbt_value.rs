@@ -1,15 +1,15 @@
+// the list variants and the marker enum generated for them share the `List` postfix
+#![allow(clippy::enum_variant_names)]
+#[cfg(not(feature = "ordered_map"))]
+use std::collections::HashMap;
 use std::{
-    collections::HashMap,
     fmt::Debug,
     io::{Read, Write},
 };
 
-use either::Either;
 use enumtype_derive::enum_convert;
-use pyo3::{
-    exceptions::PyValueError, pyclass, pymethods, types::PyInt, Py, PyAny, PyErr, PyResult, Python,
-};
-use serde::{ser::SerializeMap, Deserialize, Serialize};
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
 
 use flate2::Compression;
 
@@ -36,9 +36,9 @@ type Map<A, B> = HashMap<A, B>;
 pub use map::Map;
 #[cfg(feature = "ordered_map")]
 mod map {
-    use std::{collections::HashMap, fmt::Debug, marker::PhantomData};
+    use std::{borrow::Borrow, fmt::Debug, marker::PhantomData};
 
-    use pyo3::{pyclass, FromPyObject, IntoPyObject};
+    use pyo3::{FromPyObject, IntoPyObject};
     use serde::{de::Visitor, ser::SerializeMap, Deserialize, Serialize};
 
     #[derive(Clone, PartialEq, FromPyObject, IntoPyObject)]
@@ -63,9 +63,66 @@ mod map {
             }
         }
 
+        pub fn get<Q>(&self, key: &Q) -> Option<&B>
+        where
+            A: Borrow<Q>,
+            Q: PartialEq + ?Sized,
+        {
+            self.0.iter().find(|e| e.0.borrow() == key).map(|e| &e.1)
+        }
+
+        pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut B>
+        where
+            A: Borrow<Q>,
+            Q: PartialEq + ?Sized,
+        {
+            self.0
+                .iter_mut()
+                .find(|e| e.0.borrow() == key)
+                .map(|e| &mut e.1)
+        }
+
+        pub fn remove<Q>(&mut self, key: &Q) -> Option<B>
+        where
+            A: Borrow<Q>,
+            Q: PartialEq + ?Sized,
+        {
+            let i = self.0.iter().position(|e| e.0.borrow() == key)?;
+            Some(self.0.remove(i).1)
+        }
+
+        pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            A: Borrow<Q>,
+            Q: PartialEq + ?Sized,
+        {
+            self.get(key).is_some()
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
         pub fn iter(&self) -> impl Iterator<Item = &(A, B)> {
             self.0.iter()
         }
+
+        pub fn values_mut(&mut self) -> impl Iterator<Item = &mut B> {
+            self.0.iter_mut().map(|e| &mut e.1)
+        }
+    }
+
+    impl<A, B> Default for Map<A, B>
+    where
+        A: PartialEq,
+    {
+        fn default() -> Self {
+            Self::new()
+        }
     }
     impl<A, B, C> From<C> for Map<A, B>
     where
@@ -158,7 +215,7 @@ mod map {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[pyclass]
+#[pyclass(from_py_object)]
 #[enum_convert]
 pub enum NbtValue {
     Byte(i8),
@@ -176,7 +233,7 @@ pub enum NbtValue {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[pyclass]
+#[pyclass(from_py_object)]
 #[enum_convert]
 pub enum NbtList {
     ByteList(Vec<i8>),
@@ -195,14 +252,116 @@ pub enum NbtList {
 }
 
 impl NbtValue {
-    pub fn from_reader<T>(reader: T) -> Result<(String, NbtValue)>
+    /// looks up `key` if this is a compound
+    pub fn get(&self, key: &str) -> Option<&NbtValue> {
+        self.as_compound()?.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut NbtValue> {
+        self.as_compound_mut()?.get_mut(key)
+    }
+
+    pub fn as_compound(&self) -> Option<&Map<String, NbtValue>> {
+        match self {
+            NbtValue::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Map<String, NbtValue>> {
+        match self {
+            NbtValue::Compound(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&NbtList> {
+        match self {
+            NbtValue::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// any integer tag, widened to i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtValue::Byte(v) => Some(*v as i64),
+            NbtValue::Short(v) => Some(*v as i64),
+            NbtValue::Int(v) => Some(*v as i64),
+            NbtValue::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// any numeric tag, converted to f64
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            NbtValue::Float(v) => Some(*v as f64),
+            NbtValue::Double(v) => Some(*v),
+            v => v.as_i64().map(|v| v as f64),
+        }
+    }
+}
+
+impl NbtList {
+    pub fn len(&self) -> usize {
+        match self {
+            NbtList::ByteList(v) => v.len(),
+            NbtList::ShortList(v) => v.len(),
+            NbtList::IntList(v) => v.len(),
+            NbtList::LongList(v) => v.len(),
+            NbtList::FloatList(v) => v.len(),
+            NbtList::DoubleList(v) => v.len(),
+            NbtList::ByteArrayList(v) => v.len(),
+            NbtList::StringList(v) => v.len(),
+            NbtList::ListList(v) => v.len(),
+            NbtList::CompoundList(v) => v.len(),
+            NbtList::IntArrayList(v) => v.len(),
+            NbtList::LongArrayList(v) => v.len(),
+            NbtList::EmptyList() => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// compound elements, empty for lists of any other type
+    pub fn compounds(&self) -> &[Map<String, NbtValue>] {
+        match self {
+            NbtList::CompoundList(v) => v,
+            _ => &[],
+        }
+    }
+
+    /// numeric elements converted to f64, empty for non numeric lists
+    pub fn to_f64_vec(&self) -> Vec<f64> {
+        match self {
+            NbtList::ByteList(v) => v.iter().map(|e| *e as f64).collect(),
+            NbtList::ShortList(v) => v.iter().map(|e| *e as f64).collect(),
+            NbtList::IntList(v) => v.iter().map(|e| *e as f64).collect(),
+            NbtList::LongList(v) => v.iter().map(|e| *e as f64).collect(),
+            NbtList::FloatList(v) => v.iter().map(|e| *e as f64).collect(),
+            NbtList::DoubleList(v) => v.clone(),
+            _ => vec![],
+        }
+    }
+}
+
+impl NbtValue {
+    pub fn from_reader<T>(mut reader: T) -> Result<(String, NbtValue)>
     where
         T: Read + Debug,
     {
-        let binding = reader
-            .bytes()
-            .map(|e| e.map_err(|e| Error::FileError(e)))
-            .collect::<Result<Vec<u8>>>()?;
+        let mut binding = Vec::new();
+        reader.read_to_end(&mut binding).map_err(Error::FileError)?;
         let mut iter = binding.iter();
         let tag = iter.next().ok_or(Error::Malformed(line!()))?.to_owned();
         let name = Self::string_from_iter(&mut iter)?;
@@ -242,10 +401,10 @@ impl NbtValue {
     where
         T: Iterator<Item = &'a u8> + Debug,
     {
-        let size = Self::i32_from_iter(&mut *iter)? as usize;
+        let size = Self::i32_from_iter(iter)? as usize;
         let mut output = Vec::with_capacity(size);
         for _ in 0..size {
-            output.push(Self::i64_from_iter(&mut *iter)?);
+            output.push(Self::i64_from_iter(iter)?);
         }
         Ok(output)
     }
@@ -480,9 +639,7 @@ impl NbtValue {
     where
         T: Iterator<Item = &'a u8> + Debug,
     {
-        eprintln!("{iter:?}");
         let size = Self::i32_from_iter(iter)? as usize;
-        eprintln!("{size}");
         let mut output = Vec::with_capacity(size);
         for _ in 0..size {
             output.push(i8::from_be_bytes([iter
@@ -552,12 +709,7 @@ impl NbtValue {
                 buffer.write_all(&[TAG_BYTE_ARRAY])?;
                 Self::push_string(buffer, name)?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                buffer.write_all(
-                    &v.iter()
-                        .map(|f| f.to_be_bytes())
-                        .flatten()
-                        .collect::<Vec<_>>(),
-                )?;
+                buffer.write_all(&v.iter().flat_map(|f| f.to_be_bytes()).collect::<Vec<_>>())?;
             }
             NbtValue::String(v) => {
                 buffer.write_all(&[TAG_STRING])?;
@@ -581,23 +733,13 @@ impl NbtValue {
                 buffer.write_all(&[TAG_INT_ARRAY])?;
                 Self::push_string(buffer, name)?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                buffer.write_all(
-                    &v.iter()
-                        .map(|f| f.to_be_bytes())
-                        .flatten()
-                        .collect::<Vec<_>>(),
-                )?;
+                buffer.write_all(&v.iter().flat_map(|f| f.to_be_bytes()).collect::<Vec<_>>())?;
             }
             NbtValue::LongArray(v) => {
-                buffer.write_all(&[TAG_INT_ARRAY])?;
+                buffer.write_all(&[TAG_LONG_ARRAY])?;
                 Self::push_string(buffer, name)?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                buffer.write_all(
-                    &v.iter()
-                        .map(|f| f.to_be_bytes())
-                        .flatten()
-                        .collect::<Vec<_>>(),
-                )?;
+                buffer.write_all(&v.iter().flat_map(|f| f.to_be_bytes()).collect::<Vec<_>>())?;
             }
         }
         Ok(())
@@ -654,20 +796,16 @@ impl NbtValue {
                 buffer.write_all(&[TAG_BYTE_ARRAY])?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
                 for i in v {
-                    buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                    buffer.write_all(
-                        &i.iter()
-                            .map(|e| e.to_be_bytes().into_iter())
-                            .flatten()
-                            .collect::<Vec<_>>(),
-                    )?;
+                    buffer.write_all(&(i.len() as i32).to_be_bytes())?;
+                    buffer
+                        .write_all(&i.iter().flat_map(|e| e.to_be_bytes()).collect::<Vec<_>>())?;
                 }
             }
             NbtList::StringList(v) => {
                 buffer.write_all(&[TAG_STRING])?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
                 for i in v {
-                    Self::push_string(buffer, &i)?;
+                    Self::push_string(buffer, i)?;
                 }
             }
             NbtList::ListList(v) => {
@@ -691,26 +829,18 @@ impl NbtValue {
                 buffer.write_all(&[TAG_INT_ARRAY])?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
                 for i in v {
-                    buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                    buffer.write_all(
-                        &i.iter()
-                            .map(|e| e.to_be_bytes().into_iter())
-                            .flatten()
-                            .collect::<Vec<_>>(),
-                    )?;
+                    buffer.write_all(&(i.len() as i32).to_be_bytes())?;
+                    buffer
+                        .write_all(&i.iter().flat_map(|e| e.to_be_bytes()).collect::<Vec<_>>())?;
                 }
             }
             NbtList::LongArrayList(v) => {
-                buffer.write_all(&[TAG_INT_ARRAY])?;
+                buffer.write_all(&[TAG_LONG_ARRAY])?;
                 buffer.write_all(&(v.len() as i32).to_be_bytes())?;
                 for i in v {
-                    buffer.write_all(&(v.len() as i32).to_be_bytes())?;
-                    buffer.write_all(
-                        &i.iter()
-                            .map(|e| e.to_be_bytes().into_iter())
-                            .flatten()
-                            .collect::<Vec<_>>(),
-                    )?;
+                    buffer.write_all(&(i.len() as i32).to_be_bytes())?;
+                    buffer
+                        .write_all(&i.iter().flat_map(|e| e.to_be_bytes()).collect::<Vec<_>>())?;
                 }
             }
             NbtList::EmptyList() => {
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{
+    region::{external_chunk_path, SECTOR_SIZE},
+    transaction::Transaction,
+    world::{Chunk, Dimension, LevelDat, RegionKind, World},
+    Error, NbtValue, Result,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct PruneReport {
+    /// terrain chunks deleted, their entity and poi chunks go with them
+    pub chunks: usize,
+    /// region files removed because nothing was left in them
+    pub regions_deleted: usize,
+    /// space the deleted chunks took up, whole region files when they are removed and the `.mcc`
+    /// files of oversized chunks
+    pub bytes_saved: u64,
+    /// further savings from packing the remaining chunks of rewritten region files tightly
+    pub bytes_repacked: u64,
+    pub dry_run: bool,
+}
+
+impl World {
+    /// deletes the chunks from the terrain, entity and poi regions of `dim`, all regions are
+    /// written in one transaction that isn't recorded for [`World::undo`], keeping the deleted
+    /// chunks would free no space
+    ///
+    /// with `dry_run` nothing is written, the report shows what would happen
+    pub fn delete_chunks(
+        &self,
+        dim: Dimension,
+        chunks: &HashSet<(i32, i32)>,
+        dry_run: bool,
+    ) -> Result<PruneReport> {
+        let transaction = (!dry_run)
+            .then(|| self.transaction().map(Transaction::without_undo))
+            .transpose()?;
+        let mut report = PruneReport {
+            dry_run,
+            ..Default::default()
+        };
+        let mut by_region = BTreeMap::<(i32, i32), Vec<(i32, i32)>>::new();
+        for (x, z) in chunks {
+            by_region
+                .entry((x >> 5, z >> 5))
+                .or_default()
+                .push((x & 31, z & 31));
+        }
+        for kind in RegionKind::ALL {
+            for ((rx, rz), locals) in by_region.iter() {
+                let Some(region) = self.get_existing_region(*rx, *rz, dim, kind)? else {
+                    continue;
+                };
+                // a dry run works on a copy so the open region stays untouched
+                let mut file = region.with_file(|f| f.clone());
+                let mut removed = 0;
+                let mut freed = 0;
+                for (x, z) in locals {
+                    let Some(chunk) = file.remove_chunk(*x, *z) else {
+                        continue;
+                    };
+                    removed += 1;
+                    freed += chunk.region_sectors();
+                    // the commit deletes the chunk's `.mcc` file as well
+                    if chunk.is_external() {
+                        let path = external_chunk_path(&region.path(), rx * 32 + x, rz * 32 + z);
+                        report.bytes_saved += std::fs::metadata(path)
+                            .map(|e| e.len())
+                            .unwrap_or(chunk.data.len() as u64);
+                    }
+                }
+                if removed == 0 {
+                    continue;
+                }
+                if kind == RegionKind::Chunks {
+                    report.chunks += removed;
+                }
+                let before = std::fs::metadata(region.path())
+                    .map(|e| e.len())
+                    .unwrap_or_default();
+                let after = if file.is_empty() {
+                    0
+                } else {
+                    file.to_bytes().0.len() as u64
+                };
+                if after == 0 {
+                    report.bytes_saved += before;
+                    report.regions_deleted += 1;
+                } else {
+                    let freed = (freed * SECTOR_SIZE) as u64;
+                    report.bytes_saved += freed;
+                    report.bytes_repacked += before.saturating_sub(after).saturating_sub(freed);
+                }
+                if let Some(transaction) = &transaction {
+                    let region = transaction.get_region(*rx, *rz, dim, kind)?;
+                    for (x, z) in locals {
+                        region.remove_chunk(*x, *z);
+                    }
+                    transaction.finish_region(*rx, *rz, dim, kind)?;
+                }
+            }
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(report)
+    }
+
+    /// `InhabitedTime` of every chunk in `dim`
+    pub fn inhabited_times(&self, dim: Dimension) -> Result<Vec<((i32, i32), i64)>> {
+        let mut scan = self.par_chunks(dim).run(|_, nbt| {
+            Chunk::root(nbt)
+                .get("InhabitedTime")
+                .and_then(NbtValue::as_i64)
+                .unwrap_or_default()
+        })?;
+        // pruning on a partial read could delete chunks that should be kept
+        match scan.errors.pop() {
+            Some(e) => Err(e.error),
+            None => Ok(scan.results),
+        }
+    }
+
+    /// deletes every chunk players spent less than `min_inhabited` ticks in, chunks
+    /// within `radius` chunks of one that is kept are kept as well
+    pub fn prune_inhabited(
+        &self,
+        dim: Dimension,
+        min_inhabited: i64,
+        radius: i32,
+        dry_run: bool,
+    ) -> Result<PruneReport> {
+        let times = self.inhabited_times(dim)?;
+        let mut keep = HashSet::new();
+        for ((x, z), time) in times.iter() {
+            if *time >= min_inhabited {
+                for dz in -radius..=radius {
+                    for dx in -radius..=radius {
+                        keep.insert((x + dx, z + dz));
+                    }
+                }
+            }
+        }
+        let delete = times
+            .into_iter()
+            .map(|e| e.0)
+            .filter(|e| !keep.contains(e))
+            .collect();
+        self.delete_chunks(dim, &delete, dry_run)
+    }
+}
+
+/// the world border from level.dat, in block coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// length of a side
+    pub size: f64,
+}
+
+impl WorldBorder {
+    pub fn from_level_dat(level_dat: &LevelDat) -> Result<Self> {
+        level_dat.data(|data| {
+            let value = |key| {
+                data.and_then(|e| e.get(key))
+                    .and_then(NbtValue::as_f64)
+                    .ok_or_else(|| Error::Custom(format!("level.dat has no {key}")))
+            };
+            Ok(Self {
+                center_x: value("BorderCenterX")?,
+                center_z: value("BorderCenterZ")?,
+                size: value("BorderSize")?,
+            })
+        })
+    }
+
+    /// the border as it applies in `dim`, the game moves the nether border's center by the
+    /// coordinate scale of 8 but keeps its size
+    pub fn in_dimension(&self, dim: Dimension) -> Self {
+        match dim {
+            Dimension::Nether => Self {
+                center_x: self.center_x / 8.0,
+                center_z: self.center_z / 8.0,
+                size: self.size,
+            },
+            _ => *self,
+        }
+    }
+
+    /// true when no block of chunk x z is inside the border
+    pub fn chunk_outside(&self, x: i32, z: i32) -> bool {
+        let half = self.size / 2.0;
+        let outside = |chunk: i32, center: f64| {
+            let (min, max) = (chunk as f64 * 16.0, chunk as f64 * 16.0 + 16.0);
+            max <= center - half || min >= center + half
+        };
+        outside(x, self.center_x) || outside(z, self.center_z)
+    }
+}
+
+impl World {
+    /// deletes every chunk lying entirely outside the world border
+    pub fn trim_world_border(&self, dim: Dimension, dry_run: bool) -> Result<PruneReport> {
+        let border = WorldBorder::from_level_dat(&self.get_level_dat()?)?.in_dimension(dim);
+        let mut delete = HashSet::new();
+        for kind in RegionKind::ALL {
+            delete.extend(
+                self.chunk_positions(dim, kind)?
+                    .into_iter()
+                    .filter(|(x, z)| border.chunk_outside(*x, *z)),
+            );
+        }
+        self.delete_chunks(dim, &delete, dry_run)
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::Compression;
+
+use crate::{transaction, Error, NbtValue, Result};
+
+pub const SECTOR_SIZE: usize = 4096;
+pub const CHUNKS_PER_REGION: usize = 1024;
+
+pub const COMPRESSION_GZIP: u8 = 1;
+pub const COMPRESSION_ZLIB: u8 = 2;
+pub const COMPRESSION_NONE: u8 = 3;
+pub const COMPRESSION_LZ4: u8 = 4;
+/// a compression named at the start of the payload, only the game's plugins can read it
+pub const COMPRESSION_CUSTOM: u8 = 127;
+/// set on the compression byte when the payload lives in a `c.x.z.mcc` file
+pub const COMPRESSION_EXTERNAL: u8 = 128;
+/// what the game uses
+pub const DEFAULT_LEVEL: u32 = 6;
+
+/// largest payload that still fits inside the region file itself
+const MAX_INTERNAL_SECTORS: usize = 255;
+
+/// index of a chunk inside a region, x and z are taken modulo 32
+pub fn chunk_index(x: i32, z: i32) -> usize {
+    (x & 31) as usize + (z & 31) as usize * 32
+}
+
+/// parses the region coordinates out of a `r.x.z.mca` file name
+pub fn region_coords(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((x, z))
+}
+
+pub fn region_file_name(x: i32, z: i32) -> String {
+    format!("r.{x}.{z}.mca")
+}
+
+/// whether chunks can be written in this compression, lz4 and custom ones can only be read
+pub fn can_compress(compression: u8) -> bool {
+    matches!(
+        compression,
+        COMPRESSION_GZIP | COMPRESSION_ZLIB | COMPRESSION_NONE
+    )
+}
+
+/// compression type by name, `gzip`, `zlib` or `none`
+pub fn compression_from_name(name: &str) -> Result<u8> {
+    match name {
+        "gzip" => Ok(COMPRESSION_GZIP),
+        "zlib" => Ok(COMPRESSION_ZLIB),
+        "none" => Ok(COMPRESSION_NONE),
+        _ => Err(Error::Custom(format!("unknown compression {name}"))),
+    }
+}
+
+/// reads the block stream lz4-java writes, every block has a 21 byte header (`LZ4Block`,
+/// method and level, compressed and raw length, checksum) and an empty block ends the stream;
+/// checksums aren't verified
+fn lz4_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    const HEADER: usize = 21;
+    let mut raw = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let header = rest.get(..HEADER).ok_or(Error::CompressionError)?;
+        if &header[..8] != b"LZ4Block" {
+            return Err(Error::CompressionError);
+        }
+        let int = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap()) as usize;
+        let (compressed, length) = (int(9), int(13));
+        let block = rest
+            .get(HEADER..HEADER + compressed)
+            .ok_or(Error::CompressionError)?;
+        match header[8] & 0xf0 {
+            0x10 if compressed == length => raw.extend_from_slice(block),
+            0x20 => raw.extend(
+                lz4_flex::block::decompress(block, length).map_err(|_| Error::CompressionError)?,
+            ),
+            _ => return Err(Error::CompressionError),
+        }
+        if length == 0 {
+            break;
+        }
+        rest = &rest[HEADER + compressed..];
+    }
+    Ok(raw)
+}
+
+/// chunk payload as stored in a region file, still compressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawChunk {
+    pub compression: u8,
+    pub timestamp: u32,
+    pub data: Vec<u8>,
+}
+
+impl RawChunk {
+    pub fn encode(nbt: &NbtValue, compression: u8, timestamp: u32) -> Result<Self> {
+        let mut raw = Vec::new();
+        nbt.serialize("", &mut raw).map_err(Error::FileError)?;
+        Self::compress(&raw, compression, DEFAULT_LEVEL, timestamp)
+    }
+
+    /// compresses serialized nbt, `level` goes from 0 to 9
+    pub fn compress(raw: &[u8], compression: u8, level: u32, timestamp: u32) -> Result<Self> {
+        let level = Compression::new(level.min(9));
+        let mut data = Vec::new();
+        match compression {
+            COMPRESSION_GZIP => {
+                let mut encoder = flate2::write::GzEncoder::new(&mut data, level);
+                encoder.write_all(raw).map_err(Error::FileError)?;
+                encoder.finish().map_err(Error::FileError)?;
+            }
+            COMPRESSION_ZLIB => {
+                let mut encoder = flate2::write::ZlibEncoder::new(&mut data, level);
+                encoder.write_all(raw).map_err(Error::FileError)?;
+                encoder.finish().map_err(Error::FileError)?;
+            }
+            COMPRESSION_NONE => data.extend_from_slice(raw),
+            _ => return Err(Error::CompressionError),
+        }
+        Ok(Self {
+            compression,
+            timestamp,
+            data,
+        })
+    }
+
+    /// the serialized nbt
+    pub fn decompress(&self) -> Result<Vec<u8>> {
+        let mut raw = Vec::new();
+        match self.compression {
+            COMPRESSION_GZIP => flate2::read::GzDecoder::new(&*self.data).read_to_end(&mut raw),
+            COMPRESSION_ZLIB => flate2::read::ZlibDecoder::new(&*self.data).read_to_end(&mut raw),
+            COMPRESSION_NONE => return Ok(self.data.clone()),
+            COMPRESSION_LZ4 => return lz4_decompress(&self.data),
+            _ => return Err(Error::CompressionError),
+        }
+        .map_err(|_| Error::CompressionError)?;
+        Ok(raw)
+    }
+
+    pub fn decode(&self) -> Result<NbtValue> {
+        let (_, nbt) = NbtValue::from_reader(&*self.decompress()?)?;
+        Ok(nbt)
+    }
+
+    pub fn recompress(&self, compression: u8, level: u32) -> Result<Self> {
+        Self::compress(&self.decompress()?, compression, level, self.timestamp)
+    }
+
+    /// sectors needed to store this chunk inside the region file
+    fn sectors(&self) -> usize {
+        (self.data.len() + 5).div_ceil(SECTOR_SIZE)
+    }
+
+    /// sectors the chunk takes up in the region file, oversized chunks only keep one there
+    pub fn region_sectors(&self) -> usize {
+        match self.sectors() {
+            _ if self.is_external() => 1,
+            sectors => sectors,
+        }
+    }
+
+    /// whether the chunk is too large for the region file and lives in a `.mcc` file
+    pub fn is_external(&self) -> bool {
+        self.sectors() > MAX_INTERNAL_SECTORS
+    }
+}
+
+/// a header entry of a region file that points at no readable chunk
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnreadableChunk {
+    /// position inside the region, 0..32
+    pub x: i32,
+    pub z: i32,
+    pub reason: String,
+}
+
+/// the compression and payload of the chunk at header entry `i`
+fn read_slot(
+    bytes: &[u8],
+    i: usize,
+    location: u32,
+    path: Option<&Path>,
+) -> std::result::Result<(u8, Vec<u8>), String> {
+    let offset = (location >> 8) as usize * SECTOR_SIZE;
+    let header = bytes
+        .get(offset..offset + 5)
+        .ok_or_else(|| format!("offset {offset} is past the end of the file"))?;
+    let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let compression = header[4];
+    if length == 0 {
+        return Err("the chunk has a length of 0".to_string());
+    }
+    if compression & COMPRESSION_EXTERNAL == 0 {
+        let data = bytes
+            .get(offset + 5..offset + 4 + length)
+            .ok_or_else(|| format!("length {length} runs past the end of the file"))?;
+        return Ok((compression, data.to_vec()));
+    }
+    let (rx, rz) = path
+        .and_then(region_coords)
+        .ok_or("an external chunk without a region file name")?;
+    let external = external_chunk_path(
+        path.unwrap(),
+        rx * 32 + (i % 32) as i32,
+        rz * 32 + (i / 32) as i32,
+    );
+    let data = std::fs::read(&external).map_err(|e| format!("{}: {e}", external.display()))?;
+    Ok((compression & !COMPRESSION_EXTERNAL, data))
+}
+
+/// in memory copy of a `.mca` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionFile {
+    chunks: Vec<Option<RawChunk>>,
+    unreadable: Vec<UnreadableChunk>,
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegionFile {
+    pub fn new() -> Self {
+        Self {
+            chunks: vec![None; CHUNKS_PER_REGION],
+            unreadable: vec![],
+        }
+    }
+
+    /// reads a region file, external `.mcc` chunks are looked up next to it
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(Error::FileError)?;
+        Self::from_bytes(&bytes, Some(path))
+    }
+
+    /// parses a region file, a header entry that points outside the file or at a missing
+    /// `.mcc` file is left out and listed in [`RegionFile::unreadable`] instead of failing the
+    /// whole region
+    pub fn from_bytes(bytes: &[u8], path: Option<&Path>) -> Result<Self> {
+        let mut region = Self::new();
+        if bytes.is_empty() {
+            return Ok(region);
+        }
+        if bytes.len() < SECTOR_SIZE * 2 {
+            return Err(Error::Malformed(line!()));
+        }
+        for i in 0..CHUNKS_PER_REGION {
+            let location = u32::from_be_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+            if location == 0 {
+                continue;
+            }
+            let timestamp = u32::from_be_bytes(
+                bytes[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4]
+                    .try_into()
+                    .unwrap(),
+            );
+            match read_slot(bytes, i, location, path) {
+                Ok((compression, data)) => {
+                    region.chunks[i] = Some(RawChunk {
+                        compression,
+                        timestamp,
+                        data,
+                    })
+                }
+                Err(reason) => region.unreadable.push(UnreadableChunk {
+                    x: (i % 32) as i32,
+                    z: (i / 32) as i32,
+                    reason,
+                }),
+            }
+        }
+        Ok(region)
+    }
+
+    /// header entries whose chunk couldn't be read, they are gone once the region is saved
+    pub fn unreadable(&self) -> &[UnreadableChunk] {
+        &self.unreadable
+    }
+
+    pub fn chunk(&self, x: i32, z: i32) -> Option<&RawChunk> {
+        self.chunks[chunk_index(x, z)].as_ref()
+    }
+
+    pub fn set_chunk(&mut self, x: i32, z: i32, chunk: RawChunk) -> Option<RawChunk> {
+        self.chunks[chunk_index(x, z)].replace(chunk)
+    }
+
+    pub fn remove_chunk(&mut self, x: i32, z: i32) -> Option<RawChunk> {
+        self.chunks[chunk_index(x, z)].take()
+    }
+
+    pub fn read_chunk(&self, x: i32, z: i32) -> Result<Option<NbtValue>> {
+        self.chunk(x, z).map(RawChunk::decode).transpose()
+    }
+
+    pub fn write_chunk(&mut self, x: i32, z: i32, nbt: &NbtValue, timestamp: u32) -> Result<()> {
+        let compression = self
+            .chunk(x, z)
+            .map(|e| e.compression)
+            .filter(|e| matches!(*e, COMPRESSION_GZIP | COMPRESSION_ZLIB | COMPRESSION_NONE))
+            .unwrap_or(COMPRESSION_ZLIB);
+        self.set_chunk(x, z, RawChunk::encode(nbt, compression, timestamp)?);
+        Ok(())
+    }
+
+    /// positions (0..32) of every chunk present in this region
+    pub fn positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|e| e.1.is_some())
+            .map(|(i, _)| ((i % 32) as i32, (i / 32) as i32))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.iter().all(Option::is_none)
+    }
+
+    /// serializes the region with every chunk packed in slot order, oversized chunks
+    /// are returned separately as `(index, data)` to be stored as `.mcc` files
+    pub fn to_bytes(&self) -> (Vec<u8>, Vec<(usize, &RawChunk)>) {
+        let mut header = vec![0; SECTOR_SIZE * 2];
+        let mut body = Vec::new();
+        let mut external = Vec::new();
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let Some(chunk) = chunk else {
+                continue;
+            };
+            let offset = 2 + body.len() / SECTOR_SIZE;
+            let sectors = if chunk.is_external() {
+                external.push((i, chunk));
+                body.extend_from_slice(&1u32.to_be_bytes());
+                body.push(chunk.compression | COMPRESSION_EXTERNAL);
+                1
+            } else {
+                body.extend_from_slice(&(chunk.data.len() as u32 + 1).to_be_bytes());
+                body.push(chunk.compression);
+                body.extend_from_slice(&chunk.data);
+                chunk.sectors()
+            };
+            body.resize((offset - 2 + sectors) * SECTOR_SIZE, 0);
+            header[i * 4..i * 4 + 4]
+                .copy_from_slice(&((offset as u32) << 8 | sectors as u32).to_be_bytes());
+            header[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4]
+                .copy_from_slice(&chunk.timestamp.to_be_bytes());
+        }
+        header.extend(body);
+        (header, external)
+    }
+
+    /// writes the region to `path`, `.mcc` files are written next to it; every file is
+    /// replaced atomically so a crash leaves the old or the new one, never a truncated one
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let (bytes, external) = self.to_bytes();
+        if !external.is_empty() {
+            let (rx, rz) = region_coords(path).ok_or(Error::Malformed(line!()))?;
+            for (i, chunk) in external {
+                transaction::replace_file(
+                    &external_chunk_path(
+                        path,
+                        rx * 32 + (i % 32) as i32,
+                        rz * 32 + (i / 32) as i32,
+                    ),
+                    &chunk.data,
+                )?;
+            }
+        }
+        transaction::replace_file(path, &bytes)
+    }
+}
+
+pub(crate) fn external_chunk_path(region: &Path, x: i32, z: i32) -> PathBuf {
+    region.with_file_name(format!("c.{x}.{z}.mcc"))
+}
@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, Map, NbtList, NbtValue, Result,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct CopyReport {
+    /// terrain chunks copied
+    pub chunks: usize,
+    pub entity_chunks: usize,
+    pub poi_chunks: usize,
+    /// entity and poi chunks removed from the destination because the source chunk had none
+    pub cleared_chunks: usize,
+    /// edit session to pass to [`World::undo`]
+    pub session: Option<String>,
+}
+
+fn out_of_range() -> Error {
+    Error::Custom("a position ends up out of range".to_string())
+}
+
+/// adds `d` to an int, failing instead of overflowing
+fn add(v: &mut i32, d: i32) -> Result<()> {
+    *v = v.checked_add(d).ok_or_else(out_of_range)?;
+    Ok(())
+}
+
+/// adds `d` to a number of any type, failing when it doesn't fit the type
+fn shift(value: Option<&mut NbtValue>, d: i32) -> Result<()> {
+    match value {
+        Some(NbtValue::Byte(v)) => {
+            *v = i8::try_from(d)
+                .ok()
+                .and_then(|d| v.checked_add(d))
+                .ok_or_else(out_of_range)?
+        }
+        Some(NbtValue::Short(v)) => {
+            *v = i16::try_from(d)
+                .ok()
+                .and_then(|d| v.checked_add(d))
+                .ok_or_else(out_of_range)?
+        }
+        Some(NbtValue::Int(v)) => add(v, d)?,
+        Some(NbtValue::Long(v)) => *v = v.checked_add(d as i64).ok_or_else(out_of_range)?,
+        Some(NbtValue::Float(v)) => *v += d as f32,
+        Some(NbtValue::Double(v)) => *v += d as f64,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn compounds_mut(value: Option<&mut NbtValue>) -> &mut [Map<String, NbtValue>] {
+    match value {
+        Some(NbtValue::List(NbtList::CompoundList(v))) => v,
+        _ => &mut [],
+    }
+}
+
+/// moves things stored with `x` and `z` keys, block entities and scheduled ticks
+fn shift_xz(list: &mut [Map<String, NbtValue>], bx: i32, bz: i32) -> Result<()> {
+    for e in list {
+        shift(e.get_mut("x"), bx)?;
+        shift(e.get_mut("z"), bz)?;
+    }
+    Ok(())
+}
+
+/// moves an entity and its passengers by `bx`, `bz` blocks
+fn relocate_entity(entity: &mut Map<String, NbtValue>, bx: i32, bz: i32) -> Result<()> {
+    if let Some(NbtValue::List(NbtList::DoubleList(pos))) = entity.get_mut("Pos") {
+        if pos.len() == 3 {
+            pos[0] += bx as f64;
+            pos[2] += bz as f64;
+        }
+    }
+    // paintings and item frames hang on a block
+    shift(entity.get_mut("TileX"), bx)?;
+    shift(entity.get_mut("TileZ"), bz)?;
+    if let Some(NbtValue::IntArray(pos)) = entity.get_mut("block_pos") {
+        if pos.len() == 3 {
+            add(&mut pos[0], bx)?;
+            add(&mut pos[2], bz)?;
+        }
+    }
+    for passenger in compounds_mut(entity.get_mut("Passengers")) {
+        relocate_entity(passenger, bx, bz)?;
+    }
+    Ok(())
+}
+
+/// moves structure starts and references, pieces keep their bounding boxes in `BB`
+fn relocate_structures(structures: &mut NbtValue, dx: i32, dz: i32) -> Result<()> {
+    let (bx, bz) = blocks(dx, dz)?;
+    for key in ["References", "references"] {
+        let Some(references) = structures.get_mut(key).and_then(NbtValue::as_compound_mut) else {
+            continue;
+        };
+        for chunks in references.values_mut() {
+            if let NbtValue::LongArray(chunks) = chunks {
+                for chunk in chunks.iter_mut() {
+                    let (mut x, mut z) = (*chunk as i32, (*chunk >> 32) as i32);
+                    add(&mut x, dx)?;
+                    add(&mut z, dz)?;
+                    *chunk = x as u32 as i64 | (z as i64) << 32;
+                }
+            }
+        }
+    }
+    for key in ["starts", "Starts"] {
+        let Some(starts) = structures.get_mut(key).and_then(NbtValue::as_compound_mut) else {
+            continue;
+        };
+        for start in starts.values_mut() {
+            shift(start.get_mut("ChunkX"), dx)?;
+            shift(start.get_mut("ChunkZ"), dz)?;
+            for piece in compounds_mut(start.get_mut("Children")) {
+                if let Some(NbtValue::IntArray(bb)) = piece.get_mut("BB") {
+                    if bb.len() == 6 {
+                        add(&mut bb[0], bx)?;
+                        add(&mut bb[2], bz)?;
+                        add(&mut bb[3], bx)?;
+                        add(&mut bb[5], bz)?;
+                    }
+                }
+                // template and jigsaw pieces
+                for (x, z) in [("TPX", "TPZ"), ("PosX", "PosZ")] {
+                    shift(piece.get_mut(x), bx)?;
+                    shift(piece.get_mut(z), bz)?;
+                }
+                for junction in compounds_mut(piece.get_mut("junctions")) {
+                    shift(junction.get_mut("source_x"), bx)?;
+                    shift(junction.get_mut("source_z"), bz)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// an offset in chunks as blocks
+fn blocks(dx: i32, dz: i32) -> Result<(i32, i32)> {
+    match (dx.checked_mul(16), dz.checked_mul(16)) {
+        (Some(bx), Some(bz)) => Ok((bx, bz)),
+        _ => Err(out_of_range()),
+    }
+}
+
+/// rewrites every absolute position in a chunk read from a region of `kind` so it can be
+/// stored `dx`, `dz` chunks away, fails when a position no longer fits its type
+///
+/// covers chunk positions, block entities, scheduled ticks, structures, entities and poi
+/// records, positions kept inside block entity or entity data (beehive flowers, brain
+/// memories, ...) stay as they are
+pub fn relocate_chunk(nbt: &mut NbtValue, kind: RegionKind, dx: i32, dz: i32) -> Result<()> {
+    let (bx, bz) = blocks(dx, dz)?;
+    match kind {
+        RegionKind::Chunks => {
+            let root = Chunk::root_mut(nbt);
+            shift(root.get_mut("xPos"), dx)?;
+            shift(root.get_mut("zPos"), dz)?;
+            for key in [
+                "block_entities",
+                "TileEntities",
+                "block_ticks",
+                "fluid_ticks",
+                "TileTicks",
+                "LiquidTicks",
+            ] {
+                shift_xz(compounds_mut(root.get_mut(key)), bx, bz)?;
+            }
+            // entities were stored with the terrain before 1.17
+            for entity in compounds_mut(root.get_mut("Entities")) {
+                relocate_entity(entity, bx, bz)?;
+            }
+            for key in ["structures", "Structures"] {
+                if let Some(structures) = root.get_mut(key) {
+                    relocate_structures(structures, dx, dz)?;
+                }
+            }
+        }
+        RegionKind::Entities => {
+            if let Some(NbtValue::IntArray(pos)) = nbt.get_mut("Position") {
+                if pos.len() == 2 {
+                    add(&mut pos[0], dx)?;
+                    add(&mut pos[1], dz)?;
+                }
+            }
+            for entity in compounds_mut(nbt.get_mut("Entities")) {
+                relocate_entity(entity, bx, bz)?;
+            }
+        }
+        RegionKind::Poi => {
+            let Some(sections) = nbt.get_mut("Sections").and_then(NbtValue::as_compound_mut) else {
+                return Ok(());
+            };
+            for section in sections.values_mut() {
+                for record in compounds_mut(section.get_mut("Records")) {
+                    if let Some(NbtValue::IntArray(pos)) = record.get_mut("pos") {
+                        if pos.len() == 3 {
+                            add(&mut pos[0], bx)?;
+                            add(&mut pos[2], bz)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl World {
+    /// copies the chunks between corners `min` and `max` of `source` into this world, moved
+    /// by `offset` chunks, with their entities and poi
+    ///
+    /// the chunks are read before anything is written, so a source area that overlaps the
+    /// destination in the same world is copied as it was; everything is written in one
+    /// transaction
+    ///
+    /// entity and poi chunks already at the destination of a copied terrain chunk are removed
+    /// when the source has none, so old mobs and poi don't end up on the new terrain
+    pub fn copy_chunks(
+        &self,
+        source: &World,
+        from: Dimension,
+        min: (i32, i32),
+        max: (i32, i32),
+        to: Dimension,
+        offset: (i32, i32),
+    ) -> Result<CopyReport> {
+        let (min, max) = (
+            (min.0.min(max.0), min.1.min(max.1)),
+            (min.0.max(max.0), min.1.max(max.1)),
+        );
+        let transaction = self.transaction()?;
+        let mut report = CopyReport::default();
+        let mut copied = HashMap::<RegionKind, HashSet<(i32, i32)>>::new();
+        for kind in RegionKind::ALL {
+            for (rx, rz) in source.region_positions(from, kind)? {
+                if rx < min.0 >> 5 || rx > max.0 >> 5 || rz < min.1 >> 5 || rz > max.1 >> 5 {
+                    continue;
+                }
+                let Some(region) = source.get_existing_region(rx, rz, from, kind)? else {
+                    continue;
+                };
+                for (lx, lz) in region.chunk_positions() {
+                    let (x, z) = (rx * 32 + lx, rz * 32 + lz);
+                    if x < min.0 || x > max.0 || z < min.1 || z > max.1 {
+                        continue;
+                    }
+                    let Some(chunk) = region.get_chunk(lx, lz)? else {
+                        continue;
+                    };
+                    let mut nbt = chunk.read(NbtValue::clone);
+                    relocate_chunk(&mut nbt, kind, offset.0, offset.1)?;
+                    let (x, z) = match (x.checked_add(offset.0), z.checked_add(offset.1)) {
+                        (Some(x), Some(z)) => (x, z),
+                        _ => return Err(out_of_range()),
+                    };
+                    copied.entry(kind).or_default().insert((x, z));
+                    transaction
+                        .get_region(x >> 5, z >> 5, to, kind)?
+                        .set_chunk(x & 31, z & 31, &Chunk::new(nbt))?;
+                    match kind {
+                        RegionKind::Chunks => report.chunks += 1,
+                        RegionKind::Entities => report.entity_chunks += 1,
+                        RegionKind::Poi => report.poi_chunks += 1,
+                    }
+                }
+            }
+        }
+        let terrain = copied.remove(&RegionKind::Chunks).unwrap_or_default();
+        for kind in [RegionKind::Entities, RegionKind::Poi] {
+            let copied = copied.remove(&kind).unwrap_or_default();
+            for (x, z) in terrain.difference(&copied) {
+                if self.region_path(x >> 5, z >> 5, to, kind).is_file()
+                    && transaction
+                        .get_region(x >> 5, z >> 5, to, kind)?
+                        .remove_chunk(x & 31, z & 31)
+                {
+                    report.cleared_chunks += 1;
+                }
+            }
+        }
+        let session = transaction.session().to_string();
+        report.session = (transaction.commit()? > 0).then_some(session);
+        Ok(report)
+    }
+}
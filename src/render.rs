@@ -0,0 +1,701 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    area::BoundingBox,
+    block::{self, BlockState, PalettedContainer},
+    generate,
+    image::Image,
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, NbtValue, Result,
+};
+
+/// colors of the top of common blocks, the rest is found by [`block_color`]
+const COLORS: &[(&str, [u8; 3])] = &[
+    ("grass_block", [95, 159, 53]),
+    ("dirt", [134, 96, 67]),
+    ("coarse_dirt", [119, 85, 59]),
+    ("podzol", [91, 63, 24]),
+    ("rooted_dirt", [144, 103, 76]),
+    ("mud", [60, 57, 60]),
+    ("farmland", [110, 75, 45]),
+    ("dirt_path", [148, 122, 65]),
+    ("mycelium", [111, 99, 105]),
+    ("sand", [219, 207, 163]),
+    ("red_sand", [190, 102, 33]),
+    ("sandstone", [216, 203, 155]),
+    ("red_sandstone", [186, 99, 29]),
+    ("gravel", [131, 127, 126]),
+    ("clay", [160, 166, 179]),
+    ("stone", [125, 125, 125]),
+    ("cobblestone", [122, 122, 122]),
+    ("mossy_cobblestone", [110, 118, 94]),
+    ("smooth_stone", [158, 158, 158]),
+    ("granite", [149, 103, 85]),
+    ("diorite", [188, 188, 188]),
+    ("andesite", [136, 136, 136]),
+    ("deepslate", [80, 80, 82]),
+    ("tuff", [108, 109, 102]),
+    ("calcite", [223, 224, 220]),
+    ("dripstone_block", [134, 107, 92]),
+    ("pointed_dripstone", [134, 107, 92]),
+    ("bedrock", [85, 85, 85]),
+    ("water", [63, 118, 228]),
+    ("bubble_column", [63, 118, 228]),
+    ("lava", [207, 92, 15]),
+    ("ice", [145, 183, 253]),
+    ("packed_ice", [141, 180, 250]),
+    ("blue_ice", [116, 167, 253]),
+    ("snow", [249, 254, 254]),
+    ("snow_block", [249, 254, 254]),
+    ("powder_snow", [248, 253, 253]),
+    ("obsidian", [15, 10, 24]),
+    ("crying_obsidian", [32, 10, 60]),
+    ("netherrack", [97, 38, 38]),
+    ("nether_bricks", [44, 21, 26]),
+    ("soul_sand", [81, 62, 50]),
+    ("soul_soil", [75, 57, 46]),
+    ("basalt", [80, 81, 86]),
+    ("blackstone", [42, 36, 41]),
+    ("glowstone", [171, 131, 84]),
+    ("magma_block", [142, 63, 31]),
+    ("crimson_nylium", [130, 31, 31]),
+    ("warped_nylium", [43, 114, 101]),
+    ("nether_wart_block", [114, 2, 2]),
+    ("warped_wart_block", [22, 119, 121]),
+    ("shroomlight", [240, 146, 70]),
+    ("end_stone", [219, 222, 158]),
+    ("purpur_block", [169, 125, 169]),
+    ("moss_block", [89, 109, 45]),
+    ("moss_carpet", [89, 109, 45]),
+    ("short_grass", [100, 160, 60]),
+    ("grass", [100, 160, 60]),
+    ("tall_grass", [100, 160, 60]),
+    ("fern", [90, 145, 55]),
+    ("large_fern", [90, 145, 55]),
+    ("vine", [70, 120, 30]),
+    ("lily_pad", [32, 128, 48]),
+    ("sugar_cane", [148, 192, 101]),
+    ("cactus", [85, 127, 43]),
+    ("pumpkin", [198, 118, 24]),
+    ("melon", [111, 145, 30]),
+    ("hay_block", [166, 136, 38]),
+    ("bamboo", [93, 144, 19]),
+    ("kelp", [88, 142, 45]),
+    ("kelp_plant", [88, 142, 45]),
+    ("seagrass", [40, 100, 30]),
+    ("tall_seagrass", [40, 100, 30]),
+    ("azalea_leaves", [90, 115, 44]),
+    ("flowering_azalea_leaves", [112, 121, 64]),
+    ("cherry_leaves", [229, 172, 194]),
+    ("mangrove_roots", [74, 59, 38]),
+    ("muddy_mangrove_roots", [70, 58, 45]),
+    ("dandelion", [245, 220, 40]),
+    ("poppy", [200, 30, 30]),
+    ("torch", [255, 200, 80]),
+    ("bricks", [150, 97, 83]),
+    ("stone_bricks", [122, 121, 122]),
+    ("mossy_stone_bricks", [115, 121, 105]),
+    ("mud_bricks", [137, 103, 79]),
+    ("packed_mud", [142, 106, 79]),
+    ("quartz_block", [235, 229, 222]),
+    ("prismarine", [99, 156, 151]),
+    ("dark_prismarine", [51, 91, 75]),
+    ("sea_lantern", [172, 199, 190]),
+    ("iron_block", [220, 220, 220]),
+    ("gold_block", [246, 208, 61]),
+    ("diamond_block", [98, 237, 228]),
+    ("emerald_block", [42, 203, 87]),
+    ("lapis_block", [30, 67, 140]),
+    ("redstone_block", [175, 24, 5]),
+    ("coal_block", [16, 15, 15]),
+    ("copper_block", [192, 107, 79]),
+    ("terracotta", [152, 94, 67]),
+    ("amethyst_block", [133, 97, 191]),
+    ("bookshelf", [117, 94, 59]),
+    ("crafting_table", [119, 86, 50]),
+    ("chest", [160, 110, 40]),
+    ("furnace", [110, 110, 110]),
+    ("glass", [175, 213, 219]),
+    ("rail", [125, 110, 90]),
+    ("sculk", [12, 29, 36]),
+];
+
+const DYES: &[(&str, [u8; 3])] = &[
+    ("white", [233, 236, 236]),
+    ("orange", [240, 118, 19]),
+    ("magenta", [189, 68, 179]),
+    ("light_blue", [58, 175, 217]),
+    ("yellow", [248, 197, 39]),
+    ("lime", [112, 185, 25]),
+    ("pink", [237, 141, 172]),
+    ("gray", [62, 68, 71]),
+    ("light_gray", [142, 142, 134]),
+    ("cyan", [21, 137, 145]),
+    ("purple", [121, 42, 172]),
+    ("blue", [53, 57, 157]),
+    ("brown", [114, 71, 40]),
+    ("green", [84, 109, 27]),
+    ("red", [160, 39, 34]),
+    ("black", [20, 21, 25]),
+];
+
+/// plank colors, logs and stems are drawn darker
+const WOODS: &[(&str, [u8; 3])] = &[
+    ("oak", [162, 130, 78]),
+    ("spruce", [114, 84, 48]),
+    ("birch", [196, 179, 123]),
+    ("jungle", [160, 115, 80]),
+    ("acacia", [168, 90, 50]),
+    ("dark_oak", [66, 43, 20]),
+    ("pale_oak", [227, 217, 214]),
+    ("mangrove", [117, 54, 48]),
+    ("cherry", [226, 178, 172]),
+    ("bamboo", [193, 173, 80]),
+    ("crimson", [101, 48, 70]),
+    ("warped", [43, 104, 99]),
+];
+
+/// parts of a name that only change the shape of a block
+const SHAPES: &[&str] = &[
+    "_slab",
+    "_stairs",
+    "_wall",
+    "_fence_gate",
+    "_fence",
+    "_trapdoor",
+    "_door",
+    "_pressure_plate",
+    "_button",
+    "_wall_hanging_sign",
+    "_hanging_sign",
+    "_wall_sign",
+    "_sign",
+];
+
+/// colors of the vanilla biomes, close to the ones common map viewers use
+const BIOMES: &[(&str, [u8; 3])] = &[
+    ("ocean", [0, 0, 112]),
+    ("deep_ocean", [0, 0, 48]),
+    ("warm_ocean", [0, 0, 172]),
+    ("lukewarm_ocean", [0, 0, 144]),
+    ("deep_lukewarm_ocean", [0, 0, 64]),
+    ("cold_ocean", [32, 32, 112]),
+    ("deep_cold_ocean", [32, 32, 56]),
+    ("frozen_ocean", [112, 112, 214]),
+    ("deep_frozen_ocean", [64, 64, 144]),
+    ("river", [0, 0, 255]),
+    ("frozen_river", [160, 160, 255]),
+    ("beach", [250, 222, 85]),
+    ("snowy_beach", [250, 240, 192]),
+    ("stony_shore", [162, 162, 132]),
+    ("plains", [141, 179, 96]),
+    ("sunflower_plains", [181, 219, 136]),
+    ("snowy_plains", [255, 255, 255]),
+    ("ice_spikes", [180, 220, 220]),
+    ("desert", [250, 148, 24]),
+    ("swamp", [7, 249, 178]),
+    ("mangrove_swamp", [44, 204, 142]),
+    ("forest", [5, 102, 33]),
+    ("flower_forest", [45, 142, 73]),
+    ("birch_forest", [48, 116, 68]),
+    ("old_growth_birch_forest", [88, 156, 108]),
+    ("dark_forest", [64, 81, 26]),
+    ("pale_garden", [105, 112, 100]),
+    ("taiga", [11, 102, 89]),
+    ("old_growth_pine_taiga", [89, 102, 81]),
+    ("old_growth_spruce_taiga", [129, 142, 121]),
+    ("snowy_taiga", [49, 85, 74]),
+    ("savanna", [189, 178, 95]),
+    ("savanna_plateau", [167, 157, 100]),
+    ("windswept_savanna", [229, 218, 135]),
+    ("windswept_hills", [96, 96, 96]),
+    ("windswept_gravelly_hills", [136, 136, 136]),
+    ("windswept_forest", [80, 112, 80]),
+    ("jungle", [83, 123, 9]),
+    ("sparse_jungle", [98, 139, 23]),
+    ("bamboo_jungle", [118, 142, 20]),
+    ("badlands", [217, 69, 21]),
+    ("eroded_badlands", [255, 109, 61]),
+    ("wooded_badlands", [176, 151, 101]),
+    ("meadow", [96, 164, 69]),
+    ("cherry_grove", [232, 160, 192]),
+    ("grove", [71, 114, 106]),
+    ("snowy_slopes", [196, 196, 196]),
+    ("frozen_peaks", [160, 160, 224]),
+    ("jagged_peaks", [220, 220, 220]),
+    ("stony_peaks", [123, 143, 116]),
+    ("dripstone_caves", [134, 96, 67]),
+    ("lush_caves", [74, 122, 31]),
+    ("deep_dark", [31, 46, 51]),
+    ("mushroom_fields", [255, 0, 255]),
+    ("nether_wastes", [191, 59, 59]),
+    ("soul_sand_valley", [94, 56, 48]),
+    ("crimson_forest", [221, 8, 8]),
+    ("warped_forest", [73, 144, 123]),
+    ("basalt_deltas", [64, 54, 54]),
+    ("the_end", [128, 128, 255]),
+    ("small_end_islands", [75, 75, 171]),
+    ("end_midlands", [201, 201, 138]),
+    ("end_highlands", [181, 181, 54]),
+    ("end_barrens", [112, 112, 204]),
+    ("the_void", [0, 0, 0]),
+];
+
+fn shade([r, g, b]: [u8; 3], factor: f64) -> [u8; 3] {
+    [r, g, b].map(|e| (e as f64 * factor).round().clamp(0.0, 255.0) as u8)
+}
+
+/// color of a block seen from above
+///
+/// a built in table covers common vanilla blocks, dyed blocks, wood types, stairs, slabs and
+/// the like take the color of what they're made of, anything unknown gets a muted color
+/// derived from its name so it stays recognizable
+pub fn block_color(name: &str) -> [u8; 3] {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    let find =
+        |table: &[(&str, [u8; 3])], name: &str| table.iter().find(|e| e.0 == name).map(|e| e.1);
+    if let Some(color) = find(COLORS, name) {
+        return color;
+    }
+    if let Some(base) = SHAPES.iter().find_map(|e| name.strip_suffix(e)) {
+        for base in [
+            base.to_string(),
+            format!("{base}s"),
+            format!("{base}_block"),
+        ] {
+            if let Some(color) = find(COLORS, &base) {
+                return color;
+            }
+        }
+    }
+    let dye = DYES.iter().find(|e| name.starts_with(&format!("{}_", e.0)));
+    if let Some((_, color)) = dye {
+        return match name.ends_with("terracotta") {
+            true => shade(*color, 0.7),
+            false => *color,
+        };
+    }
+    let wood = WOODS.iter().find(|e| {
+        name.starts_with(&format!("{}_", e.0)) || name.starts_with(&format!("stripped_{}_", e.0))
+    });
+    if let Some((_, color)) = wood {
+        if name.ends_with("_leaves") {
+            return [72, 128, 40];
+        }
+        return match ["_log", "_wood", "_stem", "_hyphae"]
+            .iter()
+            .any(|e| name.ends_with(e))
+        {
+            true if !name.starts_with("stripped_") => shade(*color, 0.6),
+            _ => *color,
+        };
+    }
+    let rules: &[(&str, [u8; 3])] = &[
+        ("_leaves", [72, 128, 40]),
+        ("deepslate", [80, 80, 82]),
+        ("_ore", [125, 125, 125]),
+        ("copper", [192, 107, 79]),
+        ("quartz", [235, 229, 222]),
+        ("sandstone", [216, 203, 155]),
+        ("blackstone", [42, 36, 41]),
+        ("brick", [150, 97, 83]),
+        ("stone", [125, 125, 125]),
+        ("glass", [175, 213, 219]),
+        ("coral", [200, 90, 120]),
+        ("mushroom", [150, 110, 90]),
+        ("rail", [125, 110, 90]),
+    ];
+    if let Some((_, color)) = rules.iter().find(|e| name.contains(e.0)) {
+        return *color;
+    }
+    name_color(name)
+}
+
+/// a muted color derived from a name, the same name always gets the same color
+fn name_color(name: &str) -> [u8; 3] {
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    [0, 8, 16].map(|e| 80 + (hash >> e) as u8 % 96)
+}
+
+/// color of a biome on a biome map, biomes that aren't vanilla get a muted color derived from
+/// their name
+pub fn biome_color(name: &str) -> [u8; 3] {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    match BIOMES.iter().find(|e| e.0 == name) {
+        Some((_, color)) => *color,
+        None => name_color(name),
+    }
+}
+
+/// how top-down maps are drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// lightens slopes facing north and darkens the others, like maps in the game
+    pub shading: bool,
+    pub threads: usize,
+    /// draws the biomes at this y instead of the blocks
+    pub biomes: Option<i32>,
+    /// colors of biomes by namespaced id, for datapack biomes or to change the vanilla ones
+    pub biome_colors: HashMap<String, [u8; 3]>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            shading: true,
+            threads: thread::available_parallelism().map_or(1, |e| e.get()),
+            biomes: None,
+            biome_colors: HashMap::new(),
+        }
+    }
+}
+
+/// the blocks of every 1.18+ section of a chunk, with the section's y
+fn chunk_sections(root: &NbtValue) -> Result<Vec<(i32, PalettedContainer<BlockState>)>> {
+    let sections = root
+        .get("sections")
+        .and_then(NbtValue::as_list)
+        .map(|e| e.compounds())
+        .unwrap_or_default();
+    let mut blocks = vec![];
+    for section in sections {
+        let Some(y) = section.get("Y").and_then(NbtValue::as_i64) else {
+            continue;
+        };
+        if let Some(states) = block::section_blocks(section)? {
+            blocks.push((y as i32, states));
+        }
+    }
+    Ok(blocks)
+}
+
+/// `None` for a section the chunk doesn't have
+fn block_in(
+    sections: &[(i32, PalettedContainer<BlockState>)],
+    x: i32,
+    y: i32,
+    z: i32,
+) -> Option<&BlockState> {
+    sections
+        .iter()
+        .find(|e| e.0 == y >> 4)
+        .map(|e| e.1.get(block::section_index(x, y, z)))
+}
+
+/// the biome of every column of a chunk at `y`, x first, from the section's 4 by 4 by 4 biome
+/// cells; `None` when the chunk has no section at `y`
+fn chunk_biomes(nbt: &NbtValue, y: i32) -> Result<Option<Vec<String>>> {
+    let sections = Chunk::root(nbt)
+        .get("sections")
+        .and_then(NbtValue::as_list)
+        .map(|e| e.compounds())
+        .unwrap_or_default();
+    let section = sections
+        .iter()
+        .find(|e| e.get("Y").and_then(NbtValue::as_i64) == Some((y >> 4) as i64));
+    let Some(biomes) = section.map(block::section_biomes).transpose()?.flatten() else {
+        return Ok(None);
+    };
+    let cell_y = (y & 15) as usize / 4;
+    Ok(Some(
+        (0..256)
+            .map(|i| {
+                let (x, z) = (i % 16 / 4, i / 16 / 4);
+                biomes.get(cell_y * 16 + z * 4 + x).clone()
+            })
+            .collect(),
+    ))
+}
+
+/// the top of a column as seen from above
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub color: [u8; 3],
+    pub y: i32,
+}
+
+/// the top of every column of a chunk, x first, `None` for columns with nothing in them
+pub fn chunk_surface(nbt: &NbtValue, dim: Dimension) -> Result<Vec<Option<Column>>> {
+    let root = Chunk::root(nbt);
+    let min_y = root
+        .get("yPos")
+        .and_then(NbtValue::as_i64)
+        .map_or(dim.min_y(), |e| e as i32 * 16);
+    let blocks = chunk_sections(root)?;
+    let air = BlockState::new("air");
+    let block_at = |x: i32, y: i32, z: i32| block_in(&blocks, x, y, z).unwrap_or(&air);
+    let top_y = blocks.iter().map(|e| e.0 * 16 + 15).max().unwrap_or(min_y);
+
+    // the heightmap saves looking for the top, every entry is the height above min_y
+    let heights = match root.get("Heightmaps").and_then(|e| e.get("WORLD_SURFACE")) {
+        Some(NbtValue::LongArray(data)) if !data.is_empty() => {
+            let per_long = 256usize.div_ceil(data.len());
+            let bits = 64 / per_long;
+            let mask = (1u64 << bits) - 1;
+            Some(
+                (0..256)
+                    .map(|i| {
+                        let long = data[i / per_long] as u64;
+                        min_y + ((long >> (i % per_long * bits)) & mask) as i32 - 1
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+        _ => None,
+    };
+
+    let mut surface = vec![None; 256];
+    for (i, column) in surface.iter_mut().enumerate() {
+        let (x, z) = ((i % 16) as i32, (i / 16) as i32);
+        let top = match &heights {
+            Some(heights) => Some(heights[i]).filter(|e| *e >= min_y),
+            None => (min_y..=top_y).rev().find(|y| !block_at(x, *y, z).is_air()),
+        };
+        let Some(mut y) = top else {
+            continue;
+        };
+        let state = block_at(x, y, z);
+        if !generate::has_fluid(state) || state.name == "minecraft:lava" {
+            *column = Some(Column {
+                color: block_color(&state.name),
+                y,
+            });
+            continue;
+        }
+        // water shows the floor, fading out with depth
+        let surface_y = y;
+        while y > min_y && generate::has_fluid(block_at(x, y, z)) && surface_y - y < 16 {
+            y -= 1;
+        }
+        let depth = (surface_y - y) as f64 / 16.0;
+        let (water, floor) = (block_color("water"), block_color(&block_at(x, y, z).name));
+        let color = [0, 1, 2].map(|c| {
+            (water[c] as f64 * (0.5 + depth / 2.0) + floor[c] as f64 * (0.5 - depth / 2.0)) as u8
+        });
+        *column = Some(Column {
+            color: shade(color, 1.0 - depth * 0.3),
+            y: surface_y,
+        });
+    }
+    Ok(surface)
+}
+
+/// a plane through the world, at a fixed x, y or z
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slice {
+    /// the z, y plane, looking east with z growing to the right
+    X(i32),
+    /// the x, z plane, north up like a map
+    Y(i32),
+    /// the x, y plane, looking north with x growing to the right
+    Z(i32),
+}
+
+impl Slice {
+    /// the block at `a`, `b` along the two axes in the plane, horizontal first
+    fn block(&self, a: i32, b: i32) -> [i32; 3] {
+        match *self {
+            Slice::X(x) => [x, b, a],
+            Slice::Y(y) => [a, y, b],
+            Slice::Z(z) => [a, b, z],
+        }
+    }
+
+    fn plane(&self, [x, y, z]: [i32; 3]) -> (i32, i32) {
+        match self {
+            Slice::X(_) => (z, y),
+            Slice::Y(_) => (x, z),
+            Slice::Z(_) => (x, y),
+        }
+    }
+}
+
+impl World {
+    /// one pixel per block of `slice` between corners `min` and `max` of the plane, given
+    /// along its horizontal axis and then y (or z for [`Slice::Y`]); air, missing chunks and
+    /// chunks that can't be read are transparent
+    pub fn render_slice(
+        &self,
+        dim: Dimension,
+        slice: Slice,
+        min: (i32, i32),
+        max: (i32, i32),
+    ) -> Result<Image> {
+        let bounds = BoundingBox::new(slice.block(min.0, min.1), slice.block(max.0, max.1));
+        let (low, high) = (slice.plane(bounds.min), slice.plane(bounds.max));
+        let mut image = Image::new((high.0 - low.0 + 1) as u32, (high.1 - low.1 + 1) as u32);
+        for (cx, cz) in bounds.chunks() {
+            let Some(region) =
+                self.get_existing_region(cx >> 5, cz >> 5, dim, RegionKind::Chunks)?
+            else {
+                continue;
+            };
+            let Ok(Some(chunk)) = region.get_chunk(cx & 31, cz & 31) else {
+                continue;
+            };
+            let Ok(sections) = chunk.read(|nbt| chunk_sections(Chunk::root(nbt))) else {
+                continue;
+            };
+            let column = BoundingBox::chunk_column(cx, cz, bounds.min[1], bounds.max[1]);
+            let clip = bounds.intersection(&column).unwrap();
+            for y in clip.min[1]..=clip.max[1] {
+                for z in clip.min[2]..=clip.max[2] {
+                    for x in clip.min[0]..=clip.max[0] {
+                        let Some(state) = block_in(&sections, x, y, z).filter(|e| !e.is_air())
+                        else {
+                            continue;
+                        };
+                        let (h, v) = slice.plane([x, y, z]);
+                        // up is up in vertical slices
+                        let row = match slice {
+                            Slice::Y(_) => v - low.1,
+                            _ => high.1 - v,
+                        };
+                        let [r, g, b] = block_color(&state.name);
+                        image.set((h - low.0) as u32, row as u32, [r, g, b, 255]);
+                    }
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// a 512 by 512 top-down image of a region, north up, `None` when there is no region
+    /// file; chunks that can't be read stay transparent, and so do chunks without a section at
+    /// the y of a biome map
+    pub fn render_region(
+        &self,
+        dim: Dimension,
+        x: i32,
+        z: i32,
+        options: &RenderOptions,
+    ) -> Result<Option<Image>> {
+        let Some(region) = self.get_existing_region(x, z, dim, RegionKind::Chunks)? else {
+            return Ok(None);
+        };
+        let mut columns = vec![None; 512 * 512];
+        for (cx, cz) in region.chunk_positions() {
+            let Ok(Some(chunk)) = region.get_chunk(cx, cz) else {
+                continue;
+            };
+            let surface = match options.biomes {
+                // every column has the same y, so shading never changes a biome map
+                Some(y) => chunk.read(|nbt| chunk_biomes(nbt, y)).map(|biomes| {
+                    biomes
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|name| {
+                            let color = options.biome_colors.get(name).copied();
+                            Some(Column {
+                                color: color.unwrap_or_else(|| biome_color(name)),
+                                y,
+                            })
+                        })
+                        .collect()
+                }),
+                None => chunk.read(|nbt| chunk_surface(nbt, dim)),
+            };
+            let Ok(surface) = surface else {
+                continue;
+            };
+            for (i, column) in surface.into_iter().enumerate() {
+                let (bx, bz) = (cx as usize * 16 + i % 16, cz as usize * 16 + i / 16);
+                columns[bz * 512 + bx] = column;
+            }
+        }
+        let mut image = Image::new(512, 512);
+        for (i, column) in columns.iter().enumerate() {
+            let Some(column) = column else {
+                continue;
+            };
+            let factor = match (options.shading, i.checked_sub(512).and_then(|e| columns[e])) {
+                (true, Some(north)) => 1.0 + ((column.y - north.y) as f64 * 0.1).clamp(-0.3, 0.3),
+                _ => 1.0,
+            };
+            let [r, g, b] = shade(column.color, factor);
+            image.set((i % 512) as u32, (i / 512) as u32, [r, g, b, 255]);
+        }
+        Ok(Some(image))
+    }
+
+    /// renders every region of `dim` on `options.threads` threads and hands each image to
+    /// `f` with the region's position, returns how many there were
+    pub fn render_tiles<F>(&self, dim: Dimension, options: &RenderOptions, f: F) -> Result<usize>
+    where
+        F: Fn((i32, i32), Image) -> Result<()> + Sync,
+    {
+        let regions = self.region_positions(dim, RegionKind::Chunks)?;
+        let next = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+        let error = Mutex::new(None::<Error>);
+        thread::scope(|s| {
+            for _ in 0..options.threads.clamp(1, regions.len().max(1)) {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= regions.len() || error.lock().unwrap().is_some() {
+                        break;
+                    }
+                    let (x, z) = regions[i];
+                    let rendered = self
+                        .render_region(dim, x, z, options)
+                        .and_then(|e| e.map(|image| f((x, z), image)).transpose());
+                    match rendered {
+                        Ok(Some(_)) => {
+                            done.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Ok(None) => {}
+                        Err(e) => *error.lock().unwrap() = Some(e),
+                    }
+                });
+            }
+        });
+        match error.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(done.into_inner()),
+        }
+    }
+
+    /// the whole dimension in one image with `blocks_per_pixel` blocks to a pixel, and the
+    /// block x, z of its top left corner; `None` when there are no regions
+    pub fn render_dimension(
+        &self,
+        dim: Dimension,
+        options: &RenderOptions,
+        blocks_per_pixel: u32,
+    ) -> Result<Option<(Image, (i32, i32))>> {
+        let regions = self.region_positions(dim, RegionKind::Chunks)?;
+        let (Some(min_x), Some(min_z)) = (
+            regions.iter().map(|e| e.0).min(),
+            regions.iter().map(|e| e.1).min(),
+        ) else {
+            return Ok(None);
+        };
+        let max_x = regions.iter().map(|e| e.0).max().unwrap();
+        let max_z = regions.iter().map(|e| e.1).max().unwrap();
+        let scale = blocks_per_pixel.max(1);
+        let tile = 512u32.div_ceil(scale);
+        let image = Mutex::new(Image::new(
+            (max_x - min_x + 1) as u32 * tile,
+            (max_z - min_z + 1) as u32 * tile,
+        ));
+        self.render_tiles(dim, options, |(x, z), region| {
+            let region = region.downscale(scale);
+            let (px, pz) = ((x - min_x) as u32 * tile, (z - min_z) as u32 * tile);
+            image.lock().unwrap().paste(&region, px, pz);
+            Ok(())
+        })?;
+        Ok(Some((
+            image.into_inner().unwrap(),
+            (min_x * 512, min_z * 512),
+        )))
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    area::{Area, BoundingBox},
+    block::{self, BlockPattern, BlockState},
+    world::{Dimension, RegionKind, World},
+    Map, NbtList, NbtValue, Result,
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ReplaceReport {
+    pub chunks: usize,
+    pub sections: usize,
+    pub blocks: usize,
+    /// chunks in the pre 1.18 format inside the area, they are left untouched
+    pub skipped: usize,
+    /// edit session to pass to [`World::undo`]
+    pub session: Option<String>,
+}
+
+/// replaces matching blocks in one chunk, returns the number of sections and blocks changed
+///
+/// sections entirely inside `area` only get their palette rewritten, the packed data is
+/// left alone unless two palette entries end up the same
+pub fn replace_in_chunk(
+    nbt: &mut NbtValue,
+    x: i32,
+    z: i32,
+    area: &Area,
+    pattern: &BlockPattern,
+    replacement: &BlockState,
+) -> Result<(usize, usize)> {
+    let Some(NbtValue::List(NbtList::CompoundList(sections))) = nbt.get_mut("sections") else {
+        return Ok((0, 0));
+    };
+    let (mut changed_sections, mut changed_blocks) = (0, 0);
+    let mut removed_block_entities = HashSet::new();
+    for section in sections.iter_mut() {
+        let Some(y) = section.get("Y").and_then(NbtValue::as_i64) else {
+            continue;
+        };
+        let bounds = BoundingBox::section(x, y as i32, z);
+        let Some(clip) = area.clip(&bounds) else {
+            continue;
+        };
+        let Some(mut blocks) = block::section_blocks(section)? else {
+            continue;
+        };
+        let matched = blocks
+            .palette
+            .iter()
+            .map(|e| pattern.matches(e) && e != replacement)
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for by in clip.min[1]..=clip.max[1] {
+            for bz in clip.min[2]..=clip.max[2] {
+                for bx in clip.min[0]..=clip.max[0] {
+                    let i = block::section_index(bx, by, bz);
+                    if matched[blocks.indices[i] as usize] {
+                        count += 1;
+                        if blocks.get(i).name != replacement.name {
+                            removed_block_entities.insert([bx, by, bz]);
+                        }
+                    }
+                }
+            }
+        }
+        if count > 0 {
+            let states = section.get_mut("block_states").unwrap();
+            if clip == bounds {
+                for (state, matched) in blocks.palette.iter_mut().zip(&matched) {
+                    if *matched {
+                        *state = replacement.clone();
+                    }
+                }
+                let duplicates = blocks
+                    .palette
+                    .iter()
+                    .enumerate()
+                    .any(|(i, e)| blocks.palette[..i].contains(e));
+                if duplicates {
+                    blocks.compact();
+                    *states = block::blocks_to_nbt(&blocks);
+                } else if let Some(states) = states.as_compound_mut() {
+                    states.insert(
+                        "palette".to_string(),
+                        block::palette_to_nbt(&blocks.palette),
+                    );
+                }
+            } else {
+                for by in clip.min[1]..=clip.max[1] {
+                    for bz in clip.min[2]..=clip.max[2] {
+                        for bx in clip.min[0]..=clip.max[0] {
+                            let i = block::section_index(bx, by, bz);
+                            if matched[blocks.indices[i] as usize] {
+                                blocks.set(i, replacement.clone());
+                            }
+                        }
+                    }
+                }
+                blocks.compact();
+                *states = block::blocks_to_nbt(&blocks);
+            }
+            changed_sections += 1;
+            changed_blocks += count;
+        }
+    }
+    // block entities of the old blocks would otherwise end up on the replacement
+    if !removed_block_entities.is_empty() {
+        if let Some(NbtValue::List(NbtList::CompoundList(block_entities))) =
+            nbt.get_mut("block_entities")
+        {
+            block_entities.retain(|e| !removed_block_entities.contains(&block_entity_pos(e)));
+        }
+    }
+    Ok((changed_sections, changed_blocks))
+}
+
+fn block_entity_pos(block_entity: &Map<String, NbtValue>) -> [i32; 3] {
+    ["x", "y", "z"].map(|k| {
+        block_entity
+            .get(k)
+            .and_then(NbtValue::as_i64)
+            .unwrap_or_default() as i32
+    })
+}
+
+impl World {
+    /// replaces every block matching `pattern` inside `area` with `replacement`, all regions
+    /// are written in one transaction; chunks in the pre 1.18 format are counted as skipped
+    pub fn replace_blocks(
+        &self,
+        dim: Dimension,
+        area: &Area,
+        pattern: &BlockPattern,
+        replacement: &BlockState,
+    ) -> Result<ReplaceReport> {
+        let transaction = self.transaction()?;
+        let mut report = ReplaceReport::default();
+        for (rx, rz) in self.region_positions(dim, RegionKind::Chunks)? {
+            if !area.contains_region(rx, rz) {
+                continue;
+            }
+            let region = transaction.get_region(rx, rz, dim, RegionKind::Chunks)?;
+            for (lx, lz) in region.chunk_positions() {
+                let (x, z) = (rx * 32 + lx, rz * 32 + lz);
+                if !area.contains_chunk(x, z) {
+                    continue;
+                }
+                let Some(chunk) = region.get_chunk(lx, lz)? else {
+                    continue;
+                };
+                // edited on a copy so untouched chunks aren't marked as changed
+                let mut nbt = chunk.read(NbtValue::clone);
+                if nbt.get("Level").is_some() {
+                    report.skipped += 1;
+                    continue;
+                }
+                let (sections, blocks) =
+                    replace_in_chunk(&mut nbt, x, z, area, pattern, replacement)?;
+                if sections > 0 {
+                    chunk.write(|e| *e = nbt);
+                    region.set_chunk(lx, lz, &chunk)?;
+                    report.chunks += 1;
+                    report.sections += sections;
+                    report.blocks += blocks;
+                }
+            }
+            transaction.finish_region(rx, rz, dim, RegionKind::Chunks)?;
+        }
+        let session = transaction.session().to_string();
+        report.session = (transaction.commit()? > 0).then_some(session);
+        Ok(report)
+    }
+}
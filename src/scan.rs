@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    world::{Dimension, RegionKind, World},
+    Error, NbtValue, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub regions_done: usize,
+    pub regions_total: usize,
+    pub chunks_done: usize,
+}
+
+#[derive(Debug)]
+pub struct ScanError {
+    pub region: (i32, i32),
+    /// absolute chunk coordinates, `None` when the whole region couldn't be read
+    pub chunk: Option<(i32, i32)>,
+    pub error: Error,
+}
+
+#[derive(Debug)]
+pub struct ScanResult<T> {
+    /// one result per decoded chunk, sorted by chunk position
+    pub results: Vec<((i32, i32), T)>,
+    pub errors: Vec<ScanError>,
+    /// true when the scan stopped before every chunk was read
+    pub cancelled: bool,
+}
+
+/// a scan over every chunk of a dimension, regions are spread over a pool of threads,
+/// see [`World::par_chunks`]
+pub struct Scan<'a> {
+    world: &'a World,
+    dim: Dimension,
+    kind: RegionKind,
+    threads: usize,
+    progress: Option<&'a (dyn Fn(ScanProgress) + Sync)>,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> Scan<'a> {
+    /// which region files to read, terrain chunks by default
+    pub fn kind(mut self, kind: RegionKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// called after every region
+    pub fn progress(mut self, progress: &'a (dyn Fn(ScanProgress) + Sync)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// stops the scan once set, chunks already handed to `f` are still returned
+    pub fn cancel(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// calls `f` with the absolute position and nbt of every chunk, a chunk that fails to
+    /// decode ends up in the errors without stopping the scan
+    pub fn run<T, F>(self, f: F) -> Result<ScanResult<T>>
+    where
+        T: Send,
+        F: Fn((i32, i32), &NbtValue) -> T + Sync,
+    {
+        let regions = self.world.region_positions(self.dim, self.kind)?;
+        let cancelled = || self.cancel.is_some_and(|e| e.load(Ordering::Relaxed));
+        let next = AtomicUsize::new(0);
+        let regions_done = AtomicUsize::new(0);
+        let chunks_done = AtomicUsize::new(0);
+        let stopped = AtomicBool::new(false);
+        let output = Mutex::new((vec![], vec![]));
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.min(regions.len()) {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= regions.len() {
+                        break;
+                    }
+                    if cancelled() {
+                        stopped.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    let (rx, rz) = regions[i];
+                    let (mut results, mut errors) = (vec![], vec![]);
+                    match self.world.get_existing_region(rx, rz, self.dim, self.kind) {
+                        Ok(Some(region)) => {
+                            for chunk in region.unreadable_chunks() {
+                                errors.push(ScanError {
+                                    region: (rx, rz),
+                                    chunk: Some((rx * 32 + chunk.x, rz * 32 + chunk.z)),
+                                    error: Error::Custom(chunk.reason),
+                                });
+                            }
+                            for (x, z) in region.chunk_positions() {
+                                if cancelled() {
+                                    stopped.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                                let pos = (rx * 32 + x, rz * 32 + z);
+                                match region.get_chunk(x, z) {
+                                    Ok(Some(chunk)) => {
+                                        results.push((pos, chunk.read(|nbt| f(pos, nbt))))
+                                    }
+                                    Ok(None) => {}
+                                    Err(error) => errors.push(ScanError {
+                                        region: (rx, rz),
+                                        chunk: Some(pos),
+                                        error,
+                                    }),
+                                }
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(error) => errors.push(ScanError {
+                            region: (rx, rz),
+                            chunk: None,
+                            error,
+                        }),
+                    }
+                    let count = results.len();
+                    let chunks = chunks_done.fetch_add(count, Ordering::Relaxed) + count;
+                    let done = regions_done.fetch_add(1, Ordering::Relaxed);
+                    {
+                        let mut output = output.lock().unwrap();
+                        output.0.append(&mut results);
+                        output.1.append(&mut errors);
+                    }
+                    if let Some(progress) = self.progress {
+                        progress(ScanProgress {
+                            regions_done: done + 1,
+                            regions_total: regions.len(),
+                            chunks_done: chunks,
+                        });
+                    }
+                });
+            }
+        });
+
+        let (mut results, mut errors) = output.into_inner().unwrap();
+        results.sort_by_key(|e: &((i32, i32), T)| e.0);
+        errors.sort_by_key(|e: &ScanError| (e.region, e.chunk));
+        Ok(ScanResult {
+            results,
+            errors,
+            cancelled: stopped.into_inner(),
+        })
+    }
+}
+
+impl World {
+    /// scans every terrain chunk of `dim` on all available cores
+    pub fn par_chunks(&self, dim: Dimension) -> Scan<'_> {
+        Scan {
+            world: self,
+            dim,
+            kind: RegionKind::Chunks,
+            threads: thread::available_parallelism().map_or(1, |e| e.get()),
+            progress: None,
+            cancel: None,
+        }
+    }
+}
@@ -0,0 +1,1119 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::Read,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+use crate::{
+    area::BoundingBox,
+    block::{self, BlockState, PalettedContainer},
+    builder::DATA_VERSION,
+    generate::ChunkBuilder,
+    legacy::{join_door, legacy_block, legacy_block_entity},
+    transaction::by_region,
+    world::{unix_time, Chunk, Dimension, RegionKind, World},
+    Error, Map, NbtFile, NbtList, NbtValue, Result,
+};
+
+/// blocks, biomes, block entities and entities of a box, with positions relative to its
+/// lowest corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schematic {
+    /// width (x), height (y) and length (z)
+    pub size: [i32; 3],
+    /// where the lowest corner was in the world
+    pub offset: [i32; 3],
+    pub data_version: i32,
+    /// one per block, x first, then z, then y
+    pub blocks: PalettedContainer<BlockState>,
+    /// one per block like `blocks`, `None` when the schematic has no biomes
+    pub biomes: Option<PalettedContainer<String>>,
+    /// position and nbt with `id`, without `x`, `y` and `z`
+    pub block_entities: Vec<([i32; 3], Map<String, NbtValue>)>,
+    /// nbt with `Pos` relative to the lowest corner
+    pub entities: Vec<Map<String, NbtValue>>,
+}
+
+/// size of a schematic, for reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SchematicSummary {
+    pub size: [i32; 3],
+    pub offset: [i32; 3],
+    pub block_entities: usize,
+    pub entities: usize,
+}
+
+/// turns a schematic clockwise as seen from above
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    /// quarter turns clockwise
+    fn steps(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::Counterclockwise90 => 3,
+        }
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = Error;
+
+    /// degrees clockwise, `0`, `90`, `180` or `270`
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "0" => Ok(Rotation::None),
+            "90" => Ok(Rotation::Clockwise90),
+            "180" => Ok(Rotation::Clockwise180),
+            "270" | "-90" => Ok(Rotation::Counterclockwise90),
+            _ => Err(Error::Custom(format!("invalid rotation {s}"))),
+        }
+    }
+}
+
+/// flips a schematic before it is turned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mirror {
+    #[default]
+    None,
+    /// swaps east and west
+    X,
+    /// swaps north and south
+    Z,
+}
+
+impl FromStr for Mirror {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Mirror::None),
+            "x" => Ok(Mirror::X),
+            "z" => Ok(Mirror::Z),
+            _ => Err(Error::Custom(format!(
+                "invalid mirror {s}, expected none, x or z"
+            ))),
+        }
+    }
+}
+
+const HORIZONTAL: [&str; 4] = ["north", "east", "south", "west"];
+
+/// mirroring and then turning a schematic of `size`
+struct Transform {
+    rotation: Rotation,
+    mirror: Mirror,
+    size: [i32; 3],
+}
+
+impl Transform {
+    fn is_identity(&self) -> bool {
+        self.rotation == Rotation::None && self.mirror == Mirror::None
+    }
+
+    /// size of the result
+    fn size(&self) -> [i32; 3] {
+        let [w, h, l] = self.size;
+        match self.rotation.steps() % 2 {
+            1 => [l, h, w],
+            _ => [w, h, l],
+        }
+    }
+
+    /// where a point ends up, `edge` is 1 for block positions and 0 for entity positions
+    fn place(&self, [x, y, z]: [f64; 3], edge: f64) -> [f64; 3] {
+        let (w, l) = (self.size[0] as f64 - edge, self.size[2] as f64 - edge);
+        let (x, z) = match self.mirror {
+            Mirror::None => (x, z),
+            Mirror::X => (w - x, z),
+            Mirror::Z => (x, l - z),
+        };
+        let (x, z) = match self.rotation.steps() {
+            0 => (x, z),
+            1 => (l - z, x),
+            2 => (w - x, l - z),
+            _ => (z, w - x),
+        };
+        [x, y, z]
+    }
+
+    fn block(&self, pos: [i32; 3]) -> [i32; 3] {
+        self.place(pos.map(|e| e as f64), 1.0).map(|e| e as i32)
+    }
+
+    fn direction(&self, direction: &str) -> Option<&'static str> {
+        let i = HORIZONTAL.iter().position(|e| *e == direction)?;
+        let i = match (self.mirror, i) {
+            (Mirror::X, 1 | 3) => 4 - i,
+            (Mirror::Z, 0 | 2) => 2 - i,
+            _ => i,
+        };
+        Some(HORIZONTAL[(i + self.rotation.steps()) % 4])
+    }
+
+    /// sign and banner rotations, 0 to 15 clockwise from south
+    fn rotation(&self, rotation: u32) -> u32 {
+        let rotation = match self.mirror {
+            Mirror::None => rotation,
+            Mirror::X => (16 - rotation) % 16,
+            Mirror::Z => (24 - rotation) % 16,
+        };
+        (rotation + 4 * self.rotation.steps() as u32) % 16
+    }
+
+    /// entity yaw in degrees clockwise from south
+    fn yaw(&self, yaw: f32) -> f32 {
+        let yaw = match self.mirror {
+            Mirror::None => yaw,
+            Mirror::X => -yaw,
+            Mirror::Z => 180.0 - yaw,
+        };
+        (yaw + 90.0 * self.rotation.steps() as f32).rem_euclid(360.0)
+    }
+
+    /// rail shapes like `north_east` or `ascending_west`
+    fn rail_shape(&self, shape: &str) -> String {
+        if let Some(direction) = shape.strip_prefix("ascending_") {
+            let direction = self.direction(direction).unwrap_or(direction);
+            return format!("ascending_{direction}");
+        }
+        let mut directions = shape
+            .split('_')
+            .map(|e| self.direction(e).unwrap_or(e))
+            .collect::<Vec<_>>();
+        // north and south come first, straight rails are `north_south` and `east_west`
+        directions.sort_by_key(|e| match *e {
+            "north" => 0,
+            "south" => 1,
+            "east" => 2,
+            _ => 3,
+        });
+        directions.join("_")
+    }
+
+    /// a block state with its directional properties mirrored and turned along
+    fn state(&self, state: &BlockState) -> BlockState {
+        let mut turned = BlockState::new(&state.name);
+        let flips = self.mirror != Mirror::None;
+        for (key, value) in state.properties.iter() {
+            let value = match key.as_str() {
+                "facing" => self.direction(value).map_or(value.clone(), str::to_string),
+                "axis" if self.rotation.steps() % 2 == 1 => match value.as_str() {
+                    "x" => "z".to_string(),
+                    "z" => "x".to_string(),
+                    _ => value.clone(),
+                },
+                "rotation" => match value.parse() {
+                    Ok(rotation) => self.rotation(rotation).to_string(),
+                    Err(_) => value.clone(),
+                },
+                "shape" if state.name.ends_with("rail") => self.rail_shape(value),
+                // stair corners, door hinges and double chest halves swap sides in a mirror
+                "shape" | "hinge" | "type" if flips => {
+                    if value.contains("left") {
+                        value.replace("left", "right")
+                    } else {
+                        value.replace("right", "left")
+                    }
+                }
+                _ => value.clone(),
+            };
+            // fences, walls, panes, vines and redstone connect by direction
+            let key = self.direction(key).unwrap_or(key);
+            turned.set_property(key, &value);
+        }
+        turned
+    }
+
+    /// moves and turns an entity and its passengers inside the schematic
+    fn entity(&self, entity: &mut Map<String, NbtValue>) {
+        if let Some(NbtValue::List(NbtList::DoubleList(pos))) = entity.get_mut("Pos") {
+            if let Ok(point) = <[f64; 3]>::try_from(pos.as_slice()) {
+                *pos = self.place(point, 0.0).to_vec();
+            }
+        }
+        if let Some(NbtValue::List(NbtList::FloatList(rotation))) = entity.get_mut("Rotation") {
+            if let Some(yaw) = rotation.first_mut() {
+                *yaw = self.yaw(*yaw);
+            }
+        }
+        let tile = ["TileX", "TileY", "TileZ"].map(|e| entity.get(e).and_then(NbtValue::as_i64));
+        if let [Some(x), Some(y), Some(z)] = tile {
+            let pos = self.block([x as i32, y as i32, z as i32]);
+            for (key, v) in ["TileX", "TileY", "TileZ"].into_iter().zip(pos) {
+                entity.insert(key.to_string(), NbtValue::Int(v));
+            }
+        }
+        if let Some(NbtValue::IntArray(pos)) = entity.get_mut("block_pos") {
+            if let Ok(block) = <[i32; 3]>::try_from(pos.as_slice()) {
+                *pos = self.block(block).to_vec();
+            }
+        }
+        // item frames face any direction, paintings one of the four around
+        const FACES: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+        if let Some(NbtValue::Byte(facing)) = entity.get_mut("Facing") {
+            if let Some(direction) = FACES.get(*facing as usize).and_then(|e| self.direction(e)) {
+                *facing = FACES.iter().position(|e| *e == direction).unwrap() as i8;
+            }
+        }
+        const SIDES: [&str; 4] = ["south", "west", "north", "east"];
+        if let Some(NbtValue::Byte(facing)) = entity.get_mut("facing") {
+            if let Some(direction) = SIDES.get(*facing as usize).and_then(|e| self.direction(e)) {
+                *facing = SIDES.iter().position(|e| *e == direction).unwrap() as i8;
+            }
+        }
+        if let Some(NbtValue::List(NbtList::CompoundList(passengers))) =
+            entity.get_mut("Passengers")
+        {
+            passengers.iter_mut().for_each(|e| self.entity(e));
+        }
+    }
+}
+
+/// drops the uuids of an entity and its passengers so the game gives copies new ones
+fn strip_uuids(entity: &mut Map<String, NbtValue>) {
+    entity.remove("UUID");
+    if let Some(NbtValue::List(NbtList::CompoundList(passengers))) = entity.get_mut("Passengers") {
+        passengers.iter_mut().for_each(strip_uuids);
+    }
+}
+
+fn read_varints(data: &[i8], count: usize) -> Result<Vec<u32>> {
+    let mut values = Vec::with_capacity(count);
+    let mut bytes = data.iter().map(|e| *e as u8);
+    while values.len() < count {
+        let mut value = 0u32;
+        for shift in (0..).step_by(7) {
+            let byte = bytes.next().ok_or(Error::Malformed(line!()))?;
+            if shift > 28 {
+                return Err(Error::Malformed(line!()));
+            }
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// a Sponge palette, `name: index`, and the varint indices into it
+fn sponge_container<T: PartialEq + Clone>(
+    palette: Option<&NbtValue>,
+    data: Option<&NbtValue>,
+    count: usize,
+    parse: impl Fn(&str) -> Result<T>,
+) -> Result<PalettedContainer<T>> {
+    let palette = palette
+        .and_then(NbtValue::as_compound)
+        .ok_or(Error::Malformed(line!()))?;
+    let mut names = BTreeMap::new();
+    for (name, index) in palette.iter() {
+        let index = index.as_i64().ok_or(Error::Malformed(line!()))?;
+        names.insert(index, parse(name)?);
+    }
+    let data = match data {
+        Some(NbtValue::ByteArray(v)) => v,
+        _ => return Err(Error::Malformed(line!())),
+    };
+    // palettes may skip indices, only the used ones are kept
+    let ids = names.keys().copied().collect::<Vec<_>>();
+    let indices = read_varints(data, count)?
+        .into_iter()
+        .map(|e| {
+            ids.binary_search(&(e as i64))
+                .map(|i| i as u16)
+                .map_err(|_| Error::Malformed(line!()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if names.len() > u16::MAX as usize {
+        return Err(Error::Malformed(line!()));
+    }
+    Ok(PalettedContainer {
+        palette: names.into_values().collect(),
+        indices,
+    })
+}
+
+fn int_array3(value: Option<&NbtValue>) -> Option<[i32; 3]> {
+    match value {
+        Some(NbtValue::IntArray(v)) => v.as_slice().try_into().ok(),
+        Some(NbtValue::List(list)) => {
+            let v = list.to_f64_vec();
+            (v.len() == 3).then(|| [v[0] as i32, v[1] as i32, v[2] as i32])
+        }
+        _ => None,
+    }
+}
+
+/// how a schematic is pasted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PasteOptions {
+    pub rotation: Rotation,
+    pub mirror: Mirror,
+    /// leaves the world's blocks where the schematic has air
+    pub skip_air: bool,
+    pub entities: bool,
+    pub biomes: bool,
+    /// writes chunks that don't exist yet as finished all-air chunks instead of skipping them
+    pub create_chunks: bool,
+}
+
+impl Default for PasteOptions {
+    fn default() -> Self {
+        Self {
+            rotation: Rotation::None,
+            mirror: Mirror::None,
+            skip_air: false,
+            entities: true,
+            biomes: true,
+            create_chunks: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct PasteReport {
+    pub chunks: usize,
+    pub blocks: usize,
+    pub block_entities: usize,
+    pub entities: usize,
+    /// missing chunks written as all air, see [`PasteOptions::create_chunks`]
+    pub created_chunks: usize,
+    /// missing chunks left out together with their blocks and entities
+    pub skipped_chunks: usize,
+    /// edit session to pass to [`World::undo`]
+    pub session: Option<String>,
+}
+
+fn write_varint(data: &mut Vec<i8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte as i8);
+            return;
+        }
+        data.push((byte | 0x80) as i8);
+    }
+}
+
+/// moves an entity and its passengers by `by` blocks
+pub(crate) fn shift_entity(entity: &mut Map<String, NbtValue>, by: [f64; 3]) {
+    if let Some(NbtValue::List(NbtList::DoubleList(pos))) = entity.get_mut("Pos") {
+        if pos.len() == 3 {
+            (0..3).for_each(|i| pos[i] += by[i]);
+        }
+    }
+    // paintings and item frames hang on a block
+    for (i, key) in ["TileX", "TileY", "TileZ"].into_iter().enumerate() {
+        if let Some(NbtValue::Int(v)) = entity.get_mut(key) {
+            *v += by[i] as i32;
+        }
+    }
+    if let Some(NbtValue::IntArray(pos)) = entity.get_mut("block_pos") {
+        if pos.len() == 3 {
+            (0..3).for_each(|i| pos[i] += by[i] as i32);
+        }
+    }
+    if let Some(NbtValue::List(NbtList::CompoundList(passengers))) = entity.get_mut("Passengers") {
+        passengers.iter_mut().for_each(|e| shift_entity(e, by));
+    }
+}
+
+fn entity_pos(entity: &Map<String, NbtValue>) -> Option<[f64; 3]> {
+    match entity.get("Pos") {
+        Some(NbtValue::List(list)) => list.to_f64_vec().try_into().ok(),
+        _ => None,
+    }
+}
+
+impl Schematic {
+    /// index into `blocks` and `biomes`
+    pub fn index(&self, [x, y, z]: [i32; 3]) -> usize {
+        ((y * self.size[2] + z) * self.size[0] + x) as usize
+    }
+
+    pub fn block(&self, pos: [i32; 3]) -> &BlockState {
+        self.blocks.get(self.index(pos))
+    }
+
+    /// reads a Sponge schematic of version 1 to 3 or a legacy MCEdit schematic, gzipped or
+    /// not
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut bytes = vec![];
+        File::open(path)
+            .and_then(|mut e| e.read_to_end(&mut bytes))
+            .map_err(Error::FileError)?;
+        let file = match bytes.starts_with(&[0x1f, 0x8b]) {
+            true => NbtFile::from_compressed_reader(bytes.as_slice())?,
+            false => NbtFile::from_reader(bytes.as_slice())?,
+        };
+        Self::from_nbt(&file.nbt)
+    }
+
+    /// the root of a Sponge or legacy schematic
+    pub fn from_nbt(nbt: &NbtValue) -> Result<Self> {
+        let root = match nbt.get("Schematic") {
+            Some(schematic @ NbtValue::Compound(_)) => schematic,
+            _ => nbt,
+        };
+        match root.get("Blocks") {
+            Some(NbtValue::ByteArray(_)) => Self::from_legacy(root),
+            _ => Self::from_sponge(root),
+        }
+    }
+
+    fn read_size(root: &NbtValue) -> Result<[i32; 3]> {
+        let size = ["Width", "Height", "Length"].map(|key| {
+            // unsigned shorts
+            root.get(key)
+                .and_then(NbtValue::as_i64)
+                .map(|e| e as i32 & 0xffff)
+        });
+        match size {
+            [Some(w), Some(h), Some(l)] => Ok([w, h, l]),
+            _ => Err(Error::Malformed(line!())),
+        }
+    }
+
+    /// versions 1 to 3 of the Sponge format, version 2 biomes are per column and fill the
+    /// whole height
+    pub fn from_sponge(root: &NbtValue) -> Result<Self> {
+        let version = root.get("Version").and_then(NbtValue::as_i64).unwrap_or(1);
+        let size = Self::read_size(root)?;
+        let volume = size.iter().map(|e| *e as usize).product();
+        let block = |s: &str| s.parse::<BlockState>();
+        let biome = |s: &str| Ok(block::namespaced(s));
+        let (blocks, block_entities, biomes) = match version {
+            3 => {
+                let blocks = root.get("Blocks").ok_or(Error::Malformed(line!()))?;
+                let biomes = root
+                    .get("Biomes")
+                    .map(|e| sponge_container(e.get("Palette"), e.get("Data"), volume, biome))
+                    .transpose()?;
+                let container =
+                    sponge_container(blocks.get("Palette"), blocks.get("Data"), volume, block)?;
+                (container, blocks.get("BlockEntities"), biomes)
+            }
+            _ => {
+                let container =
+                    sponge_container(root.get("Palette"), root.get("BlockData"), volume, block)?;
+                let columns = (size[0] * size[2]) as usize;
+                let biomes = match root.get("BiomePalette") {
+                    Some(palette) => {
+                        let columns =
+                            sponge_container(Some(palette), root.get("BiomeData"), columns, biome)?;
+                        let indices =
+                            (0..volume).map(|i| columns.indices[i % columns.indices.len()]);
+                        Some(PalettedContainer {
+                            palette: columns.palette,
+                            indices: indices.collect(),
+                        })
+                    }
+                    None => None,
+                };
+                let block_entities = root.get("BlockEntities").or(root.get("TileEntities"));
+                (container, block_entities, biomes)
+            }
+        };
+
+        // version 3 keeps the data in `Data`, older versions next to the id and position
+        let entry_data = |entry: &Map<String, NbtValue>| {
+            let mut data = match entry.get("Data") {
+                Some(NbtValue::Compound(data)) if version >= 3 => data.clone(),
+                _ => entry.clone(),
+            };
+            for key in ["Pos", "Id", "ContentVersion"] {
+                data.remove(key);
+            }
+            if let Some(id) = entry.get("Id") {
+                data.insert("id".to_string(), id.clone());
+            }
+            data
+        };
+        let compounds = |value: Option<&NbtValue>| {
+            value
+                .and_then(NbtValue::as_list)
+                .map(|e| e.compounds().to_vec())
+                .unwrap_or_default()
+        };
+        let block_entities = compounds(block_entities)
+            .iter()
+            .filter_map(|e| Some((int_array3(e.get("Pos"))?, entry_data(e))))
+            .collect();
+        let entities = compounds(root.get("Entities"))
+            .iter()
+            .filter_map(|e| {
+                let mut data = entry_data(e);
+                let pos = e.get("Pos").filter(|e| matches!(e, NbtValue::List(_)))?;
+                data.insert("Pos".to_string(), pos.clone());
+                Some(data)
+            })
+            .collect();
+        Ok(Self {
+            size,
+            offset: int_array3(root.get("Offset")).unwrap_or_default(),
+            data_version: root
+                .get("DataVersion")
+                .and_then(NbtValue::as_i64)
+                .map_or(DATA_VERSION, |e| e as i32),
+            blocks,
+            biomes,
+            block_entities,
+            entities,
+        })
+    }
+
+    /// an MCEdit schematic with numeric block ids, see [`legacy_block`]; block entities are kept
+    /// with their ids renamed by [`legacy_block_entity`], entities are left out
+    pub fn from_legacy(root: &NbtValue) -> Result<Self> {
+        let size = Self::read_size(root)?;
+        let volume = size.iter().map(|e| *e as usize).product();
+        let bytes = |key| match root.get(key) {
+            Some(NbtValue::ByteArray(v)) => Some(v.as_slice()),
+            _ => None,
+        };
+        let ids = bytes("Blocks").ok_or(Error::Malformed(line!()))?;
+        let data = bytes("Data").ok_or(Error::Malformed(line!()))?;
+        if ids.len() < volume || data.len() < volume {
+            return Err(Error::Malformed(line!()));
+        }
+        let add = bytes("AddBlocks").unwrap_or_default();
+        let mut blocks = PalettedContainer::filled(BlockState::new("air"), volume);
+        for i in 0..volume {
+            // ids above 255 keep their high bits in nibbles, the low one first
+            let high = add.get(i >> 1).map_or(0, |e| match i & 1 {
+                0 => *e as u8 & 0xf,
+                _ => *e as u8 >> 4,
+            });
+            let id = ((high as u16) << 8) | ids[i] as u8 as u16;
+            blocks.set(i, legacy_block(id, data[i] as u8 & 0xf));
+        }
+        let layer = (size[0] * size[2]) as usize;
+        for i in 0..volume.saturating_sub(layer) {
+            if let Some((lower, upper)) = join_door(blocks.get(i), blocks.get(i + layer)) {
+                blocks.set(i, lower);
+                blocks.set(i + layer, upper);
+            }
+        }
+        blocks.compact();
+        let block_entities = root
+            .get("TileEntities")
+            .and_then(NbtValue::as_list)
+            .map(|e| e.compounds().to_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|mut e| {
+                let int = |key| e.get(key).and_then(NbtValue::as_i64).map(|e| e as i32);
+                let pos = [int("x")?, int("y")?, int("z")?];
+                for key in ["x", "y", "z"] {
+                    e.remove(key);
+                }
+                if let Some(id) = e.get("id").and_then(NbtValue::as_str) {
+                    let id = legacy_block_entity(id);
+                    e.insert("id".to_string(), NbtValue::String(id));
+                }
+                Some((pos, e))
+            })
+            .collect();
+        let origin = ["WEOriginX", "WEOriginY", "WEOriginZ"]
+            .map(|e| root.get(e).and_then(NbtValue::as_i64).unwrap_or(0) as i32);
+        Ok(Self {
+            size,
+            offset: origin,
+            data_version: DATA_VERSION,
+            blocks,
+            biomes: None,
+            block_entities,
+            entities: vec![],
+        })
+    }
+
+    /// the schematic mirrored and then turned around its lowest corner, directional block
+    /// states, block entity positions and entities are turned along
+    pub fn transformed(&self, rotation: Rotation, mirror: Mirror) -> Schematic {
+        let transform = Transform {
+            rotation,
+            mirror,
+            size: self.size,
+        };
+        if transform.is_identity() {
+            return self.clone();
+        }
+        let mut schematic = Schematic {
+            size: transform.size(),
+            offset: self.offset,
+            data_version: self.data_version,
+            blocks: PalettedContainer {
+                palette: self
+                    .blocks
+                    .palette
+                    .iter()
+                    .map(|e| transform.state(e))
+                    .collect(),
+                indices: self.blocks.indices.clone(),
+            },
+            biomes: self.biomes.clone(),
+            block_entities: vec![],
+            entities: vec![],
+        };
+        let [w, h, l] = self.size;
+        for y in 0..h {
+            for z in 0..l {
+                for x in 0..w {
+                    let (from, to) = (
+                        self.index([x, y, z]),
+                        schematic.index(transform.block([x, y, z])),
+                    );
+                    schematic.blocks.indices[to] = self.blocks.indices[from];
+                    if let (Some(biomes), Some(old)) = (schematic.biomes.as_mut(), &self.biomes) {
+                        biomes.indices[to] = old.indices[from];
+                    }
+                }
+            }
+        }
+        schematic.block_entities = self
+            .block_entities
+            .iter()
+            .map(|(pos, nbt)| (transform.block(*pos), nbt.clone()))
+            .collect();
+        schematic.entities = self.entities.clone();
+        schematic
+            .entities
+            .iter_mut()
+            .for_each(|e| transform.entity(e));
+        schematic
+    }
+
+    pub fn summary(&self) -> SchematicSummary {
+        SchematicSummary {
+            size: self.size,
+            offset: self.offset,
+            block_entities: self.block_entities.len(),
+            entities: self.entities.len(),
+        }
+    }
+
+    /// a palette compound and varint indices
+    fn sponge_data<T: PartialEq + Clone>(
+        container: &PalettedContainer<T>,
+        name: impl Fn(&T) -> String,
+    ) -> (Map<String, NbtValue>, NbtValue) {
+        let mut container = container.clone();
+        container.compact();
+        let palette = container
+            .palette
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (name(e), NbtValue::Int(i as i32)));
+        let mut data = Vec::with_capacity(container.indices.len());
+        for index in container.indices.iter() {
+            write_varint(&mut data, *index as u32);
+        }
+        (Map::from(palette), NbtValue::ByteArray(data))
+    }
+
+    /// the schematic in Sponge format version 2 or 3; version 2 only has one biome per column,
+    /// the one at the bottom
+    pub fn to_sponge(&self, version: i32) -> Result<NbtFile> {
+        if !(2..=3).contains(&version) {
+            return Err(Error::Custom(format!(
+                "unsupported sponge schematic version {version}"
+            )));
+        }
+        if self.size.iter().any(|e| *e > u16::MAX as i32) {
+            return Err(Error::Custom("schematic too large".to_string()));
+        }
+        let short = |v: i32| NbtValue::Short(v as u16 as i16);
+        let string = |s: &str| NbtValue::String(s.to_string());
+        let mut root = Map::from([
+            ("Version".to_string(), NbtValue::Int(version)),
+            ("DataVersion".to_string(), NbtValue::Int(self.data_version)),
+            (
+                "Metadata".to_string(),
+                NbtValue::Compound(Map::from([(
+                    "Date".to_string(),
+                    NbtValue::Long(unix_time() as i64 * 1000),
+                )])),
+            ),
+            ("Width".to_string(), short(self.size[0])),
+            ("Height".to_string(), short(self.size[1])),
+            ("Length".to_string(), short(self.size[2])),
+            (
+                "Offset".to_string(),
+                NbtValue::IntArray(self.offset.to_vec()),
+            ),
+        ]);
+        let (palette, data) =
+            Self::sponge_data(&self.blocks, |e| block::namespaced(&e.to_string()));
+
+        // version 2 keeps the data next to the id and position, version 3 in `Data`
+        let with_data = |mut entry: Map<String, NbtValue>, data: Map<String, NbtValue>| {
+            match version {
+                2 => data.iter().for_each(|(k, v)| {
+                    entry.insert(k.clone(), v.clone());
+                }),
+                _ => {
+                    entry.insert("Data".to_string(), NbtValue::Compound(data));
+                }
+            }
+            entry
+        };
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|(pos, nbt)| {
+                let mut data = nbt.clone();
+                let id = data
+                    .remove("id")
+                    .and_then(|e| e.as_str().map(str::to_string));
+                let entry = Map::from([
+                    ("Pos".to_string(), NbtValue::IntArray(pos.to_vec())),
+                    ("Id".to_string(), string(&id.unwrap_or_default())),
+                ]);
+                with_data(entry, data)
+            })
+            .collect();
+        let entities = self
+            .entities
+            .iter()
+            .map(|nbt| {
+                let mut data = nbt.clone();
+                let id = data
+                    .remove("id")
+                    .and_then(|e| e.as_str().map(str::to_string));
+                let pos = data
+                    .remove("Pos")
+                    .unwrap_or(NbtValue::List(NbtList::EmptyList()));
+                let entry = Map::from([
+                    ("Pos".to_string(), pos),
+                    ("Id".to_string(), string(&id.unwrap_or_default())),
+                ]);
+                with_data(entry, data)
+            })
+            .collect();
+        let block_entities = NbtValue::List(NbtList::CompoundList(block_entities));
+        let entities = NbtValue::List(NbtList::CompoundList(entities));
+
+        match version {
+            2 => {
+                let palette_max = palette.len() as i32;
+                root.insert("PaletteMax".to_string(), NbtValue::Int(palette_max));
+                root.insert("Palette".to_string(), NbtValue::Compound(palette));
+                root.insert("BlockData".to_string(), data);
+                root.insert("BlockEntities".to_string(), block_entities);
+                root.insert("Entities".to_string(), entities);
+                if let Some(biomes) = &self.biomes {
+                    let columns = (self.size[0] * self.size[2]) as usize;
+                    let bottom = PalettedContainer {
+                        palette: biomes.palette.clone(),
+                        indices: biomes.indices[..columns].to_vec(),
+                    };
+                    let (palette, data) = Self::sponge_data(&bottom, String::clone);
+                    let palette_max = palette.len() as i32;
+                    root.insert("BiomePaletteMax".to_string(), NbtValue::Int(palette_max));
+                    root.insert("BiomePalette".to_string(), NbtValue::Compound(palette));
+                    root.insert("BiomeData".to_string(), data);
+                }
+                Ok(NbtFile {
+                    name: "Schematic".to_string(),
+                    nbt: NbtValue::Compound(root),
+                })
+            }
+            _ => {
+                let blocks = Map::from([
+                    ("Palette".to_string(), NbtValue::Compound(palette)),
+                    ("Data".to_string(), data),
+                    ("BlockEntities".to_string(), block_entities),
+                ]);
+                root.insert("Blocks".to_string(), NbtValue::Compound(blocks));
+                if let Some(biomes) = &self.biomes {
+                    let (palette, data) = Self::sponge_data(biomes, String::clone);
+                    let biomes = Map::from([
+                        ("Palette".to_string(), NbtValue::Compound(palette)),
+                        ("Data".to_string(), data),
+                    ]);
+                    root.insert("Biomes".to_string(), NbtValue::Compound(biomes));
+                }
+                root.insert("Entities".to_string(), entities);
+                Ok(NbtFile {
+                    name: String::new(),
+                    nbt: NbtValue::Compound(Map::from([(
+                        "Schematic".to_string(),
+                        NbtValue::Compound(root),
+                    )])),
+                })
+            }
+        }
+    }
+
+    /// writes a gzipped Sponge schematic, see [`Schematic::to_sponge`]
+    pub fn save_sponge(&self, path: &Path, version: i32) -> Result<()> {
+        let file = self.to_sponge(version)?;
+        file.to_compressed_writer(File::create(path).map_err(Error::FileError)?)
+            .map_err(Error::FileError)
+    }
+}
+
+impl World {
+    /// copies the blocks, biomes, block entities and entities of `bounds`; missing chunks
+    /// are air, entities are taken when their position is inside the box
+    pub fn extract_schematic(&self, dim: Dimension, bounds: BoundingBox) -> Result<Schematic> {
+        let size = bounds.size();
+        let volume = size.iter().map(|e| *e as usize).product();
+        let mut schematic = Schematic {
+            size,
+            offset: bounds.min,
+            data_version: DATA_VERSION,
+            blocks: PalettedContainer::filled(BlockState::new("minecraft:air"), volume),
+            biomes: Some(PalettedContainer::filled(String::new(), volume)),
+            block_entities: vec![],
+            entities: vec![],
+        };
+        let mut data_version = None;
+        let chunk = |kind, cx: i32, cz: i32| -> Result<Option<Chunk>> {
+            match self.get_existing_region(cx >> 5, cz >> 5, dim, kind)? {
+                Some(region) => region.get_chunk(cx & 31, cz & 31),
+                None => Ok(None),
+            }
+        };
+        for (cx, cz) in bounds.chunks() {
+            let (builder, block_entities) = match chunk(RegionKind::Chunks, cx, cz)? {
+                Some(chunk) => chunk.read(|nbt| {
+                    let root = Chunk::root(nbt);
+                    let version = root.get("DataVersion").and_then(NbtValue::as_i64);
+                    data_version = data_version.max(version);
+                    let block_entities = match root.get("block_entities") {
+                        Some(NbtValue::List(list)) => list.compounds().to_vec(),
+                        _ => vec![],
+                    };
+                    ChunkBuilder::from_nbt(root, dim).map(|e| (e, block_entities))
+                })?,
+                None => (ChunkBuilder::new(cx, cz, dim), vec![]),
+            };
+            let column = BoundingBox::chunk_column(cx, cz, bounds.min[1], bounds.max[1]);
+            let clip = bounds.intersection(&column).unwrap();
+            for y in clip.min[1]..=clip.max[1] {
+                for z in clip.min[2]..=clip.max[2] {
+                    for x in clip.min[0]..=clip.max[0] {
+                        let pos = [0, 1, 2].map(|i| [x, y, z][i] - bounds.min[i]);
+                        let i = schematic.index(pos);
+                        schematic.blocks.set(i, builder.block(x, y, z));
+                        let biome = builder.biome(x, y, z).to_string();
+                        schematic.biomes.as_mut().unwrap().set(i, biome);
+                    }
+                }
+            }
+            for mut nbt in block_entities {
+                let int = |key| nbt.get(key).and_then(NbtValue::as_i64).map(|e| e as i32);
+                let (Some(x), Some(y), Some(z)) = (int("x"), int("y"), int("z")) else {
+                    continue;
+                };
+                if !clip.contains([x, y, z]) {
+                    continue;
+                }
+                for key in ["x", "y", "z", "keepPacked"] {
+                    nbt.remove(key);
+                }
+                let pos = [x - bounds.min[0], y - bounds.min[1], z - bounds.min[2]];
+                schematic.block_entities.push((pos, nbt));
+            }
+
+            let Some(entities) = chunk(RegionKind::Entities, cx, cz)? else {
+                continue;
+            };
+            entities.read(|nbt| {
+                let Some(NbtValue::List(list)) = nbt.get("Entities") else {
+                    return;
+                };
+                for entity in list.compounds() {
+                    let Some(pos) = entity_pos(entity) else {
+                        continue;
+                    };
+                    if !bounds.contains(pos.map(|e| e.floor() as i32)) {
+                        continue;
+                    }
+                    let mut entity = entity.clone();
+                    shift_entity(&mut entity, bounds.min.map(|e| -e as f64));
+                    schematic.entities.push(entity);
+                }
+            });
+        }
+        schematic.blocks.compact();
+        if let Some(biomes) = schematic.biomes.as_mut() {
+            biomes.compact();
+        }
+        schematic.data_version = data_version.map_or(DATA_VERSION, |e| e as i32);
+        Ok(schematic)
+    }
+
+    /// pastes `schematic` with its lowest corner at `origin` after mirroring and turning it;
+    /// block entities and scheduled ticks of replaced blocks are dropped, pasted entities get
+    /// new uuids and blocks above or below the world are left out; chunks that don't exist yet are
+    /// skipped unless [`PasteOptions::create_chunks`] is set; everything is written in one
+    /// transaction
+    pub fn paste_schematic(
+        &self,
+        dim: Dimension,
+        schematic: &Schematic,
+        origin: [i32; 3],
+        options: &PasteOptions,
+    ) -> Result<PasteReport> {
+        let schematic = schematic.transformed(options.rotation, options.mirror);
+        let max = [0, 1, 2].map(|i| origin[i] + schematic.size[i] - 1);
+        if schematic.size.contains(&0) {
+            return Ok(PasteReport::default());
+        }
+        let bounds = BoundingBox::new(origin, max);
+        let block_entities = schematic
+            .block_entities
+            .iter()
+            .map(|(pos, nbt)| ([0, 1, 2].map(|i| pos[i] + origin[i]), nbt))
+            .collect::<Vec<_>>();
+
+        let transaction = self.transaction()?;
+        let mut report = PasteReport::default();
+        let mut skipped = HashSet::new();
+        for ((rx, rz), chunks) in by_region(bounds.chunks()) {
+            for (cx, cz) in chunks {
+                let mut chunk = match transaction.get_chunk(cx, cz, dim, RegionKind::Chunks)? {
+                    Some(chunk) => chunk.read(|nbt| ChunkBuilder::from_nbt(nbt, dim))?,
+                    None if options.create_chunks => {
+                        report.created_chunks += 1;
+                        ChunkBuilder::new(cx, cz, dim)
+                    }
+                    None => {
+                        report.skipped_chunks += 1;
+                        skipped.insert((cx, cz));
+                        continue;
+                    }
+                };
+                let (min_y, max_y) = chunk.y_range();
+                let column = BoundingBox::chunk_column(cx, cz, min_y, max_y);
+                let Some(clip) = bounds.intersection(&column) else {
+                    continue;
+                };
+                let mut replaced = HashSet::new();
+                for y in clip.min[1]..=clip.max[1] {
+                    for z in clip.min[2]..=clip.max[2] {
+                        for x in clip.min[0]..=clip.max[0] {
+                            let pos = [x - origin[0], y - origin[1], z - origin[2]];
+                            let state = schematic.block(pos);
+                            if options.biomes {
+                                if let Some(biomes) = &schematic.biomes {
+                                    chunk.set_biome_at(x, y, z, biomes.get(schematic.index(pos)));
+                                }
+                            }
+                            if state.name == "minecraft:structure_void"
+                                || (options.skip_air && state.is_air())
+                            {
+                                continue;
+                            }
+                            chunk.set_block(x, y, z, state);
+                            replaced.insert([x, y, z]);
+                        }
+                    }
+                }
+                let mut nbt = chunk.to_nbt();
+                let root = nbt.as_compound_mut().ok_or(Error::Malformed(line!()))?;
+                for key in ["block_entities", "block_ticks", "fluid_ticks"] {
+                    if let Some(NbtValue::List(NbtList::CompoundList(list))) = root.get_mut(key) {
+                        list.retain(|e| {
+                            let int =
+                                |key| e.get(key).and_then(NbtValue::as_i64).unwrap_or(0) as i32;
+                            !replaced.contains(&[int("x"), int("y"), int("z")])
+                        });
+                    }
+                }
+                let mut added = block_entities
+                    .iter()
+                    .filter(|(pos, _)| replaced.contains(pos))
+                    .map(|(pos, nbt)| {
+                        let mut nbt = (*nbt).clone();
+                        for (key, v) in ["x", "y", "z"].into_iter().zip(pos) {
+                            nbt.insert(key.to_string(), NbtValue::Int(*v));
+                        }
+                        nbt
+                    })
+                    .collect::<Vec<_>>();
+                report.block_entities += added.len();
+                match root.get_mut("block_entities") {
+                    Some(NbtValue::List(NbtList::CompoundList(list))) => list.append(&mut added),
+                    _ => {
+                        let list = NbtValue::List(NbtList::CompoundList(added));
+                        root.insert("block_entities".to_string(), list);
+                    }
+                }
+                transaction
+                    .get_region(cx >> 5, cz >> 5, dim, RegionKind::Chunks)?
+                    .set_chunk(cx & 31, cz & 31, &Chunk::new(nbt))?;
+                report.blocks += replaced.len();
+                report.chunks += 1;
+            }
+            transaction.finish_region(rx, rz, dim, RegionKind::Chunks)?;
+        }
+
+        if options.entities {
+            let mut by_chunk = BTreeMap::<(i32, i32), Vec<Map<String, NbtValue>>>::new();
+            for entity in schematic.entities.iter() {
+                let mut entity = entity.clone();
+                shift_entity(&mut entity, origin.map(|e| e as f64));
+                strip_uuids(&mut entity);
+                let Some(pos) = entity_pos(&entity) else {
+                    continue;
+                };
+                let chunk = ((pos[0].floor() as i32) >> 4, (pos[2].floor() as i32) >> 4);
+                if skipped.contains(&chunk) {
+                    continue;
+                }
+                by_chunk.entry(chunk).or_default().push(entity);
+            }
+            let chunks = by_chunk.keys().copied().collect::<Vec<_>>();
+            for ((rx, rz), chunks) in by_region(chunks) {
+                for (cx, cz) in chunks {
+                    let mut entities = by_chunk.remove(&(cx, cz)).unwrap_or_default();
+                    report.entities += entities.len();
+                    let mut nbt = match transaction.get_chunk(cx, cz, dim, RegionKind::Entities)? {
+                        Some(chunk) => chunk.read(|nbt| nbt.clone()),
+                        None => NbtValue::Compound(Map::from([
+                            ("DataVersion".to_string(), NbtValue::Int(DATA_VERSION)),
+                            ("Position".to_string(), NbtValue::IntArray(vec![cx, cz])),
+                        ])),
+                    };
+                    let root = nbt.as_compound_mut().ok_or(Error::Malformed(line!()))?;
+                    match root.get_mut("Entities") {
+                        Some(NbtValue::List(NbtList::CompoundList(list))) => {
+                            list.append(&mut entities)
+                        }
+                        _ => {
+                            let list = NbtValue::List(NbtList::CompoundList(entities));
+                            root.insert("Entities".to_string(), list);
+                        }
+                    }
+                    transaction
+                        .get_region(cx >> 5, cz >> 5, dim, RegionKind::Entities)?
+                        .set_chunk(cx & 31, cz & 31, &Chunk::new(nbt))?;
+                }
+                transaction.finish_region(rx, rz, dim, RegionKind::Entities)?;
+            }
+        }
+        let session = transaction.session().to_string();
+        report.session = (transaction.commit()? > 0).then_some(session);
+        Ok(report)
+    }
+}
@@ -0,0 +1,381 @@
+use std::fs::File;
+
+use serde::Serialize;
+
+use crate::{
+    block::namespaced,
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, Map, NbtFile, NbtList, NbtValue, Result,
+};
+
+/// keys holding a list of items on block entities, entities and players
+const ITEM_LIST_KEYS: [&str; 5] = [
+    "Items",
+    "Inventory",
+    "EnderItems",
+    "HandItems",
+    "ArmorItems",
+];
+/// keys holding a single item
+const ITEM_KEYS: [&str; 8] = [
+    "Item",
+    "item",
+    "RecordItem",
+    "Book",
+    "SaddleItem",
+    "ArmorItem",
+    "body_armor_item",
+    "FireworksItem",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemPredicate {
+    /// item id, `minecraft:` is assumed when no namespace is given
+    Id(String),
+    /// case insensitive substring of the custom name
+    Name(String),
+    /// enchantment id, stored or applied
+    Enchantment(String),
+    /// path into the custom data, `a.b.c`
+    CustomData(String),
+    All(Vec<ItemPredicate>),
+    Any(Vec<ItemPredicate>),
+}
+
+impl ItemPredicate {
+    pub fn matches(&self, item: &Map<String, NbtValue>) -> bool {
+        match self {
+            ItemPredicate::Id(id) => item
+                .get("id")
+                .and_then(NbtValue::as_str)
+                .is_some_and(|e| namespaced(e) == namespaced(id)),
+            ItemPredicate::Name(name) => {
+                custom_name(item).is_some_and(|e| e.to_lowercase().contains(&name.to_lowercase()))
+            }
+            ItemPredicate::Enchantment(id) => {
+                enchantments(item).any(|e| namespaced(&e) == namespaced(id))
+            }
+            ItemPredicate::CustomData(path) => custom_data(item).is_some_and(|mut data| {
+                path.split('.')
+                    .all(|key| data.get(key).map(|e| data = e).is_some())
+            }),
+            ItemPredicate::All(v) => v.iter().all(|e| e.matches(item)),
+            ItemPredicate::Any(v) => v.iter().any(|e| e.matches(item)),
+        }
+    }
+}
+
+/// components of 1.20.5+ items, `tag` in older versions
+fn components(item: &Map<String, NbtValue>) -> Option<&NbtValue> {
+    item.get("components").or_else(|| item.get("tag"))
+}
+
+fn custom_data(item: &Map<String, NbtValue>) -> Option<&NbtValue> {
+    match item.get("components") {
+        Some(components) => components.get("minecraft:custom_data"),
+        None => item.get("tag"),
+    }
+}
+
+fn custom_name(item: &Map<String, NbtValue>) -> Option<String> {
+    let name = match item.get("components") {
+        Some(components) => components.get("minecraft:custom_name")?,
+        None => item.get("tag")?.get("display")?.get("Name")?,
+    };
+    Some(text_component(name))
+}
+
+/// plain text of a text component, either json (pre 1.21.5) or nbt
+fn text_component(value: &NbtValue) -> String {
+    fn json_text(value: &serde_json::Value, out: &mut String) {
+        match value {
+            serde_json::Value::String(s) => out.push_str(s),
+            serde_json::Value::Array(v) => v.iter().for_each(|e| json_text(e, out)),
+            serde_json::Value::Object(o) => {
+                if let Some(serde_json::Value::String(s)) = o.get("text") {
+                    out.push_str(s);
+                }
+                if let Some(extra) = o.get("extra") {
+                    json_text(extra, out);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn nbt_text(value: &NbtValue, out: &mut String) {
+        match value {
+            NbtValue::String(s) => out.push_str(s),
+            NbtValue::Compound(_) => {
+                if let Some(s) = value.get("text").and_then(NbtValue::as_str) {
+                    out.push_str(s);
+                }
+                if let Some(extra) = value.get("extra") {
+                    nbt_text(extra, out);
+                }
+            }
+            NbtValue::List(NbtList::StringList(v)) => v.iter().for_each(|e| out.push_str(e)),
+            NbtValue::List(NbtList::CompoundList(v)) => v.iter().for_each(|e| {
+                if let Some(s) = e.get("text").and_then(NbtValue::as_str) {
+                    out.push_str(s);
+                }
+                if let Some(extra) = e.get("extra") {
+                    nbt_text(extra, out);
+                }
+            }),
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    match value {
+        NbtValue::String(s) => match serde_json::from_str::<serde_json::Value>(s) {
+            Ok(json) => json_text(&json, &mut out),
+            Err(_) => out.push_str(s),
+        },
+        value => nbt_text(value, &mut out),
+    }
+    out
+}
+
+fn enchantments(item: &Map<String, NbtValue>) -> impl Iterator<Item = String> + '_ {
+    let mut ids = vec![];
+    if let Some(components) = item.get("components") {
+        for key in ["minecraft:enchantments", "minecraft:stored_enchantments"] {
+            let Some(enchantments) = components.get(key) else {
+                continue;
+            };
+            // before 1.21.5 the levels are wrapped in a `levels` compound
+            let levels = enchantments.get("levels").unwrap_or(enchantments);
+            if let Some(levels) = levels.as_compound() {
+                ids.extend(levels.iter().map(|(k, _)| k.clone()));
+            }
+        }
+    } else if let Some(tag) = item.get("tag") {
+        for key in ["Enchantments", "StoredEnchantments", "ench"] {
+            let Some(NbtValue::List(list)) = tag.get(key) else {
+                continue;
+            };
+            ids.extend(
+                list.compounds()
+                    .iter()
+                    .filter_map(|e| e.get("id").and_then(NbtValue::as_str))
+                    .map(str::to_string),
+            );
+        }
+    }
+    ids.into_iter()
+}
+
+/// what holds a matched item
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ItemHolder {
+    BlockEntity {
+        dimension: Dimension,
+        id: String,
+        pos: [i32; 3],
+    },
+    Entity {
+        dimension: Dimension,
+        id: String,
+        pos: [f64; 3],
+    },
+    Player {
+        uuid: String,
+        pos: Option<[f64; 3]>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemMatch {
+    pub holder: ItemHolder,
+    /// keys and slots leading from the holder to the item, `Items[3]`
+    pub path: Vec<String>,
+    pub item: NbtValue,
+}
+
+struct Search<'a> {
+    predicate: &'a ItemPredicate,
+    matches: Vec<ItemMatch>,
+}
+
+impl Search<'_> {
+    /// visits every item stored on a block entity, entity or player
+    fn holder(
+        &mut self,
+        holder: &ItemHolder,
+        value: &Map<String, NbtValue>,
+        path: &mut Vec<String>,
+    ) {
+        for key in ITEM_LIST_KEYS {
+            if let Some(NbtValue::List(list)) = value.get(key) {
+                self.list(holder, list, key, path);
+            }
+        }
+        for key in ITEM_KEYS {
+            if let Some(NbtValue::Compound(item)) = value.get(key) {
+                path.push(key.to_string());
+                self.item(holder, item, path);
+                path.pop();
+            }
+        }
+        // 1.21.5+ stores armor and hand items in `equipment`
+        if let Some(equipment) = value.get("equipment").and_then(NbtValue::as_compound) {
+            for (slot, item) in equipment.iter() {
+                if let NbtValue::Compound(item) = item {
+                    path.push(format!("equipment.{slot}"));
+                    self.item(holder, item, path);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    fn list(&mut self, holder: &ItemHolder, list: &NbtList, key: &str, path: &mut Vec<String>) {
+        for (i, item) in list.compounds().iter().enumerate() {
+            // container components wrap the item next to its slot
+            let (slot, item) = match item.get("item") {
+                Some(NbtValue::Compound(inner)) if item.contains_key("slot") => {
+                    (item.get("slot").and_then(NbtValue::as_i64), inner)
+                }
+                _ => (item.get("Slot").and_then(NbtValue::as_i64), item),
+            };
+            path.push(format!("{key}[{}]", slot.unwrap_or(i as i64)));
+            self.item(holder, item, path);
+            path.pop();
+        }
+    }
+
+    fn item(&mut self, holder: &ItemHolder, item: &Map<String, NbtValue>, path: &mut Vec<String>) {
+        if item.get("id").is_none() {
+            return;
+        }
+        if self.predicate.matches(item) {
+            self.matches.push(ItemMatch {
+                holder: holder.clone(),
+                path: path.clone(),
+                item: NbtValue::Compound(item.clone()),
+            });
+        }
+        let Some(components) = components(item) else {
+            return;
+        };
+        for key in ["minecraft:container", "minecraft:bundle_contents", "Items"] {
+            if let Some(NbtValue::List(list)) = components.get(key) {
+                self.list(holder, list, key, path);
+            }
+        }
+        if let Some(NbtValue::List(list)) = components
+            .get("BlockEntityTag")
+            .and_then(|e| e.get("Items"))
+        {
+            self.list(holder, list, "BlockEntityTag.Items", path);
+        }
+    }
+
+    fn entity(&mut self, dimension: Dimension, entity: &Map<String, NbtValue>) {
+        let pos = entity
+            .get("Pos")
+            .and_then(NbtValue::as_list)
+            .map(NbtList::to_f64_vec)
+            .and_then(|e| e.try_into().ok())
+            .unwrap_or_default();
+        let holder = ItemHolder::Entity {
+            dimension,
+            id: entity
+                .get("id")
+                .and_then(NbtValue::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            pos,
+        };
+        self.holder(&holder, entity, &mut vec![]);
+        if let Some(NbtValue::List(passengers)) = entity.get("Passengers") {
+            for passenger in passengers.compounds() {
+                self.entity(dimension, passenger);
+            }
+        }
+    }
+
+    fn chunk(&mut self, dimension: Dimension, nbt: &NbtValue) {
+        let root = Chunk::root(nbt);
+        for key in ["block_entities", "TileEntities"] {
+            let Some(NbtValue::List(list)) = root.get(key) else {
+                continue;
+            };
+            for block_entity in list.compounds() {
+                let coord = |k| {
+                    block_entity
+                        .get(k)
+                        .and_then(NbtValue::as_i64)
+                        .unwrap_or_default() as i32
+                };
+                let holder = ItemHolder::BlockEntity {
+                    dimension,
+                    id: block_entity
+                        .get("id")
+                        .and_then(NbtValue::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    pos: [coord("x"), coord("y"), coord("z")],
+                };
+                self.holder(&holder, block_entity, &mut vec![]);
+            }
+        }
+        if let Some(NbtValue::List(list)) = root.get("Entities") {
+            for entity in list.compounds() {
+                self.entity(dimension, entity);
+            }
+        }
+    }
+
+    fn player(&mut self, uuid: String, player: &Map<String, NbtValue>) {
+        let pos = player
+            .get("Pos")
+            .and_then(NbtValue::as_list)
+            .map(NbtList::to_f64_vec)
+            .and_then(|e| e.try_into().ok());
+        self.holder(&ItemHolder::Player { uuid, pos }, player, &mut vec![]);
+    }
+}
+
+impl World {
+    /// finds every item matching `predicate` in all dimensions and player files
+    pub fn find_items(&self, predicate: &ItemPredicate) -> Result<Vec<ItemMatch>> {
+        let mut search = Search {
+            predicate,
+            matches: vec![],
+        };
+        for dimension in Dimension::ALL {
+            // entities live in the chunk itself before 1.17
+            for kind in [RegionKind::Chunks, RegionKind::Entities] {
+                for (x, z) in self.region_positions(dimension, kind)? {
+                    let Some(region) = self.get_existing_region(x, z, dimension, kind)? else {
+                        continue;
+                    };
+                    for (cx, cz) in region.chunk_positions() {
+                        if let Some(chunk) = region.get_chunk(cx, cz)? {
+                            chunk.read(|nbt| search.chunk(dimension, nbt));
+                        }
+                    }
+                }
+            }
+        }
+        for (uuid, path) in self.player_files()? {
+            let file =
+                NbtFile::from_compressed_reader(File::open(path).map_err(Error::FileError)?)?;
+            if let Some(player) = file.nbt.as_compound() {
+                search.player(uuid, player);
+            }
+        }
+        // singleplayer worlds keep the host in level.dat
+        self.get_level_dat()?.data(|data| {
+            if let Some(player) = data
+                .and_then(|e| e.get("Player"))
+                .and_then(NbtValue::as_compound)
+            {
+                search.player("level.dat".to_string(), player);
+            }
+        });
+        Ok(search.matches)
+    }
+}
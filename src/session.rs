@@ -0,0 +1,77 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{Error, Result};
+
+/// worlds locked by this process, file locks don't conflict within one process
+static LOCKED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// the lock on `session.lock` the game holds while a world is open, released on drop
+pub(crate) struct SessionLock {
+    world: PathBuf,
+    _file: File,
+}
+
+impl SessionLock {
+    /// locks the world like vanilla does, fails with [`Error::WorldInUse`] if a game,
+    /// server or another `World` has it open
+    pub(crate) fn acquire(world: &Path) -> Result<Self> {
+        let world = world.canonicalize().map_err(Error::FileError)?;
+        let mut locked = LOCKED.lock().unwrap();
+        if locked.contains(&world) {
+            return Err(Error::WorldInUse(world));
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(world.join("session.lock"))
+            .map_err(Error::FileError)?;
+        if !try_lock(&file).map_err(Error::FileError)? {
+            return Err(Error::WorldInUse(world));
+        }
+        // vanilla writes a snowman so the file isn't empty
+        file.write_all("☃".as_bytes()).map_err(Error::FileError)?;
+        locked.push(world.clone());
+        Ok(Self { world, _file: file })
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        LOCKED.lock().unwrap().retain(|e| *e != self.world);
+    }
+}
+
+/// java locks with fcntl on unix, which flock based locks don't see
+#[cfg(unix)]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: flock is plain data, all zeroes locks the whole file
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    // SAFETY: the descriptor is open for the lifetime of `file`
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &lock) } == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EACCES | libc::EAGAIN) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    match file.try_lock() {
+        Ok(()) => Ok(true),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(false),
+        Err(std::fs::TryLockError::Error(e)) => Err(e),
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashMap, fs::File, path::Path};
+
+use crate::{
+    area::BoundingBox,
+    block::{self, BlockState, PalettedContainer},
+    builder::DATA_VERSION,
+    schematic::{PasteOptions, PasteReport, Schematic},
+    world::{Dimension, World},
+    Error, Map, NbtFile, NbtList, NbtValue, Result,
+};
+
+/// a block of a structure file, positions missing from a structure are left alone when it is
+/// placed
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureBlock {
+    pub pos: [i32; 3],
+    /// index into every palette
+    pub state: usize,
+    /// block entity data with its `id`, without `x`, `y` and `z`
+    pub nbt: Option<Map<String, NbtValue>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureEntity {
+    /// relative to the structure's lowest corner
+    pub pos: [f64; 3],
+    pub block_pos: [i32; 3],
+    pub nbt: Map<String, NbtValue>,
+}
+
+/// a vanilla structure file as saved by structure blocks and read from datapacks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    pub size: [i32; 3],
+    pub data_version: i32,
+    /// usually one, structures like shipwrecks have several variants of the same length that
+    /// the game picks from at random
+    pub palettes: Vec<Vec<BlockState>>,
+    pub blocks: Vec<StructureBlock>,
+    pub entities: Vec<StructureEntity>,
+}
+
+fn int_list(value: Option<&NbtValue>) -> Option<[i32; 3]> {
+    match value? {
+        NbtValue::List(NbtList::IntList(v)) => v.as_slice().try_into().ok(),
+        NbtValue::List(list) => {
+            let v = list.to_f64_vec();
+            (v.len() == 3).then(|| [v[0] as i32, v[1] as i32, v[2] as i32])
+        }
+        _ => None,
+    }
+}
+
+fn read_palette(list: &NbtList) -> Result<Vec<BlockState>> {
+    list.compounds()
+        .iter()
+        .map(|e| {
+            BlockState::from_nbt(&NbtValue::Compound(e.clone())).ok_or(Error::Malformed(line!()))
+        })
+        .collect()
+}
+
+impl Structure {
+    /// reads a gzipped structure file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = NbtFile::from_compressed_reader(File::open(path).map_err(Error::FileError)?)?;
+        Self::from_nbt(&file.nbt)
+    }
+
+    /// the root of a structure file with either `palette` or `palettes`
+    pub fn from_nbt(nbt: &NbtValue) -> Result<Self> {
+        let size = int_list(nbt.get("size")).ok_or(Error::Malformed(line!()))?;
+        let palettes = match (nbt.get("palette"), nbt.get("palettes")) {
+            (Some(NbtValue::List(palette)), _) => vec![read_palette(palette)?],
+            (None, Some(NbtValue::List(NbtList::ListList(palettes)))) => {
+                palettes.iter().map(read_palette).collect::<Result<_>>()?
+            }
+            _ => return Err(Error::Malformed(line!())),
+        };
+        if palettes.is_empty() || palettes.iter().any(|e| e.len() != palettes[0].len()) {
+            return Err(Error::Custom(
+                "structure palettes differ in length".to_string(),
+            ));
+        }
+        let list = |key| {
+            nbt.get(key)
+                .and_then(NbtValue::as_list)
+                .map(|e| e.compounds().to_vec())
+                .unwrap_or_default()
+        };
+        let blocks = list("blocks")
+            .into_iter()
+            .map(|e| {
+                let pos = int_list(e.get("pos")).ok_or(Error::Malformed(line!()))?;
+                let state = e
+                    .get("state")
+                    .and_then(NbtValue::as_i64)
+                    .filter(|e| (0..palettes[0].len() as i64).contains(e))
+                    .ok_or(Error::Malformed(line!()))?;
+                if (0..3).any(|i| !(0..size[i]).contains(&pos[i])) {
+                    return Err(Error::Malformed(line!()));
+                }
+                let nbt = match e.get("nbt") {
+                    Some(NbtValue::Compound(nbt)) => Some(nbt.clone()),
+                    _ => None,
+                };
+                Ok(StructureBlock {
+                    pos,
+                    state: state as usize,
+                    nbt,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let entities = list("entities")
+            .into_iter()
+            .filter_map(|e| {
+                let pos: [f64; 3] = match e.get("pos") {
+                    Some(NbtValue::List(list)) => list.to_f64_vec().try_into().ok()?,
+                    _ => return None,
+                };
+                let block_pos =
+                    int_list(e.get("blockPos")).unwrap_or(pos.map(|e| e.floor() as i32));
+                let nbt = match e.get("nbt") {
+                    Some(NbtValue::Compound(nbt)) => nbt.clone(),
+                    _ => return None,
+                };
+                Some(StructureEntity {
+                    pos,
+                    block_pos,
+                    nbt,
+                })
+            })
+            .collect();
+        Ok(Self {
+            size,
+            data_version: nbt
+                .get("DataVersion")
+                .and_then(NbtValue::as_i64)
+                .map_or(DATA_VERSION, |e| e as i32),
+            palettes,
+            blocks,
+            entities,
+        })
+    }
+
+    /// a single palette is written as `palette`, several as `palettes`
+    pub fn to_nbt(&self) -> NbtValue {
+        let ints = |v: [i32; 3]| NbtValue::List(NbtList::IntList(v.to_vec()));
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|e| {
+                let mut block = Map::from([
+                    ("pos".to_string(), ints(e.pos)),
+                    ("state".to_string(), NbtValue::Int(e.state as i32)),
+                ]);
+                if let Some(nbt) = &e.nbt {
+                    block.insert("nbt".to_string(), NbtValue::Compound(nbt.clone()));
+                }
+                block
+            })
+            .collect();
+        let entities = self
+            .entities
+            .iter()
+            .map(|e| {
+                Map::from([
+                    (
+                        "pos".to_string(),
+                        NbtValue::List(NbtList::DoubleList(e.pos.to_vec())),
+                    ),
+                    ("blockPos".to_string(), ints(e.block_pos)),
+                    ("nbt".to_string(), NbtValue::Compound(e.nbt.clone())),
+                ])
+            })
+            .collect();
+        let palettes = match self.palettes.as_slice() {
+            [palette] => ("palette".to_string(), block::palette_to_nbt(palette)),
+            palettes => (
+                "palettes".to_string(),
+                NbtValue::List(NbtList::ListList(
+                    palettes
+                        .iter()
+                        .filter_map(|e| match block::palette_to_nbt(e) {
+                            NbtValue::List(list) => Some(list),
+                            _ => None,
+                        })
+                        .collect(),
+                )),
+            ),
+        };
+        NbtValue::Compound(Map::from([
+            ("DataVersion".to_string(), NbtValue::Int(self.data_version)),
+            ("size".to_string(), ints(self.size)),
+            palettes,
+            (
+                "blocks".to_string(),
+                NbtValue::List(NbtList::CompoundList(blocks)),
+            ),
+            (
+                "entities".to_string(),
+                NbtValue::List(NbtList::CompoundList(entities)),
+            ),
+        ]))
+    }
+
+    /// writes a gzipped structure file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = NbtFile {
+            name: String::new(),
+            nbt: self.to_nbt(),
+        };
+        file.to_compressed_writer(File::create(path).map_err(Error::FileError)?)
+            .map_err(Error::FileError)
+    }
+
+    /// one palette of a schematic; structure voids are left out like structure blocks do, and
+    /// entities lose their uuids
+    pub fn from_schematic(schematic: &Schematic) -> Self {
+        let void = BlockState::new("structure_void");
+        let mut palette = vec![];
+        let mut states = vec![None; schematic.blocks.palette.len()];
+        let mut block_entities = schematic
+            .block_entities
+            .iter()
+            .cloned()
+            .collect::<HashMap<_, _>>();
+        let mut blocks = vec![];
+        let [w, h, l] = schematic.size;
+        for y in 0..h {
+            for z in 0..l {
+                for x in 0..w {
+                    let i = schematic.blocks.indices[schematic.index([x, y, z])] as usize;
+                    let state = &schematic.blocks.palette[i];
+                    if *state == void {
+                        continue;
+                    }
+                    let state = *states[i].get_or_insert_with(|| {
+                        palette.push(state.clone());
+                        palette.len() - 1
+                    });
+                    blocks.push(StructureBlock {
+                        pos: [x, y, z],
+                        state,
+                        nbt: block_entities.remove(&[x, y, z]),
+                    });
+                }
+            }
+        }
+        let entities = schematic
+            .entities
+            .iter()
+            .filter_map(|e| {
+                let pos: [f64; 3] = match e.get("Pos") {
+                    Some(NbtValue::List(list)) => list.to_f64_vec().try_into().ok()?,
+                    _ => return None,
+                };
+                let mut nbt = e.clone();
+                nbt.remove("UUID");
+                Some(StructureEntity {
+                    pos,
+                    block_pos: pos.map(|e| e.floor() as i32),
+                    nbt,
+                })
+            })
+            .collect();
+        Self {
+            size: schematic.size,
+            data_version: schematic.data_version,
+            palettes: vec![palette],
+            blocks,
+            entities,
+        }
+    }
+
+    /// the blocks of `palette` with structure voids where the structure has no block, which
+    /// [`World::paste_schematic`] leaves alone
+    pub fn to_schematic(&self, palette: usize) -> Result<Schematic> {
+        let states = self
+            .palettes
+            .get(palette)
+            .ok_or_else(|| Error::Custom(format!("structure has no palette {palette}")))?;
+        let volume = self.size.iter().map(|e| *e as usize).product();
+        let mut schematic = Schematic {
+            size: self.size,
+            offset: [0; 3],
+            data_version: self.data_version,
+            blocks: PalettedContainer::filled(BlockState::new("structure_void"), volume),
+            biomes: None,
+            block_entities: vec![],
+            entities: vec![],
+        };
+        for block in self.blocks.iter() {
+            let i = schematic.index(block.pos);
+            schematic.blocks.set(i, states[block.state].clone());
+            if let Some(nbt) = &block.nbt {
+                schematic.block_entities.push((block.pos, nbt.clone()));
+            }
+        }
+        schematic.blocks.compact();
+        schematic.entities = self
+            .entities
+            .iter()
+            .map(|e| {
+                let mut nbt = e.nbt.clone();
+                let pos = NbtValue::List(NbtList::DoubleList(e.pos.to_vec()));
+                nbt.insert("Pos".to_string(), pos);
+                nbt
+            })
+            .collect();
+        Ok(schematic)
+    }
+}
+
+impl World {
+    /// the blocks, block entities and entities of `bounds` as a structure, air included
+    pub fn extract_structure(&self, dim: Dimension, bounds: BoundingBox) -> Result<Structure> {
+        Ok(Structure::from_schematic(
+            &self.extract_schematic(dim, bounds)?,
+        ))
+    }
+
+    /// places one palette of `structure` with its lowest corner at `origin` like
+    /// [`World::paste_schematic`]; positions the structure has no block for are left alone
+    pub fn place_structure(
+        &self,
+        dim: Dimension,
+        structure: &Structure,
+        palette: usize,
+        origin: [i32; 3],
+        options: &PasteOptions,
+    ) -> Result<PasteReport> {
+        let schematic = structure.to_schematic(palette)?;
+        self.paste_schematic(dim, &schematic, origin, options)
+    }
+}
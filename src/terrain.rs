@@ -0,0 +1,254 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    block::BlockState,
+    generate::ChunkBuilder,
+    transaction::by_region,
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, NbtList, NbtValue, Result,
+};
+
+/// grayscale image with every pixel between 0 (black) and 1 (white)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    width: u32,
+    height: u32,
+    values: Vec<f64>,
+}
+
+impl Heightmap {
+    /// `values` row by row, one per pixel
+    pub fn new(width: u32, height: u32, values: Vec<f64>) -> Result<Self> {
+        if values.len() != width as usize * height as usize || values.is_empty() {
+            return Err(Error::Custom(format!(
+                "{} values for a {width}x{height} heightmap",
+                values.len()
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_png(BufReader::new(File::open(path).map_err(Error::FileError)?))
+    }
+
+    /// reads a png of any color type and bit depth, colors are converted to their luminance
+    pub fn from_png<R: BufRead + Seek>(reader: R) -> Result<Self> {
+        let invalid = |e: png::DecodingError| Error::Custom(format!("invalid png: {e}"));
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buf = vec![
+            0;
+            reader
+                .output_buffer_size()
+                .ok_or(Error::Malformed(line!()))?
+        ];
+        let info = reader.next_frame(&mut buf).map_err(invalid)?;
+        let channels = info.color_type.samples();
+        let wide = info.bit_depth == png::BitDepth::Sixteen;
+        let sample = |row: &[u8], i: usize| match wide {
+            true => u16::from_be_bytes([row[i * 2], row[i * 2 + 1]]) as f64 / 65535.0,
+            false => row[i] as f64 / 255.0,
+        };
+        let mut values = Vec::with_capacity(info.width as usize * info.height as usize);
+        for row in buf[..info.buffer_size()].chunks(info.line_size) {
+            for x in 0..info.width as usize {
+                let i = x * channels;
+                values.push(match channels {
+                    1 | 2 => sample(row, i),
+                    _ => {
+                        0.299 * sample(row, i)
+                            + 0.587 * sample(row, i + 1)
+                            + 0.114 * sample(row, i + 2)
+                    }
+                });
+            }
+        }
+        Self::new(info.width, info.height, values)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// the value at pixel coordinates, interpolated between pixel centers and clamped at the
+    /// edges
+    pub fn sample(&self, x: f64, z: f64) -> f64 {
+        let pixel = |x: i64, z: i64| {
+            let x = x.clamp(0, self.width as i64 - 1) as usize;
+            let z = z.clamp(0, self.height as i64 - 1) as usize;
+            self.values[z * self.width as usize + x]
+        };
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as i64, z0 as i64);
+        let top = pixel(x0, z0) * (1.0 - fx) + pixel(x0 + 1, z0) * fx;
+        let bottom = pixel(x0, z0 + 1) * (1.0 - fx) + pixel(x0 + 1, z0 + 1) * fx;
+        top * (1.0 - fz) + bottom * fz
+    }
+}
+
+/// how a heightmap becomes terrain
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainOptions {
+    pub dimension: Dimension,
+    /// block x, z the top left corner of the image lands on
+    pub origin: (i32, i32),
+    /// blocks per pixel
+    pub scale: f64,
+    /// y of the surface for black and for white pixels
+    pub heights: (i32, i32),
+    /// everything below the surface layers
+    pub fill: BlockState,
+    /// layers on top of the fill from the top down, with how many blocks each is thick
+    pub surface: Vec<(BlockState, u32)>,
+    /// the surface layers of columns below the sea level
+    pub underwater: Vec<(BlockState, u32)>,
+    /// the bottom layer of the world
+    pub floor: Option<BlockState>,
+    /// columns below this are covered with water up to it
+    pub sea_level: Option<i32>,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> Self {
+        Self {
+            dimension: Dimension::Overworld,
+            origin: (0, 0),
+            scale: 1.0,
+            heights: (40, 160),
+            fill: BlockState::new("stone"),
+            surface: vec![
+                (BlockState::new("grass_block").with("snowy", "false"), 1),
+                (BlockState::new("dirt"), 3),
+            ],
+            underwater: vec![(BlockState::new("sand"), 3)],
+            floor: Some(BlockState::new("bedrock")),
+            sea_level: Some(62),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TerrainReport {
+    pub chunks: usize,
+    pub columns: usize,
+    /// edit session to pass to [`World::undo`]
+    pub session: Option<String>,
+}
+
+/// replaces the column at x, z in the chunk with terrain whose surface is at `top`
+fn write_column(chunk: &mut ChunkBuilder, x: i32, z: i32, top: i32, options: &TerrainOptions) {
+    let (min, max) = chunk.y_range();
+    let top = top.clamp(min, max);
+    let sea_level = options.sea_level.filter(|e| *e > top);
+    let air = BlockState::new("air");
+    let water = BlockState::new("water").with("level", "0");
+    for y in top + 1..=max {
+        let state = match sea_level {
+            Some(sea_level) if y <= sea_level => &water,
+            _ => &air,
+        };
+        chunk.set_block(x, y, z, state);
+    }
+    let layers = match sea_level {
+        Some(_) => &options.underwater,
+        None => &options.surface,
+    };
+    let mut y = top;
+    for (state, count) in layers {
+        for _ in 0..*count {
+            chunk.set_block(x, y, z, state);
+            y -= 1;
+        }
+    }
+    for y in min..=y {
+        chunk.set_block(x, y, z, &options.fill);
+    }
+    if let Some(floor) = &options.floor {
+        chunk.set_block(x, min, z, floor);
+    }
+}
+
+impl World {
+    /// writes terrain shaped by `heightmap` into the world, every column the image covers is
+    /// rebuilt from the bottom of the world up, chunks it partly covers keep their other
+    /// columns; everything is written in one transaction
+    pub fn import_heightmap(
+        &self,
+        heightmap: &Heightmap,
+        options: &TerrainOptions,
+    ) -> Result<TerrainReport> {
+        if options.scale <= 0.0 || !options.scale.is_finite() {
+            return Err(Error::Custom(format!("invalid scale {}", options.scale)));
+        }
+        let dim = options.dimension;
+        let (x0, z0) = options.origin;
+        let x1 = x0 + (heightmap.width() as f64 * options.scale).round() as i32 - 1;
+        let z1 = z0 + (heightmap.height() as f64 * options.scale).round() as i32 - 1;
+        let (low, high) = options.heights;
+
+        let transaction = self.transaction()?;
+        let mut report = TerrainReport::default();
+        let chunks = (z0 >> 4..=z1 >> 4).flat_map(|cz| (x0 >> 4..=x1 >> 4).map(move |cx| (cx, cz)));
+        for ((rx, rz), chunks) in by_region(chunks) {
+            for (cx, cz) in chunks {
+                let mut chunk = match transaction.get_chunk(cx, cz, dim, RegionKind::Chunks)? {
+                    Some(chunk) => chunk.read(|nbt| ChunkBuilder::from_nbt(nbt, dim))?,
+                    None => ChunkBuilder::new(cx, cz, dim),
+                };
+                let mut replaced = [false; 256];
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let (bx, bz) = (cx * 16 + x, cz * 16 + z);
+                        if bx < x0 || bx > x1 || bz < z0 || bz > z1 {
+                            continue;
+                        }
+                        // pixel centers sit half a pixel in
+                        let value = heightmap.sample(
+                            ((bx - x0) as f64 + 0.5) / options.scale - 0.5,
+                            ((bz - z0) as f64 + 0.5) / options.scale - 0.5,
+                        );
+                        let top = low + (value * (high - low) as f64).round() as i32;
+                        write_column(&mut chunk, x, z, top, options);
+                        replaced[(z * 16 + x) as usize] = true;
+                        report.columns += 1;
+                    }
+                }
+                let mut nbt = chunk.to_nbt();
+                // block entities and ticks of the replaced blocks would point at the wrong block
+                for key in ["block_entities", "block_ticks", "fluid_ticks"] {
+                    if let Some(NbtValue::List(NbtList::CompoundList(list))) = nbt.get_mut(key) {
+                        list.retain(|e| {
+                            let int = |key| e.get(key).and_then(NbtValue::as_i64).unwrap_or(0);
+                            !replaced[((int("z") & 15) * 16 + (int("x") & 15)) as usize]
+                        });
+                    }
+                }
+                transaction
+                    .get_region(cx >> 5, cz >> 5, dim, RegionKind::Chunks)?
+                    .set_chunk(cx & 31, cz & 31, &Chunk::new(nbt))?;
+                report.chunks += 1;
+            }
+            transaction.finish_region(rx, rz, dim, RegionKind::Chunks)?;
+        }
+        let session = transaction.session().to_string();
+        report.session = (transaction.commit()? > 0).then_some(session);
+        Ok(report)
+    }
+}
@@ -1,8 +1,65 @@
-use std::collections::HashMap;
+use std::{fs::File, path::PathBuf};
 
-use crate::{Map, NbtValue};
+use crate::{
+    biome_color,
+    block::{self, PalettedContainer},
+    block_color, compact_region, parse_layers,
+    region::{
+        RawChunk, RegionFile, COMPRESSION_CUSTOM, COMPRESSION_LZ4, COMPRESSION_NONE,
+        COMPRESSION_ZLIB, SECTOR_SIZE,
+    },
+    validate_region, Area, BlockPattern, BlockState, BoundingBox, Chunk, ChunkBuilder, ChunkMetric,
+    CompactOptions, Dimension, Error, GameMode, Generator, Heightmap, Image, Issue, ItemHolder,
+    ItemPredicate, Map, MapArtOptions, MapItem, Mirror, NbtFile, NbtList, NbtValue, PasteOptions,
+    RegionKind, RenderOptions, Repair, Rotation, ScanProgress, Schematic, Slice, Structure,
+    TerrainOptions, World, WorldBuilder, DATA_VERSION,
+};
 use pretty_assertions::assert_eq;
 
+fn compound<const N: usize>(entries: [(&str, NbtValue); N]) -> NbtValue {
+    NbtValue::Compound(Map::from(entries.map(|(k, v)| (k.to_string(), v))))
+}
+
+fn compounds<const N: usize>(entries: [NbtValue; N]) -> NbtValue {
+    NbtValue::List(NbtList::CompoundList(
+        entries
+            .into_iter()
+            .filter_map(|e| e.as_compound().cloned())
+            .collect(),
+    ))
+}
+
+fn string(s: &str) -> NbtValue {
+    NbtValue::String(s.to_string())
+}
+
+/// empty directory in the system temp dir, cleared on every run
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("minecraft_world_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// world with a level.dat and the given chunks written to `dir/r.x.z.mca`
+fn test_world(name: &str, chunks: &[(&str, i32, i32, NbtValue)]) -> PathBuf {
+    let path = temp_dir(name);
+    NbtFile {
+        name: String::new(),
+        nbt: compound([("Data", compound([("LevelName", string(name))]))]),
+    }
+    .to_compressed_writer(File::create(path.join("level.dat")).unwrap())
+    .unwrap();
+    for (dir, x, z, nbt) in chunks {
+        let region_path = path.join(dir).join(format!("r.{}.{}.mca", x >> 5, z >> 5));
+        std::fs::create_dir_all(region_path.parent().unwrap()).unwrap();
+        let mut region = RegionFile::open(&region_path).unwrap_or_default();
+        region.write_chunk(*x, *z, nbt, 0).unwrap();
+        region.save(&region_path).unwrap();
+    }
+    path
+}
+
 #[test]
 fn test_nbt1() {
     assert_eq!(
@@ -30,3 +87,2158 @@ fn test_compressed() {
         }
     )
 }
+
+#[test]
+fn test_region_round_trip() {
+    let path = temp_dir("region_round_trip").join("r.-1.0.mca");
+    let nbt = compound([("xPos", NbtValue::Int(-1)), ("zPos", NbtValue::Int(3))]);
+    let mut region = RegionFile::new();
+    region.write_chunk(-1, 3, &nbt, 42).unwrap();
+    region.save(&path).unwrap();
+
+    let region = RegionFile::open(&path).unwrap();
+    assert_eq!(region.positions().collect::<Vec<_>>(), vec![(31, 3)]);
+    assert_eq!(region.chunk(31, 3).unwrap().timestamp, 42);
+    assert_eq!(region.read_chunk(31, 3).unwrap(), Some(nbt));
+}
+
+#[test]
+fn test_find_items() {
+    let diamond = compound([
+        ("id", string("minecraft:diamond")),
+        ("count", NbtValue::Int(3)),
+        (
+            "components",
+            compound([(
+                "minecraft:custom_data",
+                compound([("dupe", compound([("batch", NbtValue::Int(1))]))]),
+            )]),
+        ),
+    ]);
+    let chest = compound([
+        ("id", string("minecraft:chest")),
+        ("x", NbtValue::Int(16)),
+        ("y", NbtValue::Int(64)),
+        ("z", NbtValue::Int(32)),
+        (
+            "Items",
+            compounds([compound([
+                ("Slot", NbtValue::Byte(4)),
+                ("id", string("minecraft:shulker_box")),
+                (
+                    "components",
+                    compound([(
+                        "minecraft:container",
+                        compounds([compound([("slot", NbtValue::Int(0)), ("item", diamond)])]),
+                    )]),
+                ),
+            ])]),
+        ),
+    ]);
+    let frame = compound([
+        ("id", string("minecraft:item_frame")),
+        (
+            "Pos",
+            NbtValue::List(NbtList::DoubleList(vec![20.5, 70.0, 40.5])),
+        ),
+        ("Item", compound([("id", string("minecraft:diamond"))])),
+    ]);
+    let path = test_world(
+        "find_items",
+        &[
+            (
+                "region",
+                1,
+                2,
+                compound([("block_entities", compounds([chest]))]),
+            ),
+            (
+                "entities",
+                1,
+                2,
+                compound([("Entities", compounds([frame]))]),
+            ),
+        ],
+    );
+    std::fs::create_dir_all(path.join("playerdata")).unwrap();
+    NbtFile {
+        name: String::new(),
+        nbt: compound([(
+            "EnderItems",
+            compounds([compound([
+                ("Slot", NbtValue::Byte(0)),
+                ("id", string("minecraft:diamond_sword")),
+                (
+                    "components",
+                    compound([(
+                        "minecraft:enchantments",
+                        compound([("minecraft:sharpness", NbtValue::Int(5))]),
+                    )]),
+                ),
+            ])]),
+        )]),
+    }
+    .to_compressed_writer(File::create(path.join("playerdata/abc.dat")).unwrap())
+    .unwrap();
+
+    let world = World::open(&path).unwrap();
+    let found = world
+        .find_items(&ItemPredicate::Id("diamond".to_string()))
+        .unwrap();
+    assert_eq!(
+        found
+            .iter()
+            .map(|e| (e.holder.clone(), e.path.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                ItemHolder::BlockEntity {
+                    dimension: Dimension::Overworld,
+                    id: "minecraft:chest".to_string(),
+                    pos: [16, 64, 32],
+                },
+                vec!["Items[4]".to_string(), "minecraft:container[0]".to_string()],
+            ),
+            (
+                ItemHolder::Entity {
+                    dimension: Dimension::Overworld,
+                    id: "minecraft:item_frame".to_string(),
+                    pos: [20.5, 70.0, 40.5],
+                },
+                vec!["Item".to_string()],
+            ),
+        ]
+    );
+    let found = world
+        .find_items(&ItemPredicate::CustomData("dupe.batch".to_string()))
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    let found = world
+        .find_items(&ItemPredicate::Enchantment("sharpness".to_string()))
+        .unwrap();
+    assert_eq!(
+        found[0].holder,
+        ItemHolder::Player {
+            uuid: "abc".to_string(),
+            pos: None
+        }
+    );
+}
+
+#[test]
+fn test_array_round_trip() {
+    let nbt = compound([
+        ("longs", NbtValue::LongArray(vec![1, -2, i64::MAX])),
+        (
+            "ints",
+            NbtValue::List(NbtList::IntArrayList(vec![vec![1, 2, 3], vec![]])),
+        ),
+        (
+            "longs_list",
+            NbtValue::List(NbtList::LongArrayList(vec![vec![4], vec![5, 6]])),
+        ),
+    ]);
+    let mut buf = vec![];
+    nbt.to_writer("root", &mut buf).unwrap();
+    assert_eq!(
+        NbtValue::from_reader(buf.as_slice()).unwrap(),
+        ("root".to_string(), nbt)
+    );
+}
+
+/// chunk with one section at y 0, the bottom layer is `bottom` and the rest `rest`
+fn layered_chunk(x: i32, z: i32, bottom: &BlockState, rest: &BlockState) -> NbtValue {
+    let mut blocks = PalettedContainer::filled(rest.clone(), block::SECTION_BLOCKS);
+    for i in 0..256 {
+        blocks.set(i, bottom.clone());
+    }
+    compound([
+        ("xPos", NbtValue::Int(x)),
+        ("zPos", NbtValue::Int(z)),
+        (
+            "sections",
+            compounds([compound([
+                ("Y", NbtValue::Byte(0)),
+                ("block_states", block::blocks_to_nbt(&blocks)),
+            ])]),
+        ),
+    ])
+}
+
+fn section_of(world: &World, x: i32, z: i32) -> PalettedContainer<BlockState> {
+    let region = world
+        .get_region(x >> 5, z >> 5, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    let chunk = region.get_chunk(x & 31, z & 31).unwrap().unwrap();
+    chunk.read(|nbt| {
+        block::section_blocks(&nbt.get("sections").unwrap().as_list().unwrap().compounds()[0])
+            .unwrap()
+            .unwrap()
+    })
+}
+
+#[test]
+fn test_replace_blocks() {
+    let ore = BlockState::new("mod:ore").with("lit", "true");
+    let stone = BlockState::new("stone");
+    let air = BlockState::new("air");
+    let path = test_world(
+        "replace_blocks",
+        &[
+            ("region", 0, 0, layered_chunk(0, 0, &ore, &air)),
+            ("region", 1, 0, layered_chunk(1, 0, &ore, &air)),
+            (
+                "region",
+                2,
+                0,
+                compound([("Level", layered_chunk(2, 0, &ore, &air))]),
+            ),
+        ],
+    );
+    let world = World::open(&path).unwrap();
+
+    let report = world
+        .replace_blocks(
+            Dimension::Overworld,
+            &Area::Chunks(vec![(0, 0)]),
+            &"mod:ore".parse().unwrap(),
+            &stone,
+        )
+        .unwrap();
+    assert_eq!((report.chunks, report.sections, report.blocks), (1, 1, 256));
+    let section = section_of(&world, 0, 0);
+    assert_eq!(section.palette, vec![air.clone(), stone.clone()]);
+    assert_eq!(section.get(255), &stone);
+    assert_eq!(section.get(256), &air);
+    assert_eq!(section_of(&world, 1, 0).get(0), &ore);
+
+    let report = world
+        .replace_blocks(
+            Dimension::Overworld,
+            &Area::Box(BoundingBox::new([16, 0, 0], [17, 0, 15])),
+            &BlockPattern(ore.clone()),
+            &stone,
+        )
+        .unwrap();
+    assert_eq!(report.blocks, 32);
+    let section = section_of(&world, 1, 0);
+    assert_eq!(section.get(block::section_index(17, 0, 15)), &stone);
+    assert_eq!(section.get(block::section_index(18, 0, 0)), &ore);
+
+    let report = world
+        .replace_blocks(
+            Dimension::Overworld,
+            &Area::Dimension,
+            &"mod:ore[lit=false]".parse().unwrap(),
+            &stone,
+        )
+        .unwrap();
+    assert_eq!((report.blocks, report.skipped), (0, 1));
+}
+
+#[test]
+fn test_prune_inhabited() {
+    let chunk = |x, z, time| {
+        compound([
+            ("xPos", NbtValue::Int(x)),
+            ("zPos", NbtValue::Int(z)),
+            ("InhabitedTime", NbtValue::Long(time)),
+        ])
+    };
+    let path = test_world(
+        "prune_inhabited",
+        &[
+            ("region", 0, 0, chunk(0, 0, 5000)),
+            ("region", 1, 0, chunk(1, 0, 0)),
+            ("region", 5, 0, chunk(5, 0, 10)),
+            ("region", 40, 0, chunk(40, 0, 0)),
+            ("entities", 5, 0, compound([("Entities", compounds([]))])),
+            ("poi", 40, 0, compound([("Sections", compound([]))])),
+        ],
+    );
+    // chunk 5,0 is too large for the region file and goes to c.5.0.mcc
+    let region_path = path.join("region").join("r.0.0.mca");
+    let mut region = RegionFile::open(&region_path).unwrap();
+    let mut large = chunk(5, 0, 10);
+    let root = large.as_compound_mut().unwrap();
+    root.insert("Filler".to_string(), NbtValue::ByteArray(vec![0; 1 << 20]));
+    let raw = RawChunk::encode(&large, COMPRESSION_NONE, 0).unwrap();
+    region.set_chunk(5, 0, raw);
+    region.save(&region_path).unwrap();
+    let external = path.join("region").join("c.5.0.mcc");
+    let external_size = std::fs::metadata(&external).unwrap().len();
+    let world = World::open(&path).unwrap();
+
+    let report = world
+        .prune_inhabited(Dimension::Overworld, 100, 1, true)
+        .unwrap();
+    assert_eq!((report.chunks, report.regions_deleted), (2, 3));
+    // three whole files, the sector of chunk 5,0 in the region that stays and its .mcc file
+    let size = |x, kind| {
+        std::fs::metadata(world.region_path(x, 0, Dimension::Overworld, kind))
+            .unwrap()
+            .len()
+    };
+    let deleted =
+        size(1, RegionKind::Chunks) + size(0, RegionKind::Entities) + size(1, RegionKind::Poi);
+    assert_eq!(
+        report.bytes_saved,
+        deleted + SECTOR_SIZE as u64 + external_size
+    );
+    assert!(world
+        .region_path(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .is_file());
+
+    world
+        .prune_inhabited(Dimension::Overworld, 100, 1, false)
+        .unwrap();
+    let region = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    assert_eq!(region.chunk_positions(), vec![(0, 0), (1, 0)]);
+    assert!(!external.exists());
+    assert_eq!(
+        world
+            .region_positions(Dimension::Overworld, RegionKind::Chunks)
+            .unwrap(),
+        vec![(0, 0)]
+    );
+    assert!(world
+        .region_positions(Dimension::Overworld, RegionKind::Entities)
+        .unwrap()
+        .is_empty());
+    assert!(world
+        .region_positions(Dimension::Overworld, RegionKind::Poi)
+        .unwrap()
+        .is_empty());
+    // the deleted chunks don't move into an undo file
+    assert!(world.undo_sessions().unwrap().is_empty());
+}
+
+#[test]
+fn test_trim_world_border() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let path = test_world(
+        "trim_world_border",
+        &[
+            ("region", 1, 0, chunk(1, 0)),
+            ("region", 2, 0, chunk(2, 0)),
+            ("region", -3, 0, chunk(-3, 0)),
+            ("entities", 0, 5, chunk(0, 5)),
+            ("DIM-1/region", 1, 0, chunk(1, 0)),
+            ("DIM-1/region", 2, 0, chunk(2, 0)),
+        ],
+    );
+    NbtFile {
+        name: String::new(),
+        nbt: compound([(
+            "Data",
+            compound([
+                ("BorderCenterX", NbtValue::Double(0.0)),
+                ("BorderCenterZ", NbtValue::Double(0.0)),
+                ("BorderSize", NbtValue::Double(64.0)),
+            ]),
+        )]),
+    }
+    .to_compressed_writer(File::create(path.join("level.dat")).unwrap())
+    .unwrap();
+    let world = World::open(&path).unwrap();
+
+    let report = world
+        .trim_world_border(Dimension::Overworld, false)
+        .unwrap();
+    assert_eq!((report.chunks, report.regions_deleted), (2, 2));
+    assert_eq!(
+        world
+            .chunk_positions(Dimension::Overworld, RegionKind::Chunks)
+            .unwrap(),
+        vec![(1, 0)]
+    );
+    assert!(world
+        .chunk_positions(Dimension::Overworld, RegionKind::Entities)
+        .unwrap()
+        .is_empty());
+
+    // the nether border has the same size
+    let report = world.trim_world_border(Dimension::Nether, false).unwrap();
+    assert_eq!(report.chunks, 1);
+    assert_eq!(
+        world
+            .chunk_positions(Dimension::Nether, RegionKind::Chunks)
+            .unwrap(),
+        vec![(1, 0)]
+    );
+}
+
+#[test]
+fn test_validate_region() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let mut region = RegionFile::new();
+    region.write_chunk(0, 0, &chunk(0, 0), 0).unwrap();
+    region.write_chunk(1, 0, &chunk(2, 0), 0).unwrap();
+    region.set_chunk(
+        3,
+        0,
+        RawChunk {
+            compression: COMPRESSION_ZLIB,
+            timestamp: 0,
+            data: vec![1, 2, 3],
+        },
+    );
+    // an lz4 block stream as the game writes it and a chunk in a plugin's compression
+    let raw = RawChunk::encode(&chunk(6, 0), COMPRESSION_NONE, 0)
+        .unwrap()
+        .data;
+    let compressed = lz4_flex::block::compress(&raw);
+    let mut lz4 = b"LZ4Block\x2a".to_vec();
+    lz4.extend((compressed.len() as u32).to_le_bytes());
+    lz4.extend((raw.len() as u32).to_le_bytes());
+    lz4.extend([0; 4]);
+    lz4.extend(compressed);
+    lz4.extend(b"LZ4Block\x1a");
+    lz4.extend([0; 12]);
+    region.set_chunk(
+        6,
+        0,
+        RawChunk {
+            compression: COMPRESSION_LZ4,
+            timestamp: 0,
+            data: lz4,
+        },
+    );
+    region.set_chunk(
+        7,
+        0,
+        RawChunk {
+            compression: COMPRESSION_CUSTOM,
+            timestamp: 0,
+            data: b"\x00\x04zstd".to_vec(),
+        },
+    );
+    assert_eq!(region.read_chunk(6, 0).unwrap(), Some(chunk(6, 0)));
+    let (mut bytes, _) = region.to_bytes();
+    // slot 4 points past the end of the file, slot 5 shares the sectors of slot 0
+    bytes[16..20].copy_from_slice(&(1000u32 << 8 | 1).to_be_bytes());
+    let slot_0 = bytes[0..4].to_vec();
+    bytes[20..24].copy_from_slice(&slot_0);
+    let path = temp_dir("validate_region").join("r.0.0.mca");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let report = validate_region(&path, false).unwrap();
+    let mut issues = report
+        .issues
+        .iter()
+        .map(|e| (e.chunk, std::mem::discriminant(&e.issue)))
+        .collect::<Vec<_>>();
+    issues.sort_by_key(|e| e.0);
+    assert_eq!(
+        issues.iter().map(|e| e.0).collect::<Vec<_>>(),
+        vec![(1, 0), (3, 0), (4, 0), (5, 0), (5, 0), (7, 0)]
+    );
+    assert_eq!(
+        report.issues.last().unwrap().issue,
+        Issue::UnsupportedCompression {
+            compression: COMPRESSION_CUSTOM
+        }
+    );
+    assert!(report.repairs.is_empty());
+
+    let report = validate_region(&path, true).unwrap();
+    assert!(report
+        .repairs
+        .contains(&((1, 0), Repair::Relocated { to: (2, 0) })));
+    assert!(report.repairs.contains(&((3, 0), Repair::Dropped)));
+    assert!(path.with_extension("mca.bak").is_file());
+    let region = RegionFile::open(&path).unwrap();
+    assert_eq!(
+        region.positions().collect::<Vec<_>>(),
+        vec![(0, 0), (2, 0), (6, 0), (7, 0)]
+    );
+    assert_eq!(region.read_chunk(2, 0).unwrap(), Some(chunk(2, 0)));
+    assert_eq!(region.read_chunk(6, 0).unwrap(), Some(chunk(6, 0)));
+    assert!(!report.repairs.iter().any(|e| e.0 == (7, 0)));
+    assert_eq!(validate_region(&path, false).unwrap().issues.len(), 1);
+}
+
+#[test]
+fn test_compact_region() {
+    let big = compound([(
+        "data",
+        NbtValue::LongArray((0..4000).map(|e| e * 7919).collect()),
+    )]);
+    let small = compound([("xPos", NbtValue::Int(1))]);
+    let mut region = RegionFile::new();
+    region.write_chunk(0, 0, &big, 0).unwrap();
+    region.write_chunk(1, 0, &small, 0).unwrap();
+    let (mut bytes, _) = region.to_bytes();
+    // leave a hole of unused sectors between the header and the first chunk
+    bytes.splice(SECTOR_SIZE * 2..SECTOR_SIZE * 2, vec![0; SECTOR_SIZE * 3]);
+    for slot in [0, 1] {
+        let location = u32::from_be_bytes(bytes[slot * 4..slot * 4 + 4].try_into().unwrap());
+        bytes[slot * 4..slot * 4 + 4].copy_from_slice(&(location + (3 << 8)).to_be_bytes());
+    }
+    let path = temp_dir("compact_region").join("r.0.0.mca");
+    std::fs::write(&path, &bytes).unwrap();
+    assert_eq!(
+        RegionFile::open(&path).unwrap().read_chunk(0, 0).unwrap(),
+        Some(big.clone())
+    );
+
+    let report = compact_region(&path, CompactOptions::default()).unwrap();
+    assert_eq!(report.bytes_reclaimed, SECTOR_SIZE as u64 * 3);
+    assert_eq!(report.chunks_recompressed, 0);
+
+    let report = compact_region(
+        &path,
+        CompactOptions {
+            compression: Some(COMPRESSION_NONE),
+            level: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(report.chunks_recompressed, 2);
+    let region = RegionFile::open(&path).unwrap();
+    assert_eq!(region.chunk(0, 0).unwrap().compression, COMPRESSION_NONE);
+    assert_eq!(region.read_chunk(0, 0).unwrap(), Some(big));
+    assert_eq!(region.read_chunk(1, 0).unwrap(), Some(small.clone()));
+
+    // only a new level keeps each chunk's compression, lz4 can't be written and becomes zlib
+    let mut region = RegionFile::open(&path).unwrap();
+    let raw = RawChunk::encode(&small, COMPRESSION_NONE, 0).unwrap().data;
+    let compressed = lz4_flex::block::compress(&raw);
+    let mut lz4 = b"LZ4Block\x2a".to_vec();
+    lz4.extend((compressed.len() as u32).to_le_bytes());
+    lz4.extend((raw.len() as u32).to_le_bytes());
+    lz4.extend([0; 4]);
+    lz4.extend(compressed);
+    region.set_chunk(
+        1,
+        0,
+        RawChunk {
+            compression: COMPRESSION_LZ4,
+            timestamp: 0,
+            data: lz4,
+        },
+    );
+    region.save(&path).unwrap();
+    let report = compact_region(
+        &path,
+        CompactOptions {
+            compression: None,
+            level: Some(9),
+        },
+    )
+    .unwrap();
+    assert_eq!(report.chunks_recompressed, 2);
+    let region = RegionFile::open(&path).unwrap();
+    assert_eq!(region.chunk(1, 0).unwrap().compression, COMPRESSION_ZLIB);
+    assert_eq!(region.read_chunk(1, 0).unwrap(), Some(small));
+    let lz4 = CompactOptions {
+        compression: Some(COMPRESSION_LZ4),
+        level: None,
+    };
+    assert!(compact_region(&path, lz4).is_err());
+
+    // a leftover external chunk of an inlined chunk goes, one of another region stays
+    let dir = path.parent().unwrap();
+    std::fs::write(dir.join("c.1.0.mcc"), [1, 2, 3]).unwrap();
+    std::fs::write(dir.join("c.40.0.mcc"), [1, 2, 3]).unwrap();
+    compact_region(&path, CompactOptions::default()).unwrap();
+    assert!(!dir.join("c.1.0.mcc").exists());
+    assert!(dir.join("c.40.0.mcc").exists());
+    assert!(!dir.join("r.0.0.mca.staged").exists());
+
+    let empty = dir.join("r.1.0.mca");
+    std::fs::write(&empty, []).unwrap();
+    let report = compact_region(&empty, CompactOptions::default()).unwrap();
+    assert_eq!((report.bytes_before, report.bytes_after), (0, 0));
+    assert_eq!(std::fs::metadata(&empty).unwrap().len(), 0);
+}
+
+#[test]
+fn test_par_chunks() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let path = test_world(
+        "par_chunks",
+        &[
+            ("region", 0, 0, chunk(0, 0)),
+            ("region", 3, 1, chunk(3, 1)),
+            ("region", 40, 0, chunk(40, 0)),
+            ("region", -1, -40, chunk(-1, -40)),
+        ],
+    );
+    let world = World::open(&path).unwrap();
+    let region_path = world.region_path(0, 0, Dimension::Overworld, RegionKind::Chunks);
+    let mut region = RegionFile::open(&region_path).unwrap();
+    region.set_chunk(
+        2,
+        2,
+        RawChunk {
+            compression: COMPRESSION_ZLIB,
+            timestamp: 0,
+            data: vec![1, 2, 3],
+        },
+    );
+    region.save(&region_path).unwrap();
+    // a header entry pointing past the end only loses that chunk
+    let region_path = world.region_path(1, 0, Dimension::Overworld, RegionKind::Chunks);
+    let mut bytes = std::fs::read(&region_path).unwrap();
+    bytes[9 * 4..9 * 4 + 4].copy_from_slice(&(1000u32 << 8 | 1).to_be_bytes());
+    std::fs::write(&region_path, bytes).unwrap();
+
+    let progress = std::sync::Mutex::new(vec![]);
+    let on_progress = |e: ScanProgress| progress.lock().unwrap().push(e);
+    let scan = world
+        .par_chunks(Dimension::Overworld)
+        .threads(2)
+        .progress(&on_progress)
+        .run(|pos, nbt| (pos, nbt.get("xPos").and_then(NbtValue::as_i64)))
+        .unwrap();
+    assert!(!scan.cancelled);
+    assert_eq!(
+        scan.results,
+        vec![
+            ((-1, -40), ((-1, -40), Some(-1))),
+            ((0, 0), ((0, 0), Some(0))),
+            ((3, 1), ((3, 1), Some(3))),
+            ((40, 0), ((40, 0), Some(40))),
+        ]
+    );
+    assert_eq!(
+        scan.errors
+            .iter()
+            .map(|e| (e.region, e.chunk))
+            .collect::<Vec<_>>(),
+        vec![((0, 0), Some((2, 2))), ((1, 0), Some((41, 0)))]
+    );
+    let progress = progress.into_inner().unwrap();
+    assert_eq!(progress.len(), 3);
+    assert!(progress
+        .iter()
+        .any(|e| (e.regions_done, e.regions_total) == (3, 3)));
+
+    let cancel = std::sync::atomic::AtomicBool::new(true);
+    let scan = world
+        .par_chunks(Dimension::Overworld)
+        .cancel(&cancel)
+        .run(|pos, _| pos)
+        .unwrap();
+    assert!(scan.cancelled);
+    assert!(scan.results.is_empty());
+}
+
+#[test]
+fn test_region_cache() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let path = test_world(
+        "region_cache",
+        &[
+            ("region", 0, 0, chunk(0, 0)),
+            ("region", 40, 0, chunk(40, 0)),
+            ("region", 80, 0, chunk(80, 0)),
+        ],
+    );
+    let world = World::open(&path).unwrap();
+    let overworld = |x, z| {
+        world
+            .get_region(x, z, Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+    };
+    let read = |x, z| {
+        let path = world.region_path(x >> 5, z >> 5, Dimension::Overworld, RegionKind::Chunks);
+        RegionFile::open(&path)
+            .unwrap()
+            .read_chunk(x & 31, z & 31)
+            .unwrap()
+            .unwrap()
+    };
+
+    // every handle to a chunk shares the same nbt
+    let a = overworld(0, 0).get_chunk(0, 0).unwrap().unwrap();
+    a.write(|nbt| {
+        nbt.as_compound_mut()
+            .unwrap()
+            .insert("Status".to_string(), string("minecraft:full"))
+    });
+    let b = overworld(0, 0).get_chunk(0, 0).unwrap().unwrap();
+    assert_eq!(
+        b.read(|nbt| nbt.get("Status").cloned()),
+        Some(string("minecraft:full"))
+    );
+    assert!(overworld(0, 0).is_dirty());
+    assert!(!overworld(1, 0).is_dirty());
+    assert_eq!(read(0, 0).get("Status"), None);
+    drop((a, b));
+
+    assert_eq!(world.save().unwrap(), 1);
+    assert_eq!(world.save().unwrap(), 0);
+    assert_eq!(read(0, 0).get("Status"), Some(&string("minecraft:full")));
+    // written next to the region and renamed over it
+    let region_path = world.region_path(0, 0, Dimension::Overworld, RegionKind::Chunks);
+    assert!(!region_path.with_extension("mca.staged").exists());
+
+    // evicting a changed region saves it
+    world.set_cache_size(1, 1).unwrap();
+    overworld(1, 0).remove_chunk(8, 0);
+    overworld(2, 0).get_chunk(16, 0).unwrap();
+    assert!(!world
+        .region_path(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .is_file());
+
+    // a held chunk keeps its region open, so writes after the region would be evicted count
+    let held = overworld(0, 0).get_chunk(0, 0).unwrap().unwrap();
+    overworld(2, 0);
+    held.write(|nbt| {
+        nbt.as_compound_mut()
+            .unwrap()
+            .insert("Status".to_string(), string("minecraft:spawn"))
+    });
+    drop(held);
+    world.save().unwrap();
+    assert_eq!(read(0, 0).get("Status"), Some(&string("minecraft:spawn")));
+
+    // a new region is written on save
+    let region = overworld(-1, 0);
+    region.set_chunk(31, 0, &Chunk::new(chunk(-1, 0))).unwrap();
+    drop(region);
+    assert_eq!(world.save().unwrap(), 1);
+    assert_eq!(read(-1, 0), chunk(-1, 0));
+}
+
+#[test]
+fn test_concurrent_access() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let path = test_world(
+        "concurrent_access",
+        &[
+            ("region", 0, 0, chunk(0, 0)),
+            ("region", 40, 0, chunk(40, 0)),
+        ],
+    );
+    let world = World::open(&path).unwrap();
+    let get = |x: i32, z: i32| {
+        world
+            .get_region(x >> 5, z >> 5, Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+            .get_chunk(x & 31, z & 31)
+            .unwrap()
+            .unwrap()
+    };
+
+    // both readers have to be inside the chunk at once to get past the barrier
+    let barrier = std::sync::Barrier::new(2);
+    std::thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| get(0, 0).read(|_| barrier.wait()));
+        }
+    });
+
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..100 {
+                get(40, 0).write(|nbt| {
+                    nbt.as_compound_mut()
+                        .unwrap()
+                        .insert("InhabitedTime".to_string(), NbtValue::Long(i))
+                });
+            }
+            stop.store(true, std::sync::atomic::Ordering::SeqCst);
+        });
+        s.spawn(|| {
+            while !stop.load(std::sync::atomic::Ordering::SeqCst) {
+                world.save().unwrap();
+            }
+        });
+        s.spawn(|| {
+            while !stop.load(std::sync::atomic::Ordering::SeqCst) {
+                assert_eq!(
+                    get(0, 0).read(|nbt| nbt.get("xPos").cloned()),
+                    Some(NbtValue::Int(0))
+                );
+            }
+        });
+    });
+    world.save().unwrap();
+
+    let path = world.region_path(1, 0, Dimension::Overworld, RegionKind::Chunks);
+    let saved = RegionFile::open(&path)
+        .unwrap()
+        .read_chunk(8, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.get("InhabitedTime"), Some(&NbtValue::Long(99)));
+    assert!(!world
+        .get_region(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .is_dirty());
+}
+
+#[test]
+fn test_session_lock() {
+    let chunk = compound([("xPos", NbtValue::Int(0)), ("zPos", NbtValue::Int(0))]);
+    let path = test_world("session_lock", &[("region", 0, 0, chunk.clone())]);
+    let world = World::open(&path).unwrap();
+    assert!(path.join("session.lock").is_file());
+    assert!(matches!(World::open(&path), Err(Error::WorldInUse(_))));
+
+    let read_only = World::open_read_only(&path).unwrap();
+    let region = read_only
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    region.remove_chunk(0, 0);
+    assert_eq!(region.save(), Err(Error::ReadOnly));
+    assert_eq!(read_only.save(), Err(Error::ReadOnly));
+    assert_eq!(
+        read_only
+            .prune_inhabited(Dimension::Overworld, 1, 0, false)
+            .map(|_| ()),
+        Err(Error::ReadOnly)
+    );
+    assert!(read_only
+        .prune_inhabited(Dimension::Overworld, 1, 0, true)
+        .is_ok());
+
+    drop(world);
+    let world = World::open(&path).unwrap();
+    assert!(!world.is_read_only());
+}
+
+#[test]
+fn test_transaction() {
+    let chunk = |x, z| compound([("xPos", NbtValue::Int(x)), ("zPos", NbtValue::Int(z))]);
+    let path = test_world(
+        "transaction",
+        &[
+            ("region", 0, 0, chunk(0, 0)),
+            ("region", 40, 0, chunk(40, 0)),
+        ],
+    );
+    let region_path = |x| path.join("region").join(format!("r.{x}.0.mca"));
+    let read = |x: i32| {
+        RegionFile::open(&region_path(x >> 5))
+            .ok()
+            .and_then(|e| e.read_chunk(x & 31, 0).unwrap())
+    };
+    let world = World::open(&path).unwrap();
+    let mark = |chunk: &Chunk| {
+        chunk.write(|nbt| {
+            nbt.as_compound_mut()
+                .unwrap()
+                .insert("Status".to_string(), string("minecraft:full"))
+        })
+    };
+
+    let transaction = world.transaction().unwrap();
+    mark(
+        &transaction
+            .get_chunk(0, 0, Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+            .unwrap(),
+    );
+    transaction
+        .get_region(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .remove_chunk(8, 0);
+    assert!(transaction
+        .get_region(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .save()
+        .is_err());
+    // a finished region goes to a staged file and can't be changed again
+    let staged = || {
+        std::fs::read_dir(path.join("region"))
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .to_string_lossy()
+                    .ends_with(".staged")
+            })
+            .count()
+    };
+    transaction
+        .finish_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    assert_eq!(staged(), 1);
+    assert!(transaction
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .is_err());
+    transaction.rollback();
+    assert_eq!(staged(), 0);
+    assert_eq!(read(0), Some(chunk(0, 0)));
+    assert_eq!(read(40), Some(chunk(40, 0)));
+
+    let transaction = world.transaction().unwrap();
+    mark(
+        &transaction
+            .get_chunk(0, 0, Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+            .unwrap(),
+    );
+    transaction
+        .get_region(1, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .remove_chunk(8, 0);
+    transaction
+        .finish_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    assert_eq!(transaction.commit().unwrap(), 2);
+    assert_eq!(staged(), 0);
+    assert_eq!(
+        read(0).unwrap().get("Status"),
+        Some(&string("minecraft:full"))
+    );
+    assert!(!region_path(1).exists());
+    assert!(!path.join(crate::JOURNAL).exists());
+    assert_eq!(std::fs::read_dir(path.join("region")).unwrap().count(), 1);
+    // the world sees the committed region
+    let region = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    assert_eq!(
+        region
+            .get_chunk(0, 0)
+            .unwrap()
+            .unwrap()
+            .read(|nbt| nbt.get("Status").cloned()),
+        Some(string("minecraft:full"))
+    );
+
+    // handles opened before a commit can't save over it
+    let transaction = world.transaction().unwrap();
+    transaction
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .set_chunk(1, 0, &Chunk::new(chunk(1, 0)))
+        .unwrap();
+    transaction.commit().unwrap();
+    region.remove_chunk(0, 0);
+    assert!(region.save().is_err());
+    assert_eq!(read(1), Some(chunk(1, 0)));
+
+    // changes made through the world after the transaction read a region fail the commit
+    let transaction = world.transaction().unwrap();
+    transaction
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .set_chunk(2, 0, &Chunk::new(chunk(2, 0)))
+        .unwrap();
+    let region = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    region.set_chunk(3, 0, &Chunk::new(chunk(3, 0))).unwrap();
+    assert!(transaction.commit().is_err());
+    assert_eq!(read(2), None);
+    region.save().unwrap();
+    assert_eq!(read(3), Some(chunk(3, 0)));
+    drop((region, world));
+
+    // a crash after the journal was committed is finished on open
+    let mut staged = RegionFile::new();
+    staged.write_chunk(40, 0, &chunk(40, 0), 0).unwrap();
+    staged.save(&path.join("region/r.1.0.mca.staged")).unwrap();
+    std::fs::write(
+        path.join(crate::JOURNAL),
+        r#"{"committed":true,"renames":[["region/r.1.0.mca.staged","region/r.1.0.mca"]],"deletes":["region/r.0.0.mca"]}"#,
+    )
+    .unwrap();
+    let world = World::open(&path).unwrap();
+    assert_eq!(read(40), Some(chunk(40, 0)));
+    assert!(!region_path(0).exists());
+    assert!(!path.join(crate::JOURNAL).exists());
+    drop(world);
+
+    // before that it is rolled back
+    std::fs::write(path.join("region/r.2.0.mca.staged"), b"partial").unwrap();
+    std::fs::write(
+        path.join(crate::JOURNAL),
+        r#"{"committed":false,"renames":[["region/r.2.0.mca.staged","region/r.2.0.mca"]],"deletes":["region/r.1.0.mca"]}"#,
+    )
+    .unwrap();
+    World::open(&path).unwrap();
+    assert!(!path.join("region/r.2.0.mca.staged").exists());
+    assert!(!path.join("region/r.2.0.mca").exists());
+    assert_eq!(read(40), Some(chunk(40, 0)));
+}
+
+#[test]
+fn test_undo() {
+    let stone = BlockState::new("stone");
+    let dirt = BlockState::new("dirt");
+    let air = BlockState::new("air");
+    let path = test_world(
+        "undo",
+        &[
+            ("region", 0, 0, layered_chunk(0, 0, &stone, &air)),
+            ("region", 1, 0, layered_chunk(1, 0, &stone, &air)),
+        ],
+    );
+    let world = World::open(&path).unwrap();
+    let replace = |from: &str, to: &BlockState| {
+        world
+            .replace_blocks(
+                Dimension::Overworld,
+                &Area::Dimension,
+                &from.parse().unwrap(),
+                to,
+            )
+            .unwrap()
+    };
+
+    let edit = replace("stone", &dirt);
+    let session = edit.session.unwrap();
+    assert_eq!(world.undo_sessions().unwrap(), vec![session.clone()]);
+    assert_eq!(section_of(&world, 1, 0).get(0), &dirt);
+
+    let report = world.undo(&session).unwrap();
+    assert_eq!(report.chunks, 2);
+    assert_eq!(section_of(&world, 0, 0).get(0), &stone);
+    assert_eq!(section_of(&world, 1, 0).get(0), &stone);
+    // undoing the undo redoes the edit
+    let redo = report.session.unwrap();
+    assert_eq!(world.undo_sessions().unwrap(), vec![redo.clone()]);
+    world.undo(&redo).unwrap();
+    assert_eq!(section_of(&world, 0, 0).get(0), &dirt);
+
+    let edit = replace("dirt", &stone).session.unwrap();
+    let later = replace("stone", &air).session.unwrap();
+    assert!(world.undo(&edit).is_err());
+    assert_eq!(section_of(&world, 0, 0).get(0), &air);
+    world.undo(&later).unwrap();
+    world.undo(&edit).unwrap();
+    assert_eq!(section_of(&world, 0, 0).get(0), &dirt);
+    assert!(world.undo("missing").is_err());
+
+    // ids carry the process id, and an existing record is never replaced
+    let transaction = world.transaction().unwrap();
+    let session = transaction.session().to_string();
+    assert_eq!(
+        session.split('-').nth(1),
+        Some(std::process::id().to_string().as_str())
+    );
+    transaction
+        .get_chunk(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .unwrap()
+        .write(|nbt| nbt.as_compound_mut().unwrap().remove("sections"));
+    let existing = path.join(crate::UNDO_DIR).join(format!("{session}.dat"));
+    std::fs::write(&existing, b"earlier").unwrap();
+    assert!(matches!(
+        transaction.commit(),
+        Err(Error::Custom(e)) if e.ends_with("already exists")
+    ));
+    assert_eq!(std::fs::read(&existing).unwrap(), b"earlier");
+    assert_eq!(section_of(&world, 0, 0).get(0), &dirt);
+}
+
+#[test]
+fn test_copy_chunks() {
+    let terrain = compound([
+        ("xPos", NbtValue::Int(1)),
+        ("zPos", NbtValue::Int(2)),
+        (
+            "block_entities",
+            compounds([compound([
+                ("id", string("minecraft:chest")),
+                ("x", NbtValue::Int(20)),
+                ("y", NbtValue::Int(64)),
+                ("z", NbtValue::Int(40)),
+            ])]),
+        ),
+        (
+            "structures",
+            compound([
+                (
+                    "References",
+                    compound([("minecraft:village", NbtValue::LongArray(vec![1 | 2 << 32]))]),
+                ),
+                (
+                    "starts",
+                    compound([(
+                        "minecraft:village",
+                        compound([
+                            ("ChunkX", NbtValue::Int(1)),
+                            ("ChunkZ", NbtValue::Int(2)),
+                            (
+                                "Children",
+                                compounds([compound([(
+                                    "BB",
+                                    NbtValue::IntArray(vec![16, 60, 32, 31, 70, 47]),
+                                )])]),
+                            ),
+                        ]),
+                    )]),
+                ),
+            ]),
+        ),
+    ]);
+    let entities = compound([
+        ("Position", NbtValue::IntArray(vec![1, 2])),
+        (
+            "Entities",
+            compounds([compound([(
+                "Pos",
+                NbtValue::List(NbtList::DoubleList(vec![20.5, 64.0, 40.5])),
+            )])]),
+        ),
+    ]);
+    let poi = compound([(
+        "Sections",
+        compound([(
+            "4",
+            compound([(
+                "Records",
+                compounds([compound([("pos", NbtValue::IntArray(vec![20, 64, 40]))])]),
+            )]),
+        )]),
+    )]);
+    let source = test_world(
+        "copy_chunks_source",
+        &[
+            ("region", 1, 2, terrain),
+            ("region", 5, 5, compound([("xPos", NbtValue::Int(5))])),
+            ("entities", 1, 2, entities),
+            ("DIM-1/poi", 1, 2, poi),
+        ],
+    );
+    let destination = test_world(
+        "copy_chunks_destination",
+        &[(
+            "entities",
+            5,
+            5,
+            compound([("Position", NbtValue::IntArray(vec![5, 5]))]),
+        )],
+    );
+    let world = World::open(&destination).unwrap();
+
+    let report = world
+        .copy_chunks(
+            &World::open_read_only(&source).unwrap(),
+            Dimension::Overworld,
+            (0, 0),
+            (2, 2),
+            Dimension::Overworld,
+            (32, -3),
+        )
+        .unwrap();
+    assert_eq!(
+        (report.chunks, report.entity_chunks, report.poi_chunks),
+        (1, 1, 0)
+    );
+    assert!(report.session.is_some());
+
+    let chunk = |kind| {
+        world
+            .get_region(1, -1, Dimension::Overworld, kind)
+            .unwrap()
+            .get_chunk(1, 31)
+            .unwrap()
+            .unwrap()
+            .read(NbtValue::clone)
+    };
+    let terrain = chunk(RegionKind::Chunks);
+    assert_eq!(terrain.get("xPos"), Some(&NbtValue::Int(33)));
+    assert_eq!(terrain.get("zPos"), Some(&NbtValue::Int(-1)));
+    let block_entity = &terrain
+        .get("block_entities")
+        .unwrap()
+        .as_list()
+        .unwrap()
+        .compounds()[0];
+    assert_eq!(block_entity.get("x"), Some(&NbtValue::Int(20 + 512)));
+    assert_eq!(block_entity.get("z"), Some(&NbtValue::Int(40 - 48)));
+    let structures = terrain.get("structures").unwrap();
+    assert_eq!(
+        structures
+            .get("References")
+            .unwrap()
+            .get("minecraft:village"),
+        Some(&NbtValue::LongArray(vec![33 | (-1i64 << 32)]))
+    );
+    let start = structures
+        .get("starts")
+        .unwrap()
+        .get("minecraft:village")
+        .unwrap();
+    assert_eq!(start.get("ChunkX"), Some(&NbtValue::Int(33)));
+    assert_eq!(
+        start
+            .get("Children")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .compounds()[0]
+            .get("BB"),
+        Some(&NbtValue::IntArray(vec![528, 60, -16, 543, 70, -1]))
+    );
+
+    let entities = chunk(RegionKind::Entities);
+    assert_eq!(
+        entities.get("Position"),
+        Some(&NbtValue::IntArray(vec![33, -1]))
+    );
+    assert_eq!(
+        entities
+            .get("Entities")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .compounds()[0]
+            .get("Pos"),
+        Some(&NbtValue::List(NbtList::DoubleList(vec![
+            532.5, 64.0, -7.5
+        ])))
+    );
+
+    let report = world
+        .copy_chunks(
+            &World::open_read_only(&source).unwrap(),
+            Dimension::Nether,
+            (1, 2),
+            (1, 2),
+            Dimension::End,
+            (0, 0),
+        )
+        .unwrap();
+    assert_eq!(report.poi_chunks, 1);
+    let poi = world
+        .get_region(0, 0, Dimension::End, RegionKind::Poi)
+        .unwrap()
+        .get_chunk(1, 2)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        poi.read(|nbt| nbt
+            .get("Sections")
+            .unwrap()
+            .get("4")
+            .unwrap()
+            .get("Records")
+            .cloned()),
+        Some(compounds([compound([(
+            "pos",
+            NbtValue::IntArray(vec![20, 64, 40])
+        )])]))
+    );
+
+    // the source chunk has no entities, the old ones at the destination go
+    let source = World::open_read_only(&source).unwrap();
+    let overworld = Dimension::Overworld;
+    let report = world
+        .copy_chunks(&source, overworld, (5, 5), (5, 5), overworld, (0, 0))
+        .unwrap();
+    assert_eq!((report.chunks, report.cleared_chunks), (1, 1));
+    let entities = world
+        .get_region(0, 0, overworld, RegionKind::Entities)
+        .unwrap();
+    assert!(entities.get_chunk(5, 5).unwrap().is_none());
+
+    assert!(world
+        .copy_chunks(&source, overworld, (1, 2), (1, 2), overworld, (i32::MAX, 0))
+        .is_err());
+}
+
+#[test]
+fn test_world_builder() {
+    let path = temp_dir("world_builder");
+    let chunk = compound([
+        ("DataVersion", NbtValue::Int(DATA_VERSION)),
+        ("xPos", NbtValue::Int(-1)),
+        ("zPos", NbtValue::Int(40)),
+    ]);
+    let world = WorldBuilder::new("Arena")
+        .seed(-42)
+        .game_mode(GameMode::Creative)
+        .generator(Generator::Void)
+        .game_rule("doDaylightCycle", false)
+        .chunk(
+            Dimension::Overworld,
+            RegionKind::Chunks,
+            -1,
+            40,
+            chunk.clone(),
+        )
+        .create(&path)
+        .unwrap();
+
+    let level_dat = world.get_level_dat().unwrap();
+    let data = level_dat.data(|e| e.cloned()).unwrap();
+    assert_eq!(data.get("LevelName"), Some(&string("Arena")));
+    assert_eq!(data.get("DataVersion"), Some(&NbtValue::Int(DATA_VERSION)));
+    assert_eq!(data.get("GameType"), Some(&NbtValue::Int(1)));
+    assert_eq!(
+        data.get("GameRules").and_then(|e| e.get("doDaylightCycle")),
+        Some(&string("false"))
+    );
+    assert_eq!(
+        data.get("Version").and_then(|e| e.get("Name")),
+        Some(&string("1.21.4"))
+    );
+    let settings = data.get("WorldGenSettings").unwrap();
+    assert_eq!(settings.get("seed"), Some(&NbtValue::Long(-42)));
+    let data = WorldBuilder::new("Old").data_version(3953).level_data();
+    assert_eq!(
+        data.get("Version").and_then(|e| e.get("Name")),
+        Some(&string("1.21"))
+    );
+    let seed = |data: &NbtValue| data.get("WorldGenSettings").unwrap().get("seed").cloned();
+    assert_ne!(seed(&data), seed(&WorldBuilder::new("Old").level_data()));
+    let generator = |dim: &str| {
+        settings
+            .get("dimensions")
+            .and_then(|e| e.get(dim))
+            .and_then(|e| e.get("generator"))
+            .and_then(|e| e.get("type"))
+            .cloned()
+    };
+    assert_eq!(
+        generator("minecraft:overworld"),
+        Some(string("minecraft:flat"))
+    );
+    assert_eq!(
+        generator("minecraft:the_nether"),
+        Some(string("minecraft:noise"))
+    );
+
+    let region = world
+        .get_existing_region(-1, 1, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        region
+            .get_chunk(31, 8)
+            .unwrap()
+            .unwrap()
+            .read(NbtValue::clone),
+        chunk
+    );
+
+    drop(world);
+    assert!(WorldBuilder::new("Arena").create(&path).is_err());
+}
+
+#[test]
+fn test_generate_flat() {
+    let layers = parse_layers("minecraft:bedrock, 2*dirt,grass_block[snowy=false]").unwrap();
+    assert_eq!(
+        layers,
+        vec![
+            (BlockState::new("bedrock"), 1),
+            (BlockState::new("dirt"), 2),
+            (BlockState::new("grass_block").with("snowy", "false"), 1),
+        ]
+    );
+    assert!(parse_layers("x*dirt").is_err());
+
+    let path = temp_dir("generate_flat");
+    let world = WorldBuilder::new("Flat")
+        .generator(Generator::Flat {
+            layers: layers.clone(),
+            biome: "minecraft:plains".to_string(),
+        })
+        .pregenerate((-1, -1), (0, 0))
+        .create(&path)
+        .unwrap();
+    assert_eq!(
+        world
+            .chunk_positions(Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+            .len(),
+        4
+    );
+
+    // old entities where a chunk is generated go, others stay
+    let entities = |x: i32| compound([("Position", NbtValue::IntArray(vec![x, 0]))]);
+    let region_path = world.region_path(0, 0, Dimension::Overworld, RegionKind::Entities);
+    std::fs::create_dir_all(region_path.parent().unwrap()).unwrap();
+    let mut region = RegionFile::new();
+    region.write_chunk(1, 0, &entities(1), 0).unwrap();
+    region.write_chunk(3, 0, &entities(3), 0).unwrap();
+    region.save(&region_path).unwrap();
+
+    let report = world
+        .generate(Dimension::Overworld, &Generator::Void, (1, 0), (2, 0))
+        .unwrap();
+    assert_eq!((report.chunks, report.cleared_chunks), (2, 1));
+    assert_eq!(
+        world
+            .chunk_positions(Dimension::Overworld, RegionKind::Entities)
+            .unwrap(),
+        vec![(3, 0)]
+    );
+    world.clear_cache();
+
+    let chunk = |x, z| {
+        world
+            .get_region(x >> 5, z >> 5, Dimension::Overworld, RegionKind::Chunks)
+            .unwrap()
+            .get_chunk(x & 31, z & 31)
+            .unwrap()
+            .unwrap()
+            .read(NbtValue::clone)
+    };
+    let flat = chunk(-1, -1);
+    assert_eq!(flat.get("Status"), Some(&string("minecraft:full")));
+    assert_eq!(flat.get("xPos"), Some(&NbtValue::Int(-1)));
+    // grass block on top at y -61, 4 above the bottom at -64, 9 bits per column
+    let heightmap = match flat
+        .get("Heightmaps")
+        .and_then(|e| e.get("MOTION_BLOCKING"))
+    {
+        Some(NbtValue::LongArray(v)) => v.clone(),
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(heightmap.len(), 37);
+    assert_eq!(heightmap[0] & 0x1ff, 4);
+    assert_eq!(heightmap[36] >> 27 & 0x1ff, 4);
+    let sections = flat.get("sections").and_then(NbtValue::as_list).unwrap();
+    assert_eq!(sections.compounds().len(), 24);
+    let bottom = &sections.compounds()[0];
+    let blocks = block::section_blocks(bottom).unwrap().unwrap();
+    assert_eq!(
+        blocks.get(block::section_index(5, -64, 5)),
+        &BlockState::new("bedrock")
+    );
+    assert_eq!(
+        blocks.get(block::section_index(5, -61, 5)),
+        &BlockState::new("grass_block").with("snowy", "false")
+    );
+    assert!(blocks.get(block::section_index(5, -60, 5)).is_air());
+
+    let void = chunk(2, 0);
+    let heightmap = void.get("Heightmaps").and_then(|e| e.get("WORLD_SURFACE"));
+    assert_eq!(heightmap, Some(&NbtValue::LongArray(vec![0; 37])));
+}
+
+#[test]
+fn test_import_heightmap() {
+    let mut image = vec![];
+    let mut encoder = png::Encoder::new(&mut image, 2, 2);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&[0, 255, 255, 0])
+        .unwrap();
+    let heightmap = Heightmap::from_png(std::io::Cursor::new(image)).unwrap();
+    assert_eq!((heightmap.width(), heightmap.height()), (2, 2));
+    assert_eq!(heightmap.sample(0.5, 0.0), 0.5);
+
+    let path = temp_dir("import_heightmap");
+    let world = WorldBuilder::new("Terrain")
+        .generator(Generator::Void)
+        .pregenerate((0, 0), (0, 0))
+        .create(&path)
+        .unwrap();
+    let options = TerrainOptions {
+        origin: (4, 4),
+        scale: 4.0,
+        heights: (50, 70),
+        sea_level: Some(60),
+        ..Default::default()
+    };
+    let report = world.import_heightmap(&heightmap, &options).unwrap();
+    assert_eq!((report.chunks, report.columns), (1, 64));
+
+    let nbt = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap()
+        .get_chunk(0, 0)
+        .unwrap()
+        .unwrap()
+        .read(NbtValue::clone);
+    assert_eq!(nbt.get("Status"), Some(&string("minecraft:full")));
+    let chunk = ChunkBuilder::from_nbt(&nbt, Dimension::Overworld).unwrap();
+    let name = |x, y, z| chunk.block(x, y, z).name;
+    // black corner, under water
+    assert_eq!(name(4, -64, 4), "minecraft:bedrock");
+    assert_eq!(name(4, 49, 4), "minecraft:sand");
+    assert_eq!(name(4, 47, 4), "minecraft:stone");
+    assert_eq!(name(4, 60, 4), "minecraft:water");
+    assert_eq!(name(4, 61, 4), "minecraft:air");
+    // white corner
+    assert_eq!(name(11, 70, 4), "minecraft:grass_block");
+    assert_eq!(name(11, 67, 4), "minecraft:dirt");
+    assert_eq!(name(11, 66, 4), "minecraft:stone");
+    assert_eq!(name(11, 71, 4), "minecraft:air");
+    // outside the image
+    assert_eq!(name(3, 0, 4), "minecraft:air");
+    assert_eq!(name(12, -64, 4), "minecraft:air");
+
+    let heightmap = |key: &str| match nbt.get("Heightmaps").and_then(|e| e.get(key)) {
+        Some(NbtValue::LongArray(v)) => v[(4 * 16 + 4) / 7] >> ((4 * 16 + 4) % 7 * 9) & 0x1ff,
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(heightmap("WORLD_SURFACE"), 125);
+    assert_eq!(heightmap("OCEAN_FLOOR"), 115);
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(
+        block_color("minecraft:oak_stairs"),
+        block_color("oak_planks")
+    );
+    assert_eq!(
+        block_color("stone_brick_stairs"),
+        block_color("stone_bricks")
+    );
+    assert_eq!(block_color("light_blue_wool"), [58, 175, 217]);
+    assert_ne!(block_color("mod:unknown"), block_color("mod:other"));
+
+    let path = temp_dir("render");
+    let world = WorldBuilder::new("Render")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (1, 0))
+        .create(&path)
+        .unwrap();
+    world
+        .generate(
+            Dimension::Overworld,
+            &"sand,2*water".parse().unwrap(),
+            (0, 1),
+            (0, 1),
+        )
+        .unwrap();
+
+    let options = RenderOptions::default();
+    let image = world
+        .render_region(Dimension::Overworld, 0, 0, &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!((image.width(), image.height()), (512, 512));
+    let [r, g, b] = block_color("grass_block");
+    assert_eq!(image.get(5, 5), [r, g, b, 255]);
+    assert_eq!(image.get(20, 5), [r, g, b, 255]);
+    // shallow water over sand
+    let [r, g, b, a] = image.get(5, 20);
+    assert!(b > r && b > g && a == 255);
+    assert_ne!([r, g, b], block_color("water"));
+    assert_eq!(image.get(40, 40), [0; 4]);
+    assert!(world
+        .render_region(Dimension::Overworld, 1, 0, &options)
+        .unwrap()
+        .is_none());
+
+    let (image, origin) = world
+        .render_dimension(Dimension::Overworld, &options, 4)
+        .unwrap()
+        .unwrap();
+    assert_eq!((image.width(), image.height(), origin), (128, 128, (0, 0)));
+    let file = path.join("map.png");
+    image.save(&file).unwrap();
+    assert_eq!(Heightmap::open(&file).unwrap().width(), 128);
+}
+
+#[test]
+fn test_render_slice() {
+    let path = temp_dir("render_slice");
+    let world = WorldBuilder::new("Slice")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (1, 0))
+        .create(&path)
+        .unwrap();
+    let color = |name| {
+        let [r, g, b] = block_color(name);
+        [r, g, b, 255]
+    };
+
+    let image = world
+        .render_slice(Dimension::Overworld, Slice::Z(5), (31, -60), (0, -64))
+        .unwrap();
+    assert_eq!((image.width(), image.height()), (32, 5));
+    assert_eq!(image.get(31, 4), color("stone"));
+    assert_eq!(image.get(0, 3), color("grass_block"));
+    assert_eq!(image.get(0, 0), [0; 4]);
+
+    let image = world
+        .render_slice(Dimension::Overworld, Slice::X(3), (0, -64), (20, -63))
+        .unwrap();
+    assert_eq!((image.width(), image.height()), (21, 2));
+    assert_eq!(image.get(15, 1), color("stone"));
+    assert_eq!(image.get(16, 1), [0; 4]);
+
+    let image = world
+        .render_slice(Dimension::Overworld, Slice::Y(-63), (0, 0), (40, 10))
+        .unwrap();
+    assert_eq!(image.get(0, 0), color("grass_block"));
+    assert_eq!(image.get(31, 10), color("grass_block"));
+    assert_eq!(image.get(32, 0), [0; 4]);
+}
+
+#[test]
+fn test_chunk_heatmap() {
+    let path = temp_dir("chunk_heatmap");
+    let world = WorldBuilder::new("Heatmap")
+        .generator("stone".parse().unwrap())
+        .pregenerate((-1, 0), (1, 1))
+        .create(&path)
+        .unwrap();
+    let region = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    region.get_chunk(1, 1).unwrap().unwrap().write(|nbt| {
+        nbt.as_compound_mut()
+            .unwrap()
+            .insert("InhabitedTime".to_string(), NbtValue::Long(72000))
+    });
+    world.save().unwrap();
+
+    let heatmap = world
+        .chunk_heatmap(Dimension::Overworld, ChunkMetric::InhabitedTime)
+        .unwrap();
+    assert_eq!(heatmap.chunks.len(), 6);
+    assert_eq!(heatmap.range(), Some((0, 72000)));
+    assert_eq!(heatmap.origin(), Some((-1, 0)));
+    let mut csv = vec![];
+    heatmap.to_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().next(), Some("chunk_x,chunk_z,inhabited-time"));
+    assert!(csv.contains("\n1,1,72000\n"));
+
+    let image = heatmap.render(2, false);
+    assert_eq!(image.height(), 4 + 30);
+    assert!(image.width() >= 6);
+    // the hottest chunk is the opposite end of the ramp from the rest
+    assert_ne!(image.get(4, 2), image.get(0, 0));
+    assert_eq!(image.get(0, 0), image.get(2, 2));
+    assert_eq!(image.get(6, 0), [0; 4]);
+
+    let heatmap = world
+        .chunk_heatmap(Dimension::Overworld, ChunkMetric::Size)
+        .unwrap();
+    assert_eq!(heatmap.chunks.len(), 6);
+    assert!(heatmap.chunks.iter().all(|e| e.1 > 0));
+    assert_eq!(
+        "timestamp".parse::<ChunkMetric>().unwrap(),
+        ChunkMetric::Timestamp
+    );
+}
+
+#[test]
+fn test_render_biomes() {
+    let path = temp_dir("render_biomes");
+    let world = WorldBuilder::new("Biomes")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (2, 0))
+        .create(&path)
+        .unwrap();
+    let region = world
+        .get_region(0, 0, Dimension::Overworld, RegionKind::Chunks)
+        .unwrap();
+    for (x, biome) in [(1, "desert"), (2, "mypack:glade")] {
+        let chunk = region.get_chunk(x, 0).unwrap().unwrap();
+        let mut builder = chunk
+            .read(|nbt| ChunkBuilder::from_nbt(nbt, Dimension::Overworld))
+            .unwrap();
+        builder.set_biome(biome);
+        region
+            .set_chunk(x, 0, &Chunk::new(builder.to_nbt()))
+            .unwrap();
+    }
+
+    let rgba = |[r, g, b]: [u8; 3]| [r, g, b, 255];
+    let mut options = RenderOptions {
+        biomes: Some(64),
+        ..Default::default()
+    };
+    let image = world
+        .render_region(Dimension::Overworld, 0, 0, &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(image.get(3, 15), rgba(biome_color("plains")));
+    assert_eq!(image.get(16, 0), rgba(biome_color("minecraft:desert")));
+    assert_eq!(image.get(40, 8), rgba(biome_color("mypack:glade")));
+    assert_eq!(image.get(48, 0), [0; 4]);
+
+    options
+        .biome_colors
+        .insert("mypack:glade".to_string(), [1, 2, 3]);
+    options.biomes = Some(-64);
+    let image = world
+        .render_region(Dimension::Overworld, 0, 0, &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(image.get(40, 8), [1, 2, 3, 255]);
+
+    options.biomes = Some(400);
+    let image = world
+        .render_region(Dimension::Overworld, 0, 0, &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(image.get(0, 0), [0; 4]);
+}
+
+#[test]
+fn test_export_maps() {
+    let path = test_world("export_maps", &[]);
+    std::fs::create_dir_all(path.join("data")).unwrap();
+    let write_map = |id: u32, colors: Vec<i8>| {
+        NbtFile {
+            name: String::new(),
+            nbt: compound([(
+                "data",
+                compound([
+                    ("scale", NbtValue::Byte(2)),
+                    ("dimension", string("minecraft:overworld")),
+                    ("xCenter", NbtValue::Int(64)),
+                    ("zCenter", NbtValue::Int(-64)),
+                    ("colors", NbtValue::ByteArray(colors)),
+                ]),
+            )]),
+        }
+        .to_compressed_writer(File::create(path.join(format!("data/map_{id}.dat"))).unwrap())
+        .unwrap()
+    };
+    let mut colors = vec![0i8; 128 * 128];
+    colors[0] = 4 + 2;
+    colors[2] = (12 * 4 + 3) as i8;
+    colors[128 * 128 - 1] = (59 * 4 + 1) as i8;
+    write_map(0, colors);
+    write_map(7, vec![0; 10]);
+    File::create(path.join("data/idcounts.dat")).unwrap();
+
+    let world = World::open(&path).unwrap();
+    assert_eq!(world.map_ids().unwrap(), vec![0, 7]);
+    let map = world.get_map(0).unwrap().unwrap();
+    assert_eq!((map.scale, map.center), (2, (64, -64)));
+    let image = map.to_image();
+    assert_eq!(image.get(0, 0), [127, 178, 56, 255]);
+    assert_eq!(image.get(1, 0), [0; 4]);
+    assert_eq!(image.get(2, 0), [33, 33, 135, 255]);
+    assert_eq!(image.get(127, 127), [86, 86, 86, 255]);
+    assert!(world.get_map(7).is_err());
+    assert!(world.get_map(8).unwrap().is_none());
+
+    let report = world.export_maps(&path.join("maps")).unwrap();
+    assert_eq!((report.maps, report.unreadable), (vec![0], vec![7]));
+    let file = path.join("maps/map_0.png");
+    assert_eq!(Heightmap::open(&file).unwrap().width(), 128);
+}
+
+#[test]
+fn test_map_art() {
+    let path = test_world("map_art", &[]);
+    let world = World::open(&path).unwrap();
+    let mut image = Image::new(200, 100);
+    for y in 0..100 {
+        for x in 0..200 {
+            let color = match x < 100 {
+                true => [255, 0, 0, 255],
+                false => [127, 178, 56, 255],
+            };
+            image.set(x, y, color);
+        }
+    }
+    std::fs::create_dir_all(path.join("data")).unwrap();
+    MapItem::new(vec![0; 128 * 128])
+        .unwrap()
+        .save(&path.join("data/map_3.dat"))
+        .unwrap();
+    NbtFile {
+        name: String::new(),
+        nbt: compound([("data", compound([("map", NbtValue::Int(5))]))]),
+    }
+    .to_compressed_writer(File::create(path.join("data/idcounts.dat")).unwrap())
+    .unwrap();
+    let last_id = || {
+        let file = File::open(path.join("data/idcounts.dat")).unwrap();
+        let counts = NbtFile::from_compressed_reader(file).unwrap().nbt;
+        counts.get("data").unwrap().get("map").unwrap().as_i64()
+    };
+
+    let options = MapArtOptions {
+        dither: false,
+        ..Default::default()
+    };
+    let report = world.create_maps(&image, &options).unwrap();
+    assert_eq!(
+        (report.columns, report.rows, report.ids),
+        (2, 1, vec![6, 7])
+    );
+    assert_eq!(last_id(), Some(7));
+    let left = world.get_map(6).unwrap().unwrap();
+    assert!(left.locked);
+    assert_eq!(left.colors[0], 4 * 4 + 2);
+    assert_eq!(left.colors[127], 4 + 2);
+    let right = world.get_map(7).unwrap().unwrap().to_image();
+    assert_eq!(right.get(0, 0), [127, 178, 56, 255]);
+    assert_eq!(right.get(100, 0), [0; 4]);
+    assert_eq!(right.get(0, 110), [0; 4]);
+
+    let options = MapArtOptions {
+        size: Some((1, 1)),
+        dither: true,
+    };
+    let report = world.create_maps(&image, &options).unwrap();
+    assert_eq!(report.ids, vec![8]);
+    assert_eq!(last_id(), Some(8));
+    let map = world.get_map(8).unwrap().unwrap().to_image();
+    assert_eq!(map.get(10, 127), [255, 0, 0, 255]);
+    assert_eq!(map.get(127, 0), [127, 178, 56, 255]);
+    assert_eq!(world.map_ids().unwrap(), vec![3, 6, 7, 8]);
+}
+
+#[test]
+fn test_export_schematic() {
+    let path = temp_dir("export_schematic");
+    let world = WorldBuilder::new("Schematic")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (1, 0))
+        .create(&path)
+        .unwrap();
+    let dim = Dimension::Overworld;
+    let region = world.get_region(0, 0, dim, RegionKind::Chunks).unwrap();
+    let chunk = region.get_chunk(1, 0).unwrap().unwrap();
+    let mut builder = chunk.read(|nbt| ChunkBuilder::from_nbt(nbt, dim)).unwrap();
+    builder.set_block(1, -62, 3, &"chest[facing=north]".parse().unwrap());
+    let mut nbt = builder.to_nbt();
+    nbt.as_compound_mut().unwrap().insert(
+        "block_entities".to_string(),
+        compounds([compound([
+            ("id", string("minecraft:chest")),
+            ("x", NbtValue::Int(17)),
+            ("y", NbtValue::Int(-62)),
+            ("z", NbtValue::Int(3)),
+            ("Items", NbtValue::List(NbtList::EmptyList())),
+        ])]),
+    );
+    region.set_chunk(1, 0, &Chunk::new(nbt)).unwrap();
+    let pig = compound([
+        ("id", string("minecraft:pig")),
+        (
+            "Pos",
+            NbtValue::List(NbtList::DoubleList(vec![18.5, -61.0, 4.5])),
+        ),
+    ]);
+    world
+        .get_region(0, 0, dim, RegionKind::Entities)
+        .unwrap()
+        .set_chunk(
+            1,
+            0,
+            &Chunk::new(compound([
+                ("Position", NbtValue::IntArray(vec![1, 0])),
+                ("Entities", compounds([pig])),
+            ])),
+        )
+        .unwrap();
+    world.save().unwrap();
+
+    let bounds = BoundingBox::new([15, -64, 0], [18, -61, 5]);
+    let schematic = world.extract_schematic(dim, bounds).unwrap();
+    assert_eq!(schematic.size, [4, 4, 6]);
+    assert_eq!(schematic.offset, [15, -64, 0]);
+    assert_eq!(schematic.block([0, 0, 0]), &BlockState::new("stone"));
+    assert_eq!(schematic.block([0, 1, 5]), &BlockState::new("grass_block"));
+    assert_eq!(schematic.block([2, 2, 3]).name, "minecraft:chest");
+    assert_eq!(schematic.block([2, 3, 3]), &BlockState::new("air"));
+    let summary = schematic.summary();
+    assert_eq!((summary.block_entities, summary.entities), (1, 1));
+
+    let file = schematic.to_sponge(3).unwrap();
+    let root = file.nbt.get("Schematic").unwrap();
+    assert_eq!(root.get("Version"), Some(&NbtValue::Int(3)));
+    assert_eq!(root.get("Width"), Some(&NbtValue::Short(4)));
+    let blocks = root.get("Blocks").unwrap();
+    let palette = blocks.get("Palette").unwrap().as_compound().unwrap();
+    assert_eq!(palette.len(), 4);
+    assert!(palette.contains_key("minecraft:chest[facing=north]"));
+    let data = match blocks.get("Data") {
+        Some(NbtValue::ByteArray(v)) => v,
+        _ => panic!(),
+    };
+    assert_eq!(data.len(), 96);
+    let chest = &blocks
+        .get("BlockEntities")
+        .unwrap()
+        .as_list()
+        .unwrap()
+        .compounds()[0];
+    assert_eq!(chest.get("Pos"), Some(&NbtValue::IntArray(vec![2, 2, 3])));
+    assert_eq!(chest.get("Id"), Some(&string("minecraft:chest")));
+    assert!(chest.get("Data").unwrap().get("Items").is_some());
+    let pig = &root.get("Entities").unwrap().as_list().unwrap().compounds()[0];
+    assert_eq!(
+        pig.get("Pos"),
+        Some(&NbtValue::List(NbtList::DoubleList(vec![3.5, 3.0, 4.5])))
+    );
+    let biomes = root.get("Biomes").unwrap().get("Palette").unwrap();
+    assert!(biomes.get("minecraft:plains").is_some());
+
+    let file = schematic.to_sponge(2).unwrap();
+    assert_eq!(file.name, "Schematic");
+    assert_eq!(file.nbt.get("PaletteMax"), Some(&NbtValue::Int(4)));
+    let chest = &file
+        .nbt
+        .get("BlockEntities")
+        .unwrap()
+        .as_list()
+        .unwrap()
+        .compounds()[0];
+    assert!(chest.get("Items").is_some());
+    match file.nbt.get("BiomeData") {
+        Some(NbtValue::ByteArray(v)) => assert_eq!(v.len(), 24),
+        _ => panic!(),
+    }
+    assert!(schematic.to_sponge(1).is_err());
+    schematic.save_sponge(&path.join("test.schem"), 3).unwrap();
+    let file = NbtFile::from_compressed_reader(File::open(path.join("test.schem")).unwrap());
+    assert!(file.unwrap().nbt.get("Schematic").is_some());
+}
+
+#[test]
+fn test_paste_schematic() {
+    // oak stairs facing east, a chest facing north and red wool in a 2 by 1 by 3 box
+    let legacy = compound([
+        ("Width", NbtValue::Short(2)),
+        ("Height", NbtValue::Short(1)),
+        ("Length", NbtValue::Short(3)),
+        ("Materials", string("Alpha")),
+        ("Blocks", NbtValue::ByteArray(vec![53, 54, 0, 0, 0, 35])),
+        ("Data", NbtValue::ByteArray(vec![0, 2, 0, 0, 0, 14])),
+        (
+            "TileEntities",
+            compounds([compound([
+                ("id", string("Chest")),
+                ("x", NbtValue::Int(1)),
+                ("y", NbtValue::Int(0)),
+                ("z", NbtValue::Int(0)),
+                ("Items", NbtValue::List(NbtList::EmptyList())),
+            ])]),
+        ),
+        ("WEOriginX", NbtValue::Int(5)),
+    ]);
+    let schematic = Schematic::from_nbt(&legacy).unwrap();
+    assert_eq!(schematic.offset, [5, 0, 0]);
+    let stairs = BlockState::new("oak_stairs")
+        .with("facing", "east")
+        .with("half", "bottom");
+    assert_eq!(schematic.block([0, 0, 0]), &stairs);
+    assert_eq!(schematic.block([1, 0, 2]), &BlockState::new("red_wool"));
+    let (pos, chest) = &schematic.block_entities[0];
+    assert_eq!(*pos, [1, 0, 0]);
+    assert_eq!(chest.get("id"), Some(&string("minecraft:chest")));
+
+    // an open door facing south with its hinge on the right, and a spawner
+    let door = compound([
+        ("Width", NbtValue::Short(1)),
+        ("Height", NbtValue::Short(2)),
+        ("Length", NbtValue::Short(1)),
+        ("Blocks", NbtValue::ByteArray(vec![64, 64])),
+        ("Data", NbtValue::ByteArray(vec![5, 9])),
+        (
+            "TileEntities",
+            compounds([compound([
+                ("id", string("MobSpawner")),
+                ("x", NbtValue::Int(0)),
+                ("y", NbtValue::Int(0)),
+                ("z", NbtValue::Int(0)),
+            ])]),
+        ),
+    ]);
+    let door = Schematic::from_nbt(&door).unwrap();
+    for (y, half) in [(0, "lower"), (1, "upper")] {
+        assert_eq!(
+            door.block([0, y, 0]).to_string(),
+            format!(
+                "minecraft:oak_door[facing=south,half={half},hinge=right,open=true,powered=false]"
+            )
+        );
+    }
+    let spawner = &door.block_entities[0].1;
+    assert_eq!(spawner.get("id"), Some(&string("minecraft:mob_spawner")));
+
+    for version in [2, 3] {
+        let file = schematic.to_sponge(version).unwrap();
+        let read = Schematic::from_nbt(&file.nbt).unwrap();
+        assert_eq!((read.size, read.offset), (schematic.size, schematic.offset));
+        assert_eq!(read.block([0, 0, 0]), &stairs);
+        assert_eq!(read.block([1, 0, 2]), &BlockState::new("red_wool"));
+        assert_eq!(read.block_entities[0].0, [1, 0, 0]);
+        assert!(read.block_entities[0].1.get("Items").is_some());
+    }
+
+    let turned = schematic.transformed(Rotation::Clockwise90, Mirror::None);
+    assert_eq!(turned.size, [3, 1, 2]);
+    assert_eq!(
+        turned.block([2, 0, 0]),
+        &stairs.clone().with("facing", "south")
+    );
+    assert_eq!(turned.block([2, 0, 1]).properties[0].1, "east");
+    assert_eq!(turned.block([0, 0, 1]), &BlockState::new("red_wool"));
+    assert_eq!(turned.block_entities[0].0, [2, 0, 1]);
+    let mirrored = schematic.transformed(Rotation::None, Mirror::X);
+    assert_eq!(
+        mirrored.block([1, 0, 0]),
+        &stairs.clone().with("facing", "west")
+    );
+    assert_eq!(
+        "oak_fence[east=true,north=false]"
+            .parse::<BlockState>()
+            .map(|e| Schematic {
+                blocks: PalettedContainer::filled(e, 1),
+                size: [1, 1, 1],
+                ..schematic.clone()
+            })
+            .unwrap()
+            .transformed(Rotation::Clockwise180, Mirror::None)
+            .block([0, 0, 0])
+            .to_string(),
+        "minecraft:oak_fence[south=false,west=true]"
+    );
+
+    let path = temp_dir("paste_schematic");
+    let world = WorldBuilder::new("Paste")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (0, 0))
+        .create(&path)
+        .unwrap();
+    let dim = Dimension::Overworld;
+    let options = PasteOptions {
+        rotation: Rotation::Clockwise90,
+        skip_air: true,
+        ..Default::default()
+    };
+    // only chunk 0,0 exists, the part in chunk 1,0 is left out
+    let report = world
+        .paste_schematic(dim, &schematic, [14, -63, 0], &options)
+        .unwrap();
+    assert_eq!((report.chunks, report.skipped_chunks), (1, 1));
+    let report = world
+        .paste_schematic(
+            dim,
+            &schematic,
+            [14, -63, 0],
+            &PasteOptions {
+                create_chunks: true,
+                ..options
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        (report.chunks, report.blocks, report.block_entities),
+        (2, 3, 1)
+    );
+    assert_eq!((report.created_chunks, report.skipped_chunks), (1, 0));
+    assert!(report.session.is_some());
+    let read = |x: i32, z: i32| {
+        let chunk = world
+            .get_region(0, 0, dim, RegionKind::Chunks)
+            .unwrap()
+            .get_chunk(x >> 4, 0)
+            .unwrap()
+            .unwrap();
+        chunk
+            .read(|nbt| ChunkBuilder::from_nbt(nbt, dim))
+            .unwrap()
+            .block(x & 15, -63, z)
+    };
+    assert_eq!(read(16, 0), stairs.clone().with("facing", "south"));
+    assert_eq!(read(14, 1), BlockState::new("red_wool"));
+    // air was skipped, the grass stays
+    assert_eq!(read(15, 0), BlockState::new("grass_block"));
+}
+
+#[test]
+fn test_structure() {
+    let ints = |v: [i32; 3]| NbtValue::List(NbtList::IntList(v.to_vec()));
+    let palette = |wood: &str| {
+        NbtList::CompoundList(vec![
+            BlockState::new(&format!("{wood}_planks"))
+                .to_nbt()
+                .as_compound()
+                .unwrap()
+                .clone(),
+            BlockState::new("chest")
+                .with("facing", "west")
+                .to_nbt()
+                .as_compound()
+                .unwrap()
+                .clone(),
+        ])
+    };
+    // a 2 by 2 by 1 piece with one position left empty and a chest
+    let nbt = compound([
+        ("DataVersion", NbtValue::Int(DATA_VERSION)),
+        ("size", ints([2, 2, 1])),
+        (
+            "palettes",
+            NbtValue::List(NbtList::ListList(vec![palette("oak"), palette("spruce")])),
+        ),
+        (
+            "blocks",
+            compounds([
+                compound([("pos", ints([0, 0, 0])), ("state", NbtValue::Int(0))]),
+                compound([("pos", ints([1, 0, 0])), ("state", NbtValue::Int(0))]),
+                compound([
+                    ("pos", ints([0, 1, 0])),
+                    ("state", NbtValue::Int(1)),
+                    (
+                        "nbt",
+                        compound([
+                            ("id", string("minecraft:chest")),
+                            ("Items", NbtValue::List(NbtList::EmptyList())),
+                        ]),
+                    ),
+                ]),
+            ]),
+        ),
+        (
+            "entities",
+            compounds([compound([
+                (
+                    "pos",
+                    NbtValue::List(NbtList::DoubleList(vec![1.5, 1.0, 0.5])),
+                ),
+                ("blockPos", ints([1, 1, 0])),
+                ("nbt", compound([("id", string("minecraft:armor_stand"))])),
+            ])]),
+        ),
+    ]);
+    let structure = Structure::from_nbt(&nbt).unwrap();
+    assert_eq!(structure.palettes.len(), 2);
+    assert_eq!(structure.blocks.len(), 3);
+    assert_eq!(structure.entities[0].block_pos, [1, 1, 0]);
+    assert_eq!(Structure::from_nbt(&structure.to_nbt()).unwrap(), structure);
+
+    let path = temp_dir("structure");
+    structure.save(&path.join("piece.nbt")).unwrap();
+    let structure = Structure::open(&path.join("piece.nbt")).unwrap();
+    let mut single = structure.clone();
+    single.palettes.truncate(1);
+    assert!(single.to_nbt().get("palette").is_some());
+    assert_eq!(Structure::from_nbt(&single.to_nbt()).unwrap(), single);
+    assert!(structure.to_schematic(2).is_err());
+
+    let world = WorldBuilder::new("Structure")
+        .generator("stone,grass_block".parse().unwrap())
+        .pregenerate((0, 0), (0, 0))
+        .create(path.join("world"))
+        .unwrap();
+    let dim = Dimension::Overworld;
+    let report = world
+        .place_structure(dim, &structure, 1, [4, -63, 4], &PasteOptions::default())
+        .unwrap();
+    assert_eq!(
+        (report.blocks, report.block_entities, report.entities),
+        (3, 1, 1)
+    );
+    let extracted = world
+        .extract_structure(dim, BoundingBox::new([4, -63, 4], [5, -62, 4]))
+        .unwrap();
+    assert_eq!(extracted.size, [2, 2, 1]);
+    // the empty position kept the world's air
+    assert_eq!(extracted.blocks.len(), 4);
+    let state = |pos: [i32; 3]| {
+        let block = extracted.blocks.iter().find(|e| e.pos == pos).unwrap();
+        (&extracted.palettes[0][block.state], block.nbt.is_some())
+    };
+    assert_eq!(state([1, 0, 0]), (&BlockState::new("spruce_planks"), false));
+    assert_eq!(state([1, 1, 0]), (&BlockState::new("air"), false));
+    assert_eq!(state([0, 1, 0]).1, true);
+    assert_eq!(extracted.entities[0].pos, [1.5, 1.0, 0.5]);
+}
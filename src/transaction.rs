@@ -0,0 +1,472 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    region::{self, RawChunk, RegionFile},
+    undo::{self, UndoWriter, UNDO_DIR},
+    world::{self, Chunk, Dimension, Region, RegionKey, RegionKind, World},
+    Error, Result,
+};
+
+/// tells apart sessions this process started in the same second, the process id tells apart
+/// those of other processes
+static SESSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// name of the journal in the world directory while a transaction commits
+pub const JOURNAL: &str = "edit.journal";
+
+/// what a commit is about to do, paths are relative to the world directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    /// once set the staged files are complete and the commit has to be finished
+    committed: bool,
+    /// staged file and the file it replaces
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// region files that end up empty and `.mcc` files of chunks that no longer need one
+    deletes: Vec<PathBuf>,
+}
+
+impl Journal {
+    fn write(&self, root: &Path) -> Result<()> {
+        // written next to the journal and renamed so it is never half written
+        let tmp = root.join(format!("{JOURNAL}.new"));
+        write_synced(&tmp, &serde_json::to_vec(self).unwrap())?;
+        std::fs::rename(&tmp, root.join(JOURNAL)).map_err(Error::FileError)?;
+        sync_dir(root);
+        Ok(())
+    }
+
+    /// moves the staged files in place, safe to repeat after a crash
+    fn finish(&self, root: &Path) -> Result<()> {
+        for (staged, target) in self.renames.iter() {
+            if root.join(staged).is_file() {
+                std::fs::rename(root.join(staged), root.join(target)).map_err(Error::FileError)?;
+            }
+        }
+        for path in self.deletes.iter() {
+            if root.join(path).is_file() {
+                std::fs::remove_file(root.join(path)).map_err(Error::FileError)?;
+            }
+        }
+        self.close(root)
+    }
+
+    /// removes whatever was staged, leaving the world as it was
+    fn discard(&self, root: &Path) -> Result<()> {
+        for (staged, _) in self.renames.iter() {
+            if root.join(staged).is_file() {
+                std::fs::remove_file(root.join(staged)).map_err(Error::FileError)?;
+            }
+        }
+        self.close(root)
+    }
+
+    fn close(&self, root: &Path) -> Result<()> {
+        for dir in self.renames.iter().map(|e| &e.1).chain(&self.deletes) {
+            if let Some(dir) = root.join(dir).parent() {
+                sync_dir(dir);
+            }
+        }
+        std::fs::remove_file(root.join(JOURNAL)).map_err(Error::FileError)?;
+        sync_dir(root);
+        Ok(())
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = File::create(path).map_err(Error::FileError)?;
+    file.write_all(bytes).map_err(Error::FileError)?;
+    file.sync_all().map_err(Error::FileError)
+}
+
+/// makes renames in `dir` durable, directories can't be opened for this on every platform
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".staged");
+    path.with_file_name(name)
+}
+
+/// replaces a single file atomically, the new content is staged next to it and renamed over
+/// it so a crash leaves either the old or the new file
+pub(crate) fn replace_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    let staged = staged_path(path);
+    let replaced = write_synced(&staged, bytes)
+        .and_then(|_| std::fs::rename(&staged, path).map_err(Error::FileError));
+    if replaced.is_err() {
+        let _ = std::fs::remove_file(&staged);
+    }
+    if let Some(dir) = path.parent() {
+        sync_dir(dir);
+    }
+    replaced
+}
+
+/// chunks grouped by the region they are in, regions in order
+pub(crate) fn by_region(
+    chunks: impl IntoIterator<Item = (i32, i32)>,
+) -> BTreeMap<(i32, i32), Vec<(i32, i32)>> {
+    let mut regions = BTreeMap::<_, Vec<_>>::new();
+    for (x, z) in chunks {
+        regions.entry((x >> 5, z >> 5)).or_default().push((x, z));
+    }
+    regions
+}
+
+/// fnv-1a of the region as it would be written, tells whether the world's copy changed
+fn fingerprint(file: &RegionFile) -> u64 {
+    let (bytes, external) = file.to_bytes();
+    let external = external.into_iter().flat_map(|(_, e)| e.data.iter());
+    bytes
+        .iter()
+        .chain(external)
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// what the transaction has written to staged files so far
+#[derive(Default)]
+struct Staged {
+    journal: Journal,
+    /// changed regions with a fingerprint of what they started out as
+    regions: HashMap<RegionKey, u64>,
+    undo: Option<UndoWriter>,
+}
+
+/// edits to any number of regions that are written all at once or not at all
+///
+/// regions come from [`Transaction::get_region`] and are copies, nothing reaches the world
+/// until [`Transaction::commit`], dropping the transaction rolls it back
+///
+/// every commit is an edit session that can be undone with [`World::undo`], unless the
+/// transaction was started [`Transaction::without_undo`]
+pub struct Transaction {
+    world: World,
+    session: String,
+    record_undo: bool,
+    /// the copies and what they started out as
+    regions: Mutex<HashMap<RegionKey, (Region, RegionFile)>>,
+    staged: Mutex<Staged>,
+}
+
+impl Transaction {
+    /// id of the edit session the commit is recorded as
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// commits without recording the previous chunks in `undo/`, for edits that are meant to
+    /// free space like pruning, which would otherwise move the deleted chunks into the undo file
+    pub fn without_undo(mut self) -> Self {
+        self.record_undo = false;
+        self
+    }
+
+    /// the transaction's copy of the region, starting out as the world sees it
+    ///
+    /// fails for a region that was changed and already finished with
+    /// [`Transaction::finish_region`]
+    pub fn get_region(&self, x: i32, z: i32, dim: Dimension, kind: RegionKind) -> Result<Region> {
+        let key = (dim, kind, x, z);
+        let mut regions = self.regions.lock().unwrap();
+        if let Some((region, _)) = regions.get(&key) {
+            return Ok(region.clone());
+        }
+        if self.staged.lock().unwrap().regions.contains_key(&key) {
+            return Err(Error::Custom(format!(
+                "region {x} {z} was already finished in this transaction"
+            )));
+        }
+        let file = self.world.region_snapshot(key)?;
+        let region = self.world.staged_region(key, file.clone());
+        regions.insert(key, (region.clone(), file));
+        Ok(region)
+    }
+
+    /// the chunk at absolute chunk coordinates x, z
+    pub fn get_chunk(
+        &self,
+        x: i32,
+        z: i32,
+        dim: Dimension,
+        kind: RegionKind,
+    ) -> Result<Option<Chunk>> {
+        self.get_region(x >> 5, z >> 5, dim, kind)?
+            .get_chunk(x & 31, z & 31)
+    }
+
+    /// writes the transaction's copy of the region to its staged file and lets go of it, so
+    /// edits to more regions than fit in memory only hold the ones in use; the commit still
+    /// replaces all of them at once
+    pub fn finish_region(&self, x: i32, z: i32, dim: Dimension, kind: RegionKind) -> Result<()> {
+        let key = (dim, kind, x, z);
+        let Some((region, original)) = self.regions.lock().unwrap().remove(&key) else {
+            return Ok(());
+        };
+        self.stage(key, &region, &original)
+    }
+
+    fn stage(&self, key: RegionKey, region: &Region, original: &RegionFile) -> Result<()> {
+        if !region.is_dirty() {
+            return Ok(());
+        }
+        region.flush()?;
+        let root = self.world.path();
+        let path = region.path();
+        let mut staged = self.staged.lock().unwrap();
+        let staged = &mut *staged;
+        region.with_file(|file: &RegionFile| -> Result<()> {
+            if !undo::differs(original, file) {
+                return Ok(());
+            }
+            if self.record_undo {
+                let undo = match &mut staged.undo {
+                    Some(undo) => undo,
+                    None => {
+                        let path = self.undo_path();
+                        // replacing it would lose the record of an earlier edit
+                        if path.exists() {
+                            return Err(Error::Custom(format!(
+                                "undo session {} already exists",
+                                self.session
+                            )));
+                        }
+                        let mut name = path.into_os_string();
+                        name.push(".part");
+                        staged
+                            .undo
+                            .insert(UndoWriter::create(name.into(), &self.session)?)
+                    }
+                };
+                for entry in undo::record(key, original, file)? {
+                    undo.push(entry)?;
+                }
+            }
+            let (rx, rz) = region.position();
+            for (x, z) in original.positions() {
+                let was_external = original.chunk(x, z).is_some_and(RawChunk::is_external);
+                if was_external && !file.chunk(x, z).is_some_and(RawChunk::is_external) {
+                    let external = region::external_chunk_path(&path, rx * 32 + x, rz * 32 + z);
+                    staged.journal.deletes.push(relative(&root, &external));
+                }
+            }
+            if file.is_empty() {
+                staged.journal.deletes.push(relative(&root, &path));
+            } else {
+                let (bytes, external) = file.to_bytes();
+                for (i, chunk) in external {
+                    let (x, z) = (rx * 32 + (i % 32) as i32, rz * 32 + (i / 32) as i32);
+                    let external = region::external_chunk_path(&path, x, z);
+                    self.write_staged(&mut staged.journal, &external, &chunk.data)?;
+                }
+                self.write_staged(&mut staged.journal, &path, &bytes)?;
+            }
+            staged.regions.insert(key, fingerprint(original));
+            Ok(())
+        })
+    }
+
+    /// the file `path` is staged as, named after the session so transactions don't overwrite
+    /// each other's
+    fn staged_path(&self, path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.staged", self.session));
+        path.with_file_name(name)
+    }
+
+    fn write_staged(&self, journal: &mut Journal, path: &Path, bytes: &[u8]) -> Result<()> {
+        let root = self.world.path();
+        let staged = self.staged_path(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(Error::FileError)?;
+        }
+        // listed first so a failed write is cleaned up as well
+        journal
+            .renames
+            .push((relative(&root, &staged), relative(&root, path)));
+        write_synced(&staged, bytes)
+    }
+
+    fn undo_path(&self) -> PathBuf {
+        self.world
+            .path()
+            .join(UNDO_DIR)
+            .join(format!("{}.dat", self.session))
+    }
+
+    /// writes every changed region, returns how many there were
+    ///
+    /// regions not finished yet are staged next to their targets like
+    /// [`Transaction::finish_region`] does, then a journal is written before any of them is
+    /// replaced, if the process dies after that [`World::open`] finishes the commit, before
+    /// that it throws the staged files away
+    ///
+    /// the previous nbt of every changed chunk goes to `undo/<session>.dat` in the same commit,
+    /// that file is about as large as the changed chunks were
+    ///
+    /// fails without writing anything if a region was changed through the world since the
+    /// transaction read it; handles to the committed regions opened before can't be saved
+    /// afterwards, get them from the world again
+    pub fn commit(self) -> Result<usize> {
+        let mut regions = self.regions.lock().unwrap().drain().collect::<Vec<_>>();
+        regions.sort_by_key(|e| (e.0 .2, e.0 .3));
+        for (key, (region, original)) in regions {
+            self.stage(key, &region, &original)?;
+        }
+
+        let _commits = self.world.lock_commits();
+        let root = self.world.path();
+        let mut staged = self.staged.lock().unwrap();
+        if staged.regions.is_empty() {
+            return Ok(0);
+        }
+        let mut committed = staged.regions.iter().collect::<Vec<_>>();
+        committed.sort_by_key(|e| (e.0 .2, e.0 .3));
+        for (key, original) in committed {
+            // the world's copy changed after the transaction read it, committing would lose that
+            if fingerprint(&self.world.region_snapshot(*key)?) != *original {
+                let (dim, kind, x, z) = *key;
+                let path = self.world.region_path(x, z, dim, kind);
+                return Err(Error::Custom(format!(
+                    "{} changed outside the transaction",
+                    relative(&root, &path).display()
+                )));
+            }
+        }
+        if let Some(undo) = staged.undo.take() {
+            let path = self.undo_path();
+            if path.exists() {
+                undo.discard();
+                return Err(Error::Custom(format!(
+                    "undo session {} already exists",
+                    self.session
+                )));
+            }
+            let target = self.staged_path(&path);
+            staged
+                .journal
+                .renames
+                .push((relative(&root, &target), relative(&root, &path)));
+            undo.finish(&target)?;
+        }
+
+        staged.journal.committed = true;
+        if let Err(e) = staged.journal.write(&root) {
+            let _ = std::fs::remove_file(root.join(JOURNAL));
+            return Err(e);
+        }
+        // the commit can no longer be rolled back
+        let Staged {
+            journal, regions, ..
+        } = std::mem::take(&mut *staged);
+        journal.finish(&root)?;
+        for key in regions.keys() {
+            self.world.uncache_region(*key);
+        }
+        Ok(regions.len())
+    }
+
+    /// throws away every change, the same as dropping the transaction
+    pub fn rollback(self) {}
+}
+
+impl Drop for Transaction {
+    /// removes whatever was staged and not committed
+    fn drop(&mut self) {
+        let staged = std::mem::take(self.staged.get_mut().unwrap());
+        let root = self.world.path();
+        for (path, _) in staged.journal.renames.iter() {
+            let _ = std::fs::remove_file(root.join(path));
+        }
+        if let Some(undo) = staged.undo {
+            undo.discard();
+        }
+    }
+}
+
+fn relative(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+impl World {
+    /// starts a transaction, see [`Transaction`]
+    pub fn transaction(&self) -> Result<Transaction> {
+        self.check_writable()?;
+        Ok(Transaction {
+            world: self.clone(),
+            session: format!(
+                "{}-{}-{}",
+                world::unix_time(),
+                std::process::id(),
+                SESSIONS.fetch_add(1, Ordering::Relaxed)
+            ),
+            record_undo: true,
+            regions: Mutex::new(HashMap::new()),
+            staged: Mutex::new(Staged::default()),
+        })
+    }
+
+    /// removes files staged by transactions that never got to commit, only safe while no
+    /// transaction on the world is open
+    pub(crate) fn remove_uncommitted(&self) -> Result<()> {
+        let mut dirs = vec![self.path().join(UNDO_DIR)];
+        for dim in Dimension::ALL {
+            dirs.extend(RegionKind::ALL.map(|kind| self.region_dir(dim, kind)));
+        }
+        for dir in dirs {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry.map_err(Error::FileError)?.path();
+                let name = path
+                    .file_name()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or_default();
+                if name.ends_with(".staged") || name.ends_with(".dat.part") {
+                    std::fs::remove_file(&path).map_err(Error::FileError)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// finishes or rolls back a commit that was interrupted, returns whether there was one
+    ///
+    /// [`World::open`] does this already
+    pub fn recover(&self) -> Result<bool> {
+        self.check_writable()?;
+        let root = self.path();
+        let _ = std::fs::remove_file(root.join(format!("{JOURNAL}.new")));
+        let bytes = match std::fs::read(root.join(JOURNAL)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::FileError(e)),
+        };
+        let journal = serde_json::from_slice::<Journal>(&bytes)
+            .map_err(|e| Error::Custom(format!("unreadable {JOURNAL}: {e}")))?;
+        if journal.committed {
+            journal.finish(&root)?;
+        } else {
+            journal.discard(&root)?;
+        }
+        self.clear_cache();
+        Ok(true)
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+
+use crate::{
+    region::{RawChunk, RegionFile},
+    world::{Chunk, Dimension, RegionKey, RegionKind, World},
+    Error, Map, NbtFile, NbtValue, Result,
+};
+
+/// directory in the world holding one file per edit session
+pub const UNDO_DIR: &str = "undo";
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct UndoReport {
+    /// chunks put back to how they were
+    pub chunks: usize,
+    /// session recording the undo itself, undoing it redoes the edit
+    pub session: Option<String>,
+}
+
+/// fnv-1a of the serialized nbt, tells whether a chunk was changed after a session
+fn fingerprint(chunk: &RawChunk) -> Result<i64> {
+    let hash = chunk
+        .decompress()?
+        .iter()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+    Ok(hash as i64)
+}
+
+/// an entry for every chunk that differs between `before` and `after`, with the nbt it had
+/// before and a fingerprint of what it is now, chunks that didn't exist have no `Previous`,
+/// removed ones no `Hash`
+pub(crate) fn record(
+    (dim, kind, rx, rz): RegionKey,
+    before: &RegionFile,
+    after: &RegionFile,
+) -> Result<Vec<Map<String, NbtValue>>> {
+    let mut entries = vec![];
+    for z in 0..32 {
+        for x in 0..32 {
+            let (old, new) = (before.chunk(x, z), after.chunk(x, z));
+            if old == new {
+                continue;
+            }
+            let mut entry = Map::new();
+            entry.insert(
+                "Dimension".to_string(),
+                NbtValue::String(dim.id().to_string()),
+            );
+            entry.insert(
+                "Kind".to_string(),
+                NbtValue::String(kind.dir_name().to_string()),
+            );
+            entry.insert("X".to_string(), NbtValue::Int(rx * 32 + x));
+            entry.insert("Z".to_string(), NbtValue::Int(rz * 32 + z));
+            if let Some(old) = old {
+                entry.insert("Previous".to_string(), old.decode()?);
+            }
+            if let Some(new) = new {
+                entry.insert("Hash".to_string(), NbtValue::Long(fingerprint(new)?));
+            }
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// whether any chunk differs between `before` and `after`
+pub(crate) fn differs(before: &RegionFile, after: &RegionFile) -> bool {
+    (0..32).any(|z| (0..32).any(|x| before.chunk(x, z) != after.chunk(x, z)))
+}
+
+/// an undo session written entry by entry while a transaction stages its regions, so the
+/// previous chunks don't have to stay in memory; the uncompressed nbt goes to a `.part` file
+/// that is gzipped into place when the transaction commits
+pub(crate) struct UndoWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    entries: i32,
+    /// where the length of the chunk list goes once it is known
+    count_at: u64,
+}
+
+impl UndoWriter {
+    pub(crate) fn create(path: PathBuf, session: &str) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(Error::FileError)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(Error::FileError)?;
+        // the unnamed root compound, the session and the header of a compound list
+        let mut header = vec![10, 0, 0];
+        NbtValue::String(session.to_string())
+            .serialize("Session", &mut header)
+            .map_err(Error::FileError)?;
+        header.extend([9, 0, 6]);
+        header.extend(b"Chunks");
+        header.push(10);
+        let count_at = header.len() as u64;
+        header.extend(0i32.to_be_bytes());
+        let mut file = BufWriter::new(file);
+        file.write_all(&header).map_err(Error::FileError)?;
+        Ok(Self {
+            path,
+            file,
+            entries: 0,
+            count_at,
+        })
+    }
+
+    pub(crate) fn push(&mut self, entry: Map<String, NbtValue>) -> Result<()> {
+        let mut bytes = vec![];
+        NbtValue::Compound(entry)
+            .serialize("", &mut bytes)
+            .map_err(Error::FileError)?;
+        // list elements have no tag or name
+        self.file.write_all(&bytes[3..]).map_err(Error::FileError)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// writes the gzipped session file to `target` and removes the part file
+    pub(crate) fn finish(self, target: &Path) -> Result<()> {
+        let mut file = self
+            .file
+            .into_inner()
+            .map_err(|e| Error::FileError(e.into_error()))?;
+        let mut end = || -> std::io::Result<()> {
+            file.write_all(&[0])?;
+            file.seek(SeekFrom::Start(self.count_at))?;
+            file.write_all(&self.entries.to_be_bytes())?;
+            file.seek(SeekFrom::Start(0))?;
+            let mut encoder = GzEncoder::new(File::create(target)?, Compression::new(9));
+            std::io::copy(&mut file, &mut encoder)?;
+            encoder.finish()?.sync_all()
+        };
+        end().map_err(Error::FileError)?;
+        drop(file);
+        std::fs::remove_file(&self.path).map_err(Error::FileError)
+    }
+
+    pub(crate) fn discard(self) {
+        drop(self.file);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl World {
+    pub fn undo_dir(&self) -> PathBuf {
+        self.path().join(UNDO_DIR)
+    }
+
+    /// every recorded edit session, oldest first
+    pub fn undo_sessions(&self) -> Result<Vec<String>> {
+        let dir = self.undo_dir();
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut sessions = vec![];
+        for entry in std::fs::read_dir(dir).map_err(Error::FileError)? {
+            let path = entry.map_err(Error::FileError)?.path();
+            if path.extension().is_some_and(|e| e == "dat") {
+                if let Some(session) = path.file_stem().and_then(|e| e.to_str()) {
+                    sessions.push(session.to_string());
+                }
+            }
+        }
+        // ids start with the time, numerically sorted so 10 comes after 9
+        sessions.sort_by_key(|e| {
+            let parts = e
+                .split('-')
+                .map(|e| e.parse::<u64>().ok())
+                .collect::<Vec<_>>();
+            (parts, e.clone())
+        });
+        Ok(sessions)
+    }
+
+    /// puts every chunk changed in `session` back the way it was, in one transaction
+    ///
+    /// refuses without changing anything if any of those chunks changed again since
+    pub fn undo(&self, session: &str) -> Result<UndoReport> {
+        let path = self.undo_dir().join(format!("{session}.dat"));
+        let file = std::fs::File::open(&path)
+            .map_err(|_| Error::Custom(format!("no undo session {session}")))?;
+        let nbt = NbtFile::from_compressed_reader(file)?.nbt;
+        let entries = nbt
+            .get("Chunks")
+            .and_then(NbtValue::as_list)
+            .map(|e| e.compounds())
+            .unwrap_or_default();
+
+        let transaction = self.transaction()?;
+        let mut changed = vec![];
+        for entry in entries {
+            let int = |key| {
+                entry
+                    .get(key)
+                    .and_then(NbtValue::as_i64)
+                    .map(|e| e as i32)
+                    .ok_or(Error::Malformed(line!()))
+            };
+            let (x, z) = (int("X")?, int("Z")?);
+            let dim = entry
+                .get("Dimension")
+                .and_then(NbtValue::as_str)
+                .ok_or(Error::Malformed(line!()))?
+                .parse::<Dimension>()?;
+            let kind = entry.get("Kind").and_then(NbtValue::as_str);
+            let kind = RegionKind::ALL
+                .into_iter()
+                .find(|e| Some(e.dir_name()) == kind)
+                .ok_or(Error::Malformed(line!()))?;
+
+            let region = transaction.get_region(x >> 5, z >> 5, dim, kind)?;
+            let current = region.with_file(|f| f.chunk(x, z).map(fingerprint).transpose())?;
+            if current != entry.get("Hash").and_then(NbtValue::as_i64) {
+                changed.push(format!("{} {} {x} {z}", dim.id(), kind.dir_name()));
+                continue;
+            }
+            match entry.get("Previous") {
+                Some(previous) => {
+                    region.set_chunk(x & 31, z & 31, &Chunk::new(previous.clone()))?;
+                }
+                None => {
+                    region.remove_chunk(x & 31, z & 31);
+                }
+            }
+        }
+        if !changed.is_empty() {
+            return Err(Error::Custom(format!(
+                "can't undo {session}, chunks changed since: {}",
+                changed.join(", ")
+            )));
+        }
+
+        let undo_session = transaction.session().to_string();
+        let committed = transaction.commit()?;
+        std::fs::remove_file(&path).map_err(Error::FileError)?;
+        Ok(UndoReport {
+            chunks: entries.len(),
+            session: (committed > 0).then_some(undo_session),
+        })
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    region::{
+        self, RawChunk, RegionFile, CHUNKS_PER_REGION, COMPRESSION_CUSTOM, COMPRESSION_EXTERNAL,
+        COMPRESSION_GZIP, COMPRESSION_LZ4, COMPRESSION_NONE, COMPRESSION_ZLIB, SECTOR_SIZE,
+    },
+    world::{Chunk, Dimension, RegionKind, World},
+    Error, NbtValue, Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Issue {
+    /// the offset points into the header or past the end of the file
+    OffsetOutOfBounds {
+        offset: usize,
+        sectors: usize,
+    },
+    /// shares sectors with the chunk in slot `other`
+    Overlap {
+        other: (i32, i32),
+    },
+    /// the stored length is zero or doesn't fit the allocated sectors
+    BadLength {
+        length: usize,
+    },
+    UnknownCompression {
+        compression: u8,
+    },
+    /// a valid compression this tool can't decode, the chunk is never dropped by repair
+    UnsupportedCompression {
+        compression: u8,
+    },
+    MissingExternal,
+    Undecodable {
+        error: String,
+    },
+    /// xPos and zPos don't match the slot the chunk is stored in
+    WrongPosition {
+        found: (i32, i32),
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChunkIssue {
+    /// absolute chunk coordinates of the slot
+    pub chunk: (i32, i32),
+    pub issue: Issue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Repair {
+    Dropped,
+    Relocated { to: (i32, i32) },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionReport {
+    pub path: PathBuf,
+    pub issues: Vec<ChunkIssue>,
+    /// what repair did to every slot it changed, empty when not repairing
+    pub repairs: Vec<((i32, i32), Repair)>,
+}
+
+struct Entry {
+    slot: usize,
+    offset: usize,
+    sectors: usize,
+    chunk: Option<RawChunk>,
+}
+
+/// checks every chunk slot of a region file, with `repair` bad chunks are dropped or moved
+/// to their own slot and the file is rewritten, the original is kept as `.mca.bak`
+pub fn validate_region(path: &Path, repair: bool) -> Result<RegionReport> {
+    let bytes = std::fs::read(path).map_err(Error::FileError)?;
+    let (rx, rz) = region::region_coords(path).ok_or(Error::Malformed(line!()))?;
+    let slot_pos = |slot: usize| (rx * 32 + (slot % 32) as i32, rz * 32 + (slot / 32) as i32);
+    let mut report = RegionReport {
+        path: path.to_path_buf(),
+        issues: vec![],
+        repairs: vec![],
+    };
+    let mut issue = |slot, issue| {
+        report.issues.push(ChunkIssue {
+            chunk: slot_pos(slot),
+            issue,
+        })
+    };
+    if bytes.is_empty() {
+        return Ok(report);
+    }
+
+    let total_sectors = bytes.len().div_ceil(SECTOR_SIZE);
+    let mut entries = vec![];
+    for slot in 0..CHUNKS_PER_REGION {
+        let Some(location) = bytes.get(slot * 4..slot * 4 + 4) else {
+            issue(
+                slot,
+                Issue::OffsetOutOfBounds {
+                    offset: 0,
+                    sectors: 0,
+                },
+            );
+            break;
+        };
+        let location = u32::from_be_bytes(location.try_into().unwrap());
+        if location == 0 {
+            continue;
+        }
+        let timestamp = bytes
+            .get(SECTOR_SIZE + slot * 4..SECTOR_SIZE + slot * 4 + 4)
+            .map(|e| u32::from_be_bytes(e.try_into().unwrap()))
+            .unwrap_or_default();
+        let (offset, sectors) = ((location >> 8) as usize, (location & 255) as usize);
+        if offset < 2 || sectors == 0 || offset + sectors > total_sectors {
+            issue(slot, Issue::OffsetOutOfBounds { offset, sectors });
+            continue;
+        }
+        let mut entry = Entry {
+            slot,
+            offset,
+            sectors,
+            chunk: None,
+        };
+        let start = offset * SECTOR_SIZE;
+        let length = bytes
+            .get(start..start + 4)
+            .map(|e| u32::from_be_bytes(e.try_into().unwrap()) as usize)
+            .unwrap_or_default();
+        let compression = bytes.get(start + 4).copied().unwrap_or_default();
+        let external = compression & COMPRESSION_EXTERNAL != 0;
+        if length == 0 || length + 4 > sectors * SECTOR_SIZE || start + 4 + length > bytes.len() {
+            issue(slot, Issue::BadLength { length });
+        } else if !matches!(
+            compression & !COMPRESSION_EXTERNAL,
+            COMPRESSION_GZIP
+                | COMPRESSION_ZLIB
+                | COMPRESSION_NONE
+                | COMPRESSION_LZ4
+                | COMPRESSION_CUSTOM
+        ) {
+            issue(slot, Issue::UnknownCompression { compression });
+        } else {
+            let (x, z) = slot_pos(slot);
+            let data = if external {
+                std::fs::read(path.with_file_name(format!("c.{x}.{z}.mcc"))).ok()
+            } else {
+                Some(bytes[start + 5..start + 4 + length].to_vec())
+            };
+            match data {
+                Some(data) => {
+                    entry.chunk = Some(RawChunk {
+                        compression: compression & !COMPRESSION_EXTERNAL,
+                        timestamp,
+                        data,
+                    })
+                }
+                None => issue(slot, Issue::MissingExternal),
+            }
+        }
+        entries.push(entry);
+    }
+
+    // sector ranges are checked separately so a chunk that overlaps but still decodes is kept
+    for (i, a) in entries.iter().enumerate() {
+        for b in entries[..i].iter() {
+            if a.offset < b.offset + b.sectors && b.offset < a.offset + a.sectors {
+                issue(
+                    a.slot,
+                    Issue::Overlap {
+                        other: slot_pos(b.slot),
+                    },
+                );
+            }
+        }
+    }
+
+    let mut good = RegionFile::new();
+    let mut moved = vec![];
+    for entry in entries.iter_mut() {
+        let Some(chunk) = entry.chunk.take() else {
+            continue;
+        };
+        let expected = slot_pos(entry.slot);
+        if chunk.compression == COMPRESSION_CUSTOM {
+            let compression = chunk.compression;
+            issue(entry.slot, Issue::UnsupportedCompression { compression });
+            good.set_chunk(expected.0, expected.1, chunk);
+            continue;
+        }
+        let nbt = match chunk.decode() {
+            Ok(nbt) => nbt,
+            Err(e) => {
+                issue(
+                    entry.slot,
+                    Issue::Undecodable {
+                        error: e.to_string(),
+                    },
+                );
+                continue;
+            }
+        };
+        match chunk_position(&nbt) {
+            Some(found) if found != expected => {
+                issue(entry.slot, Issue::WrongPosition { found });
+                moved.push((expected, found, chunk));
+            }
+            _ => {
+                good.set_chunk(expected.0, expected.1, chunk);
+            }
+        }
+    }
+
+    let unsupported = |e: &ChunkIssue| matches!(e.issue, Issue::UnsupportedCompression { .. });
+    if !repair || report.issues.iter().all(unsupported) {
+        return Ok(report);
+    }
+    for (from, to, chunk) in moved {
+        if (to.0 >> 5, to.1 >> 5) == (rx, rz) && good.chunk(to.0, to.1).is_none() {
+            good.set_chunk(to.0, to.1, chunk);
+            report.repairs.push((from, Repair::Relocated { to }));
+        } else {
+            report.repairs.push((from, Repair::Dropped));
+        }
+    }
+    for issue in report.issues.iter() {
+        let handled = report.repairs.iter().any(|e| e.0 == issue.chunk);
+        if !handled && good.chunk(issue.chunk.0, issue.chunk.1).is_none() {
+            report.repairs.push((issue.chunk, Repair::Dropped));
+        }
+    }
+    std::fs::copy(path, path.with_extension("mca.bak")).map_err(Error::FileError)?;
+    good.save(path)?;
+    Ok(report)
+}
+
+/// position a chunk claims to be at, terrain chunks use xPos and zPos,
+/// entity chunks `Position`
+fn chunk_position(nbt: &NbtValue) -> Option<(i32, i32)> {
+    let root = Chunk::root(nbt);
+    if let (Some(x), Some(z)) = (root.get("xPos"), root.get("zPos")) {
+        return Some((x.as_i64()? as i32, z.as_i64()? as i32));
+    }
+    match root.get("Position") {
+        Some(NbtValue::IntArray(v)) if v.len() == 2 => Some((v[0], v[1])),
+        _ => None,
+    }
+}
+
+impl World {
+    /// validates every region file of `dim`, only regions with issues are reported
+    ///
+    /// repairing saves and closes the open regions first
+    pub fn validate(&self, dim: Dimension, repair: bool) -> Result<Vec<RegionReport>> {
+        if repair {
+            self.check_writable()?;
+            self.save()?;
+            self.clear_cache();
+        }
+        let mut reports = vec![];
+        for kind in RegionKind::ALL {
+            for (x, z) in self.region_positions(dim, kind)? {
+                let report = validate_region(&self.region_path(x, z, dim, kind), repair)?;
+                if !report.issues.is_empty() {
+                    reports.push(report);
+                }
+            }
+        }
+        Ok(reports)
+    }
+}
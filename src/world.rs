@@ -1,60 +1,750 @@
-#[allow(unused)]
 use std::{
-    ffi::OsString,
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    fs::File,
+    hash::Hash,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 
-use crate::NbtValue;
+use serde::Serialize;
+
+use crate::{
+    region::{self, RegionFile, UnreadableChunk},
+    session::SessionLock,
+    Error, NbtFile, NbtValue, Result,
+};
+
+/// regions kept open by default, see [`World::set_cache_size`]
+pub const DEFAULT_CACHED_REGIONS: usize = 16;
+/// decoded chunks kept per region by default
+pub const DEFAULT_CACHED_CHUNKS: usize = 256;
+
+pub(crate) type RegionKey = (Dimension, RegionKind, i32, i32);
+
+/// cache entries with the tick they were last used at
+struct Lru<K, V> {
+    entries: HashMap<K, (u64, V)>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl<K: Copy + Eq + Hash, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    /// marks the entry as most recently used
+    fn get(&mut self, key: K) -> Option<V> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&key)?;
+        entry.0 = self.tick;
+        Some(entry.1.clone())
+    }
+
+    /// inserts `value` unless the key is already there, returns what ends up cached
+    fn insert(&mut self, key: K, value: V) -> V {
+        self.tick += 1;
+        let entry = self.entries.entry(key).or_insert((self.tick, value));
+        entry.0 = self.tick;
+        entry.1.clone()
+    }
+
+    fn replace(&mut self, key: K, value: V) {
+        self.tick += 1;
+        self.entries.insert(key, (self.tick, value));
+    }
+
+    fn remove(&mut self, key: K) {
+        self.entries.remove(&key);
+    }
+
+    fn values(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.entries.iter().map(|(k, v)| (*k, v.1.clone()))
+    }
+
+    /// least recently used entries past the capacity that `evictable` accepts
+    fn excess(&self, evictable: impl Fn(&V) -> bool) -> Vec<(K, V)> {
+        let Some(excess) = self.entries.len().checked_sub(self.capacity) else {
+            return vec![];
+        };
+        let mut entries = self
+            .entries
+            .iter()
+            .filter(|e| evictable(&e.1 .1))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.1 .0);
+        entries
+            .into_iter()
+            .take(excess)
+            .map(|(k, v)| (*k, v.1.clone()))
+            .collect()
+    }
+
+    fn evict(&mut self, evictable: impl Fn(&V) -> bool) {
+        for (key, _) in self.excess(evictable) {
+            self.entries.remove(&key);
+        }
+    }
+}
 
 struct _World {
-    path: OsString,
-    data: Arc<Mutex<NbtValue>>,
+    path: PathBuf,
+    regions: Mutex<Lru<RegionKey, Region>>,
+    cached_chunks: AtomicUsize,
+    /// `None` when opened read only
+    lock: Option<SessionLock>,
+    /// held while a transaction commits
+    commits: Mutex<()>,
 }
 
-struct World(Arc<Mutex<World>>);
+/// a world directory, handles are cheap to clone and can be shared between threads
+///
+/// regions and chunks have their own locks, so threads working on different ones don't
+/// wait on each other and readers of the same chunk share it
+#[derive(Clone)]
+pub struct World(Arc<_World>);
 
 impl World {
-    pub fn get_region(&self, x: i32, y: i32, dim: Dimension) -> Region {
-        todo!()
+    /// opens the world directory at `path` for editing, it has to contain a `level.dat`
+    ///
+    /// takes the `session.lock` like the game does, fails with [`Error::WorldInUse`] while
+    /// a game or server has the world open
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = Self::check_dir(path.as_ref())?;
+        let lock = SessionLock::acquire(&path)?;
+        let world = Self::new(path, Some(lock));
+        world.recover()?;
+        world.remove_uncommitted()?;
+        Ok(world)
+    }
+
+    /// opens the world without locking it, saving anything fails with [`Error::ReadOnly`]
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(Self::check_dir(path.as_ref())?, None))
+    }
+
+    fn check_dir(path: &Path) -> Result<PathBuf> {
+        if !path.join("level.dat").is_file() {
+            return Err(Error::Custom(format!(
+                "{} does not contain a level.dat",
+                path.display()
+            )));
+        }
+        Ok(path.to_path_buf())
+    }
+
+    fn new(path: PathBuf, lock: Option<SessionLock>) -> Self {
+        Self(Arc::new(_World {
+            path,
+            regions: Mutex::new(Lru::new(DEFAULT_CACHED_REGIONS)),
+            cached_chunks: AtomicUsize::new(DEFAULT_CACHED_CHUNKS),
+            lock,
+            commits: Mutex::new(()),
+        }))
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.0.lock.is_none()
+    }
+
+    /// fails with [`Error::ReadOnly`] if the world can't be written to
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        match self.is_read_only() {
+            true => Err(Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// how many regions stay open and how many decoded chunks each of them keeps,
+    /// regions or chunks still held outside the cache, and regions with held chunks, are never
+    /// dropped
+    pub fn set_cache_size(&self, regions: usize, chunks: usize) -> Result<()> {
+        self.0.cached_chunks.store(chunks, Ordering::Relaxed);
+        let open = {
+            let mut cache = self.0.regions.lock().unwrap();
+            cache.capacity = regions;
+            cache.values().collect::<Vec<_>>()
+        };
+        for (_, region) in open {
+            region.0.chunks.lock().unwrap().capacity = chunks;
+            region.evict()?;
+        }
+        self.evict()
     }
 
-    pub fn get_existing_region(&self, x: i32, y: i32, dim: Dimension) -> Option<Region> {
-        todo!()
+    /// saves every open region that changed, returns how many were written
+    ///
+    /// other threads can keep reading while this runs
+    pub fn save(&self) -> Result<usize> {
+        let open = self.0.regions.lock().unwrap().values().collect::<Vec<_>>();
+        let mut saved = 0;
+        for (_, region) in open {
+            if region.is_dirty() {
+                region.save()?;
+                saved += 1;
+            }
+        }
+        Ok(saved)
     }
-    pub fn get_level_dat(&self) -> LevelDat {
-        todo!()
+
+    /// closes every open region, changes that weren't saved are lost
+    pub fn clear_cache(&self) {
+        self.0.regions.lock().unwrap().entries.clear();
+    }
+
+    /// the region as this world sees it, including changes that weren't saved yet
+    pub(crate) fn region_snapshot(&self, (dim, kind, x, z): RegionKey) -> Result<RegionFile> {
+        match self.get_existing_region(x, z, dim, kind)? {
+            Some(region) => {
+                region.flush()?;
+                Ok(region.with_file(RegionFile::clone))
+            }
+            None => Ok(RegionFile::new()),
+        }
+    }
+
+    /// a region outside the cache that only a transaction can write
+    pub(crate) fn staged_region(&self, (dim, kind, x, z): RegionKey, file: RegionFile) -> Region {
+        Region(Arc::new(_Region {
+            staged: true,
+            ..self.new_region(self.region_path(x, z, dim, kind), x, z, file)
+        }))
+    }
+
+    /// closes the region so it is read from disk again next time, handles to it that are
+    /// still held can no longer be saved
+    pub(crate) fn uncache_region(&self, key: RegionKey) {
+        let mut regions = self.0.regions.lock().unwrap();
+        if let Some(region) = regions.get(key) {
+            region.0.stale.store(true, Ordering::SeqCst);
+        }
+        regions.remove(key);
+    }
+
+    pub(crate) fn lock_commits(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.0.commits.lock().unwrap()
+    }
+
+    /// drops least recently used regions nobody else holds until the cache fits,
+    /// changed regions are saved first without holding up other threads
+    ///
+    /// a region stays open while any of its chunks is held, writes through that handle would
+    /// be lost otherwise
+    fn evict(&self) -> Result<()> {
+        let unused = |e: &Region| Arc::strong_count(&e.0) == 1 && !e.has_chunk_handles();
+        let dirty = self.0.regions.lock().unwrap().excess(unused);
+        // read only worlds keep changed regions open instead
+        for (_, region) in dirty.into_iter().filter(|_| !self.is_read_only()) {
+            if region.is_dirty() {
+                region.save()?;
+            }
+        }
+        self.0
+            .regions
+            .lock()
+            .unwrap()
+            .evict(|e| unused(e) && !e.is_dirty());
+        Ok(())
+    }
+
+    /// adds a freshly loaded region, unless another thread got there first
+    fn cache_region(&self, key: RegionKey, region: _Region) -> Result<Region> {
+        let region = self
+            .0
+            .regions
+            .lock()
+            .unwrap()
+            .insert(key, Region(Arc::new(region)));
+        self.evict()?;
+        Ok(region)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.0.path.clone()
+    }
+
+    /// directory holding the region files of `kind` for `dim`
+    pub fn region_dir(&self, dim: Dimension, kind: RegionKind) -> PathBuf {
+        dim.dir(&self.path()).join(kind.dir_name())
+    }
+
+    pub fn region_path(&self, x: i32, z: i32, dim: Dimension, kind: RegionKind) -> PathBuf {
+        self.region_dir(dim, kind)
+            .join(region::region_file_name(x, z))
+    }
+
+    /// coordinates of every region file of `kind` in `dim`
+    pub fn region_positions(&self, dim: Dimension, kind: RegionKind) -> Result<Vec<(i32, i32)>> {
+        let dir = self.region_dir(dim, kind);
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut positions = std::fs::read_dir(dir)
+            .map_err(Error::FileError)?
+            .map(|e| e.map(|e| region::region_coords(&e.path())))
+            .filter_map(|e| e.transpose())
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(Error::FileError)?;
+        positions.sort();
+        Ok(positions)
+    }
+
+    /// absolute coordinates of every chunk stored in a region of `kind`, without decoding them
+    pub fn chunk_positions(&self, dim: Dimension, kind: RegionKind) -> Result<Vec<(i32, i32)>> {
+        let mut chunks = vec![];
+        for (rx, rz) in self.region_positions(dim, kind)? {
+            if let Some(region) = self.get_existing_region(rx, rz, dim, kind)? {
+                chunks.extend(
+                    region
+                        .chunk_positions()
+                        .into_iter()
+                        .map(|(x, z)| (rx * 32 + x, rz * 32 + z)),
+                );
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// the region, or an empty one if the file doesn't exist yet
+    pub fn get_region(&self, x: i32, z: i32, dim: Dimension, kind: RegionKind) -> Result<Region> {
+        if let Some(region) = self.get_existing_region(x, z, dim, kind)? {
+            return Ok(region);
+        }
+        let path = self.region_path(x, z, dim, kind);
+        self.cache_region(
+            (dim, kind, x, z),
+            self.new_region(path, x, z, RegionFile::new()),
+        )
+    }
+
+    /// the region if it is open or its file exists, regions are cached so changes made
+    /// through one handle are seen by all of them
+    pub fn get_existing_region(
+        &self,
+        x: i32,
+        z: i32,
+        dim: Dimension,
+        kind: RegionKind,
+    ) -> Result<Option<Region>> {
+        let key = (dim, kind, x, z);
+        if let Some(region) = self.0.regions.lock().unwrap().get(key) {
+            return Ok(Some(region));
+        }
+        let path = self.region_path(x, z, dim, kind);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        // the world stays unlocked while the file is read
+        let file = RegionFile::open(&path)?;
+        self.cache_region(key, self.new_region(path, x, z, file))
+            .map(Some)
+    }
+
+    pub fn get_level_dat(&self) -> Result<LevelDat> {
+        let path = self.path().join("level.dat");
+        let file = NbtFile::from_compressed_reader(File::open(&path).map_err(Error::FileError)?)?;
+        Ok(LevelDat(Arc::new(RwLock::new(_LevelDat { path, file }))))
+    }
+
+    fn new_region(&self, path: PathBuf, x: i32, z: i32, file: RegionFile) -> _Region {
+        _Region {
+            path,
+            x,
+            z,
+            file: RwLock::new(file),
+            chunks: Mutex::new(Lru::new(self.0.cached_chunks.load(Ordering::Relaxed))),
+            dirty: AtomicBool::new(false),
+            read_only: self.is_read_only(),
+            staged: false,
+            stale: AtomicBool::new(false),
+        }
+    }
+
+    /// uuid and path of every file in `playerdata`
+    pub fn player_files(&self) -> Result<Vec<(String, PathBuf)>> {
+        let dir = self.path().join("playerdata");
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut players = vec![];
+        for entry in std::fs::read_dir(dir).map_err(Error::FileError)? {
+            let path = entry.map_err(Error::FileError)?.path();
+            if path.extension().is_some_and(|e| e == "dat") {
+                if let Some(uuid) = path.file_stem().and_then(|e| e.to_str()) {
+                    players.push((uuid.to_string(), path.clone()));
+                }
+            }
+        }
+        players.sort();
+        Ok(players)
     }
 }
 
 struct _Region {
-    data: Arc<Mutex<NbtValue>>,
+    path: PathBuf,
+    x: i32,
+    z: i32,
+    file: RwLock<RegionFile>,
+    /// decoded chunks by local position
+    chunks: Mutex<Lru<(i32, i32), Chunk>>,
+    /// the file differs from what is on disk
+    dirty: AtomicBool,
+    read_only: bool,
+    /// belongs to a transaction, which writes it on commit
+    staged: bool,
+    /// a transaction replaced the file, saving would undo its changes
+    stale: AtomicBool,
 }
 
-impl _Region {
-    fn wrapped(self) -> Region {
-        Region(Arc::new(Mutex::new(self)))
-    }
-}
+// locks are always taken in the order file, chunk, chunk cache
 
-struct Region(Arc<Mutex<_Region>>);
+#[derive(Clone)]
+pub struct Region(Arc<_Region>);
 
 impl Region {
-    /// panics if x or y > 31
-    fn get_chunk(&self, x: u8, y: u8) -> Chunk {
-        todo!()
+    pub fn path(&self) -> PathBuf {
+        self.0.path.clone()
+    }
+
+    /// region coordinates
+    pub fn position(&self) -> (i32, i32) {
+        (self.0.x, self.0.z)
+    }
+
+    /// local positions (0..32) of the chunks in this region
+    pub fn chunk_positions(&self) -> Vec<(i32, i32)> {
+        self.0.file.read().unwrap().positions().collect()
+    }
+
+    /// chunks the header points at that couldn't be read when the region was opened
+    pub fn unreadable_chunks(&self) -> Vec<UnreadableChunk> {
+        self.0.file.read().unwrap().unreadable().to_vec()
+    }
+
+    /// the chunk at local position x, z, decoded chunks are cached so every handle
+    /// to it sees the same nbt
+    ///
+    /// panics if x or z > 31
+    pub fn get_chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>> {
+        assert!((0..32).contains(&x) && (0..32).contains(&z));
+        if let Some(chunk) = self.0.chunks.lock().unwrap().get((x, z)) {
+            return Ok(Some(chunk));
+        }
+        let chunk = {
+            // decoding only needs a shared lock, the file can't change until it's cached
+            let file = self.0.file.read().unwrap();
+            let Some(nbt) = file.read_chunk(x, z)? else {
+                return Ok(None);
+            };
+            self.0
+                .chunks
+                .lock()
+                .unwrap()
+                .insert((x, z), Chunk::new(nbt))
+        };
+        self.evict()?;
+        Ok(Some(chunk))
+    }
+
+    /// encodes `chunk` into the region, stamped with the current time
+    pub fn set_chunk(&self, x: i32, z: i32, chunk: &Chunk) -> Result<()> {
+        {
+            let mut file = self.0.file.write().unwrap();
+            chunk.0.dirty.store(false, Ordering::SeqCst);
+            chunk.read(|nbt| file.write_chunk(x, z, nbt, unix_time()))?;
+            self.0.dirty.store(true, Ordering::SeqCst);
+            self.0.chunks.lock().unwrap().replace((x, z), chunk.clone());
+        }
+        self.evict()
+    }
+
+    pub fn remove_chunk(&self, x: i32, z: i32) -> bool {
+        let mut file = self.0.file.write().unwrap();
+        self.0.chunks.lock().unwrap().remove((x, z));
+        let removed = file.remove_chunk(x, z).is_some();
+        if removed {
+            self.0.dirty.store(true, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// the underlying file, chunks changed through their handle are only in it after
+    /// [`Region::save`]
+    pub fn with_file<R>(&self, f: impl FnOnce(&RegionFile) -> R) -> R {
+        f(&self.0.file.read().unwrap())
+    }
+
+    /// true when the region has changes that weren't saved
+    pub fn is_dirty(&self) -> bool {
+        self.0.dirty.load(Ordering::SeqCst)
+            || self
+                .0
+                .chunks
+                .lock()
+                .unwrap()
+                .values()
+                .any(|e| e.1.is_dirty())
+    }
+
+    /// writes the region back to disk, an empty region deletes the file instead
+    ///
+    /// readers aren't blocked while the file is written
+    pub fn save(&self) -> Result<()> {
+        if self.0.read_only {
+            return Err(Error::ReadOnly);
+        }
+        if self.0.staged {
+            return Err(Error::Custom(
+                "the region belongs to a transaction, commit it instead".to_string(),
+            ));
+        }
+        if self.0.stale.load(Ordering::SeqCst) {
+            return Err(Error::Custom(
+                "a transaction replaced the region since it was opened, get it again".to_string(),
+            ));
+        }
+        self.flush()?;
+        let file = self.0.file.read().unwrap();
+        self.0.dirty.store(false, Ordering::SeqCst);
+        let saved = if file.is_empty() {
+            if self.0.path.is_file() {
+                std::fs::remove_file(&self.0.path).map_err(Error::FileError)
+            } else {
+                Ok(())
+            }
+        } else {
+            self.0
+                .path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .map_err(Error::FileError)
+                .and_then(|_| file.save(&self.0.path))
+        };
+        if saved.is_err() {
+            self.0.dirty.store(true, Ordering::SeqCst);
+        }
+        saved
+    }
+
+    /// encodes every chunk changed through its handle back into the file
+    pub(crate) fn flush(&self) -> Result<()> {
+        let dirty = self
+            .0
+            .chunks
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.1.is_dirty())
+            .collect::<Vec<_>>();
+        self.write_back(&dirty)
+    }
+
+    /// encodes chunks changed through their handle back into the file
+    fn write_back(&self, chunks: &[((i32, i32), Chunk)]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let mut file = self.0.file.write().unwrap();
+        for ((x, z), chunk) in chunks {
+            // cleared first so a write that happens meanwhile marks it again
+            if chunk.0.dirty.swap(false, Ordering::SeqCst) {
+                if let Err(e) = chunk.read(|nbt| file.write_chunk(*x, *z, nbt, unix_time())) {
+                    chunk.0.dirty.store(true, Ordering::SeqCst);
+                    return Err(e);
+                }
+                self.0.dirty.store(true, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    }
+
+    /// true when a chunk of this region is held outside its cache
+    fn has_chunk_handles(&self) -> bool {
+        self.0
+            .chunks
+            .lock()
+            .unwrap()
+            .entries
+            .values()
+            .any(|e| Arc::strong_count(&e.1 .0) > 1)
+    }
+
+    /// drops least recently used chunks nobody else holds until the cache fits
+    fn evict(&self) -> Result<()> {
+        let unused = |e: &Chunk| Arc::strong_count(&e.0) == 1;
+        let excess = self.0.chunks.lock().unwrap().excess(unused);
+        self.write_back(&excess)?;
+        drop(excess);
+        self.0
+            .chunks
+            .lock()
+            .unwrap()
+            .evict(|e| unused(e) && !e.is_dirty());
+        Ok(())
     }
 }
 
-enum Dimension {
+/// seconds since the epoch, as stored in region timestamps
+pub fn unix_time() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|e| e.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Dimension {
     Overworld,
     Nether,
     End,
 }
 
-struct _LevelDat {}
+impl Dimension {
+    pub const ALL: [Dimension; 3] = [Dimension::Overworld, Dimension::Nether, Dimension::End];
+
+    /// root directory of the dimension inside the world directory
+    pub fn dir(&self, world: &Path) -> PathBuf {
+        match self {
+            Dimension::Overworld => world.to_path_buf(),
+            Dimension::Nether => world.join("DIM-1"),
+            Dimension::End => world.join("DIM1"),
+        }
+    }
+
+    /// lowest block y in vanilla
+    pub fn min_y(&self) -> i32 {
+        match self {
+            Dimension::Overworld => -64,
+            _ => 0,
+        }
+    }
+
+    /// blocks from `min_y` to the build limit
+    pub fn height(&self) -> i32 {
+        match self {
+            Dimension::Overworld => 384,
+            _ => 256,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Dimension::Overworld => "minecraft:overworld",
+            Dimension::Nether => "minecraft:the_nether",
+            Dimension::End => "minecraft:the_end",
+        }
+    }
+}
+
+impl FromStr for Dimension {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.strip_prefix("minecraft:").unwrap_or(s) {
+            "overworld" => Ok(Dimension::Overworld),
+            "the_nether" | "nether" => Ok(Dimension::Nether),
+            "the_end" | "end" => Ok(Dimension::End),
+            _ => Err(Error::Custom(format!("unknown dimension {s}"))),
+        }
+    }
+}
+
+/// the three kinds of region files every dimension has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum RegionKind {
+    /// terrain and block entities, `region/`
+    Chunks,
+    /// `entities/`
+    Entities,
+    /// points of interest, `poi/`
+    Poi,
+}
+
+impl RegionKind {
+    pub const ALL: [RegionKind; 3] = [RegionKind::Chunks, RegionKind::Entities, RegionKind::Poi];
 
-pub struct LevelDat(Arc<Mutex<_LevelDat>>);
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            RegionKind::Chunks => "region",
+            RegionKind::Entities => "entities",
+            RegionKind::Poi => "poi",
+        }
+    }
+}
 
-struct _Chunk {}
+struct _LevelDat {
+    path: PathBuf,
+    file: NbtFile,
+}
 
-pub struct Chunk(Arc<Mutex<_Chunk>>);
+#[derive(Clone)]
+pub struct LevelDat(Arc<RwLock<_LevelDat>>);
+
+impl LevelDat {
+    pub fn path(&self) -> PathBuf {
+        self.0.read().unwrap().path.clone()
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&NbtValue) -> R) -> R {
+        f(&self.0.read().unwrap().file.nbt)
+    }
+
+    /// the `Data` compound most values live in
+    pub fn data<R>(&self, f: impl FnOnce(Option<&NbtValue>) -> R) -> R {
+        self.read(|nbt| f(nbt.get("Data")))
+    }
+}
+
+struct _Chunk {
+    nbt: RwLock<NbtValue>,
+    /// set by [`Chunk::write`], cleared once the region has the change
+    dirty: AtomicBool,
+}
+
+#[derive(Clone)]
+pub struct Chunk(Arc<_Chunk>);
+
+impl Chunk {
+    pub fn new(nbt: NbtValue) -> Self {
+        Self(Arc::new(_Chunk {
+            nbt: RwLock::new(nbt),
+            dirty: AtomicBool::new(false),
+        }))
+    }
+
+    /// any number of threads can read a chunk at the same time
+    pub fn read<R>(&self, f: impl FnOnce(&NbtValue) -> R) -> R {
+        f(&self.0.nbt.read().unwrap())
+    }
+
+    /// changes the nbt and marks the chunk as changed
+    pub fn write<R>(&self, f: impl FnOnce(&mut NbtValue) -> R) -> R {
+        let mut nbt = self.0.nbt.write().unwrap();
+        self.0.dirty.store(true, Ordering::SeqCst);
+        f(&mut nbt)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.0.dirty.load(Ordering::SeqCst)
+    }
+
+    /// the compound holding the chunk data, `Level` in pre 1.18 chunks
+    pub fn root(nbt: &NbtValue) -> &NbtValue {
+        nbt.get("Level").unwrap_or(nbt)
+    }
+
+    pub fn root_mut(nbt: &mut NbtValue) -> &mut NbtValue {
+        if nbt.get("Level").is_some() {
+            return nbt.get_mut("Level").unwrap();
+        }
+        nbt
+    }
+}